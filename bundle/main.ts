@@ -1,12 +1,15 @@
 import { colors, rollup, streamUtils } from './deps.ts'
 import { flush, print } from './print.ts'
 
-type Input = [string, { resolutions: Record<string, Record<string, string>>; source_files: Record<string, string> }]
+type Input = [
+	string,
+	{ resolutions: Record<string, Record<string, string>>; source_files: Record<string, string>; sources: Record<string, string> },
+]
 
 const entryId = 'entry://default'
 
 const unknownInput = await streamUtils.toJson(Deno.stdin.readable)
-const [entryCode, { resolutions, source_files: sourceFiles }] = unknownInput as Input
+const [entryCode, { resolutions, source_files: sourceFiles, sources }] = unknownInput as Input
 
 const build = await rollup.rollup({
 	input: entryId,
@@ -28,6 +31,10 @@ const build = await rollup.rollup({
 		async load(id) {
 			if (id === entryId) return entryCode
 
+			// sources that have been loaded or transformed by plugins take precedence over the source files
+			const source = sources[id]
+			if (source !== undefined) return source
+
 			const sourceFile = sourceFiles[id]
 			if (!sourceFile) throw new Error(`Encountered an id "${id}", for which no source file was provided`)
 
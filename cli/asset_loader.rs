@@ -1,23 +1,46 @@
 use anyhow::{anyhow, bail, Context, Result};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use serde_json::{from_str, from_value, Value};
 use sha2::{Digest, Sha256};
-use std::collections::{HashMap, HashSet};
-use tokio::fs::read_to_string;
+use std::{
+	collections::{HashMap, HashSet},
+	num::NonZeroUsize,
+	sync::Arc,
+	time::{Duration, Instant},
+};
+use tokio::{fs::read_to_string, sync::Semaphore, task::JoinSet, time::sleep};
 use url::Url;
 
 use crate::{
 	diagnostic::{Diagnostic, DiagnosticList},
 	module_loader::InfoGraph,
-	writer::Writer,
+	writer::{is_transient_download_error, BuildCache, Writer},
 };
 
+/// The number of assets that are downloaded at once, unless `AssetsLoaderWriteOptions::concurrency` says otherwise
+pub const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 8;
+
+/// The number of times that a download is retried after a timeout or connection error, unless `AssetsLoaderWriteOptions::retries`
+/// says otherwise
+pub const DEFAULT_DOWNLOAD_RETRIES: u32 = 3;
+
+/// The delay before the first retry of a download, which doubles with each retry, unless `AssetsLoaderWriteOptions::retry_delay`
+/// says otherwise
+pub const DEFAULT_DOWNLOAD_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// How often the progress of writing assets is logged, when no `AssetsLoaderWriteOptions::on_progress` callback is set
+const PROGRESS_LOG_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct RawAsset {
 	sha256: String,
 	local_path: String,
 	web_path: String,
+	/// Headers to send when downloading this asset, overriding any that were configured globally
+	#[serde(default)]
+	headers: HashMap<String, String>,
 }
 
 #[derive(Debug)]
@@ -25,18 +48,36 @@ struct Asset {
 	sha256: Vec<u8>,
 	url: Url,
 	web_path: String,
+	headers: HashMap<String, String>,
 }
 
 impl Asset {
-	pub fn from_json(index_url: &Url, value: Value) -> Result<Asset> {
+	/// Relative local paths are resolved against `asset_root` if it is set, and against the index otherwise. Absolute paths and urls
+	/// are always resolved against the index.
+	pub fn from_json(index_url: &Url, asset_root: Option<&Url>, value: Value) -> Result<Asset> {
 		let raw = from_value::<RawAsset>(value)?;
-		let url = index_url
+		let is_relative = Url::parse(&raw.local_path).is_err() && !raw.local_path.starts_with('/');
+		let base_url = match asset_root {
+			Some(asset_root) if is_relative => asset_root,
+			_ => index_url,
+		};
+		let url = base_url
 			.join(&raw.local_path)
-			.with_context(|| format!("failed to join local path '{}' to index url '{index_url}'", raw.local_path))?;
+			.with_context(|| format!("failed to join local path '{}' to '{base_url}'", raw.local_path))?;
 		let sha256 = hex::decode(raw.sha256).context("sha256 is not encoded as valid hexidecimal")?;
+
+		if sha256.len() != 32 {
+			bail!("sha256 should be 32 bytes, but it is {} bytes", sha256.len());
+		}
+
 		let web_path = normalize_web_path(&raw.web_path);
 
-		Ok(Asset { sha256, url, web_path })
+		Ok(Asset {
+			sha256,
+			url,
+			web_path,
+			headers: raw.headers,
+		})
 	}
 }
 
@@ -51,14 +92,47 @@ pub enum AssetKind {
 #[derive(Debug, Default)]
 pub struct AssetsLoader {
 	indexes: Vec<Url>,
+	/// The url that relative local paths are resolved against. See `AssetsLoader::set_asset_root`
+	asset_root: Option<Url>,
 	web_paths: HashSet<String>,
 	assets: Vec<Asset>,
 }
 
-#[derive(Debug, Default)]
-pub struct AssetsLoaderWriteOptions {
+#[derive(Default)]
+pub struct AssetsLoaderWriteOptions<'a> {
 	pub kind: AssetKind,
 	pub hash_url: bool,
+	/// Headers to send with every remote download. Per-asset headers from the index take precedence. Never sent for `file:` assets.
+	pub headers: &'a [(String, String)],
+	/// If set, assets that the cache shows are already in place are not re-hashed, and every written asset is recorded
+	pub cache: Option<&'a mut BuildCache>,
+	/// The maximum number of assets that are checked and downloaded at once. Defaults to `DEFAULT_DOWNLOAD_CONCURRENCY`
+	pub concurrency: Option<NonZeroUsize>,
+	/// The number of times that a download is retried after a timeout or connection error. Defaults to `DEFAULT_DOWNLOAD_RETRIES`
+	pub retries: Option<u32>,
+	/// The delay before the first retry, which doubles with each retry. Defaults to `DEFAULT_DOWNLOAD_RETRY_DELAY`
+	pub retry_delay: Option<Duration>,
+	/// Called each time that an asset has been processed, so that a progress bar can be rendered. If unset, the progress is logged
+	/// periodically instead.
+	pub on_progress: Option<&'a (dyn Fn(AssetProgress) + Sync)>,
+}
+
+/// How far `AssetsLoader::write` has gotten. Assets that were already in place count as processed, but transfer no bytes. Assets
+/// that the cache shows are in place are counted as processed before anything is downloaded.
+#[derive(Debug, Clone, Copy)]
+pub struct AssetProgress {
+	pub processed: usize,
+	pub total: usize,
+	/// The number of bytes that were read from the assets' sources, excluding the bytes of resumed partial downloads that were already
+	/// on disk
+	pub bytes_transferred: u64,
+}
+
+/// How a failed download is retried. See `AssetsLoaderWriteOptions::retries`
+#[derive(Debug, Clone, Copy)]
+struct RetryPolicy {
+	retries: u32,
+	base_delay: Duration,
 }
 
 impl AssetsLoader {
@@ -66,17 +140,89 @@ impl AssetsLoader {
 		self.indexes.push(url.into());
 	}
 
-	pub async fn load(&mut self, diagnostic_list: &mut DiagnosticList) -> Result<()> {
-		for index_url in &self.indexes {
-			let mut assets = match load_index(&index_url).await {
-				Ok(assets) => assets,
+	/// Resolve the relative local paths of every index against `asset_root`, rather than against the index's directory. Useful when
+	/// the index is generated somewhere other than where the assets live. `asset_root` is treated as a directory.
+	pub fn set_asset_root(&mut self, mut asset_root: Url) {
+		if !asset_root.path().ends_with('/') {
+			let path = format!("{}/", asset_root.path());
+			asset_root.set_path(&path);
+		}
+
+		self.asset_root = Some(asset_root);
+	}
+
+	/// Load every registered index. Indexes are fetched concurrently, each holding a permit from `semaphore`, but their assets are
+	/// registered in the order that the indexes were registered.
+	pub async fn load(&mut self, diagnostic_list: &mut DiagnosticList, semaphore: &Arc<Semaphore>) -> Result<()> {
+		let mut tasks = JoinSet::new();
+
+		for (order, index_url) in self.indexes.iter().cloned().enumerate() {
+			let semaphore = semaphore.clone();
+			let asset_root = self.asset_root.clone();
+
+			tasks.spawn(async move {
+				let _permit = semaphore.acquire_owned().await.expect("the build semaphore is never closed");
+				let assets = load_index(&index_url, asset_root.as_ref()).await;
+
+				(order, index_url, assets)
+			});
+		}
+
+		let mut results = Vec::with_capacity(tasks.len());
+
+		while let Some(result) = tasks.join_next().await {
+			results.push(result.context("failed to join the task that loads an asset index")?);
+		}
+
+		results.sort_by_key(|(order, _, _)| *order);
+
+		// every index is validated before any assets are registered, so that all problems are reported at once
+		let mut hashes = HashMap::<Url, (&[u8], &Url)>::new();
+
+		for (_, index_url, result) in &results {
+			let (assets, problems) = match result {
+				Ok(result) => result,
 				Err(error) => {
-					println!("{}", error);
-					diagnostic_list.add_error(error.context(format!("Failed to load asset index at {index_url}")));
+					diagnostic_list.add(
+						Diagnostic::start("Failed to load asset index at ")
+							.text(index_url)
+							.shift()
+							.text(format!("{error:#}"))
+							.build(),
+					);
 					continue;
 				}
 			};
 
+			for problem in problems {
+				diagnostic_list.add(Diagnostic::start("Invalid asset index at ").text(index_url).shift().text(problem).build());
+			}
+
+			for asset in assets {
+				match hashes.get(&asset.url) {
+					Some((existing_hash, existing_index_url)) if *existing_hash != asset.sha256.as_slice() => diagnostic_list.add(
+						Diagnostic::start("The asset at ")
+							.text(&asset.url)
+							.text(" is declared with different sha256 hashes by ")
+							.text(existing_index_url)
+							.text(" and ")
+							.text(index_url)
+							.build(),
+					),
+					Some(_) => (),
+					None => {
+						hashes.insert(asset.url.clone(), (&asset.sha256, index_url));
+					}
+				}
+			}
+		}
+
+		for (_, index_url, result) in results {
+			let mut assets = match result {
+				Ok((assets, _)) => assets,
+				Err(_) => continue,
+			};
+
 			for (asset_index, asset) in assets.drain(..).enumerate() {
 				if self.web_paths.contains(&asset.web_path) {
 					diagnostic_list.add(
@@ -99,17 +245,47 @@ impl AssetsLoader {
 		Ok(())
 	}
 
-	pub async fn write(&self, writer: &Writer, diagnostic_list: &mut DiagnosticList, options: AssetsLoaderWriteOptions) -> Result<()> {
+	/// Warn about indexed assets whose web paths are never referenced by the bundled runtime, and about web paths that the runtime
+	/// references, but that no index declares. References are found by looking for string literals that look like asset paths, so
+	/// dynamically constructed paths are not detected.
+	pub fn check_references(&self, client_bundle: &str) {
+		let (unused, unindexed) = compare_references(&self.web_paths, client_bundle);
+
+		for web_path in unused {
+			Diagnostic::start("Asset ")
+				.inline_code(web_path)
+				.text(" is indexed, but is never referenced by the runtime")
+				.build()
+				.print_warn();
+		}
+
+		for web_path in unindexed {
+			Diagnostic::start("The runtime references ")
+				.inline_code(web_path)
+				.text(", but no asset index declares that web path")
+				.build()
+				.print_warn();
+		}
+	}
+
+	/// Write every asset of `options.kind` into `writer`'s directory. Up to `options.concurrency` assets are checked and downloaded at
+	/// once, but failures are reported in the order of the assets, so that the diagnostics are the same from build to build.
+	pub async fn write(&self, writer: &Writer, diagnostic_list: &mut DiagnosticList, mut options: AssetsLoaderWriteOptions<'_>) -> Result<()> {
 		let allow_all_schemes = options.kind == AssetKind::All;
 		let allow_file_scheme = allow_all_schemes || options.kind == AssetKind::Local;
 		let allow_other_schemes = allow_all_schemes || options.kind == AssetKind::Remote;
+		let concurrency = options.concurrency.map(NonZeroUsize::get).unwrap_or(DEFAULT_DOWNLOAD_CONCURRENCY);
+		let semaphore = Arc::new(Semaphore::new(concurrency));
+		let retry_policy = RetryPolicy {
+			retries: options.retries.unwrap_or(DEFAULT_DOWNLOAD_RETRIES),
+			base_delay: options.retry_delay.unwrap_or(DEFAULT_DOWNLOAD_RETRY_DELAY),
+		};
+		let mut tasks = JoinSet::new();
+		let mut fresh_count = 0;
 
-		for asset in &self.assets {
+		for (order, asset) in self.assets.iter().enumerate() {
 			let path = if options.hash_url {
-				let mut sha = Sha256::new();
-				sha.update(asset.url.to_string().as_bytes());
-
-				hex::encode(sha.finalize())
+				get_hashed_path(&asset.url)
 			} else {
 				asset.url.to_string()
 			};
@@ -122,43 +298,90 @@ impl AssetsLoader {
 				continue;
 			}
 
-			if let Ok(actual_sha) = writer.get_sha256(&path).await {
-				if &asset.sha256 == &actual_sha {
-					continue;
-				}
+			let full_path = writer.get_full_path(&path);
+
+			if options
+				.cache
+				.as_deref()
+				.map(|cache| cache.is_fresh(&full_path, &asset.sha256))
+				.unwrap_or_default()
+			{
+				fresh_count += 1;
+				continue;
 			}
 
-			let download_res = writer.download_file(&path, &asset.url).await;
+			let mut headers = options.headers.iter().cloned().collect::<HashMap<_, _>>();
+			headers.extend(asset.headers.iter().map(|(name, value)| (name.clone(), value.clone())));
 
-			let downloaded_hash = match download_res {
-				Ok(hash) => hash,
-				Err(error) => {
-					diagnostic_list.add_error(error.context(format!("Failed to download {}", asset.url)));
-					continue;
-				}
-			};
+			let semaphore = semaphore.clone();
+			let writer = writer.clone();
+			let url = asset.url.clone();
+			let sha256 = asset.sha256.clone();
 
-			if downloaded_hash != asset.sha256 {
-				diagnostic_list.add(
-					Diagnostic::start("After being download, the expected hash in the asset index does not match the actual hash of the file")
-						.shift()
-						.text(&asset.url)
-						.build(),
-				);
-				continue;
+			tasks.spawn(async move {
+				let _permit = semaphore.acquire_owned().await.expect("the download semaphore is never closed");
+				let outcome = write_asset(&writer, &path, &url, &sha256, &headers, retry_policy).await;
+
+				(order, full_path, outcome)
+			});
+		}
+
+		let mut results = Vec::with_capacity(tasks.len());
+
+		let mut progress = AssetProgress {
+			processed: fresh_count,
+			total: fresh_count + tasks.len(),
+			bytes_transferred: 0,
+		};
+		let mut last_logged = Instant::now();
+
+		if fresh_count > 0 {
+			report_progress(options.on_progress, progress, &mut last_logged);
+		}
+
+		while let Some(result) = tasks.join_next().await {
+			let (order, full_path, outcome) = result.context("failed to join the task that writes an asset")?;
+
+			progress.processed += 1;
+
+			if let Ok(bytes) = &outcome {
+				progress.bytes_transferred += bytes;
+			}
+
+			report_progress(options.on_progress, progress, &mut last_logged);
+
+			results.push((order, full_path, outcome));
+		}
+
+		results.sort_by_key(|(order, _, _)| *order);
+
+		for (order, full_path, outcome) in results {
+			match outcome {
+				Ok(_) => {
+					if let Some(cache) = options.cache.as_deref_mut() {
+						cache.record(full_path, &self.assets[order].sha256);
+					}
+				}
+				Err(diagnostic) => diagnostic_list.add(diagnostic),
 			}
 		}
 
 		Ok(())
 	}
 
-	pub async fn download(self, cache_writer: &Writer, diagnostic_list: &mut DiagnosticList) -> Result<AccessibleAssets> {
+	pub async fn download(self, cache_writer: &Writer, diagnostic_list: &mut DiagnosticList, headers: &[(String, String)]) -> Result<AccessibleAssets> {
 		self.write(
 			cache_writer,
 			diagnostic_list,
 			AssetsLoaderWriteOptions {
 				kind: AssetKind::Remote,
 				hash_url: true,
+				headers,
+				cache: None,
+				concurrency: None,
+				retries: None,
+				retry_delay: None,
+				on_progress: None,
 			},
 		)
 		.await?;
@@ -171,12 +394,7 @@ impl AssetsLoader {
 					if asset.url.scheme() == "file" {
 						asset.url.path().to_string()
 					} else {
-						let path = {
-							let mut hasher = Sha256::new();
-							hasher.update(asset.url.to_string());
-							hex::encode(hasher.finalize())
-						};
-						cache_writer.get_full_path(path).into_os_string().into_string().unwrap()
+						cache_writer.get_full_path(get_hashed_path(&asset.url)).into_os_string().into_string().unwrap()
 					}
 				})
 			})
@@ -186,17 +404,320 @@ impl AssetsLoader {
 	}
 }
 
+/// Pass `progress` to `on_progress`, or log it if that is unset and it has been a while since the last time that it was logged
+fn report_progress(on_progress: Option<&(dyn Fn(AssetProgress) + Sync)>, progress: AssetProgress, last_logged: &mut Instant) {
+	match on_progress {
+		Some(on_progress) => on_progress(progress),
+		None if progress.processed == progress.total || last_logged.elapsed() >= PROGRESS_LOG_INTERVAL => {
+			info!(
+				"Processed {} of {} assets ({} bytes transferred)",
+				progress.processed, progress.total, progress.bytes_transferred
+			);
+			*last_logged = Instant::now();
+		}
+		None => (),
+	}
+}
+
+/// Make sure that the file at `path` has the hash `sha256`, downloading it from `url` if it doesn't. Transient download failures are
+/// retried according to `retry_policy`. Returns the number of bytes that were downloaded, or the problem that prevented the file from
+/// being written. An asset that is already in place transfers no bytes.
+async fn write_asset(
+	writer: &Writer,
+	path: &str,
+	url: &Url,
+	sha256: &[u8],
+	headers: &HashMap<String, String>,
+	retry_policy: RetryPolicy,
+) -> Result<u64, Diagnostic> {
+	if let Ok(actual_sha) = writer.get_sha256(path).await {
+		if sha256 == actual_sha {
+			return Ok(0);
+		}
+	}
+
+	let mut retry = 0;
+
+	let download = loop {
+		match writer.download_file(path, url, headers).await {
+			Ok(download) => break download,
+			Err(error) if retry < retry_policy.retries && is_transient_download_error(&error) => {
+				let delay = retry_policy.base_delay.saturating_mul(2_u32.saturating_pow(retry));
+
+				warn!("{error:#}");
+				info!("Retrying the download of {url} in {delay:?} (retry {} of {})", retry + 1, retry_policy.retries);
+
+				sleep(delay).await;
+				retry += 1;
+			}
+			Err(error) => return Err(Diagnostic::from_error(error.context(format!("Failed to download {url}")))),
+		}
+	};
+
+	if download.sha256 != sha256 {
+		return Err(
+			Diagnostic::start("After being download, the expected hash in the asset index does not match the actual hash of the file")
+				.shift()
+				.text(url)
+				.build(),
+		);
+	}
+
+	Ok(download.bytes_transferred)
+}
+
 #[derive(Debug, Clone)]
 pub struct AccessibleAssets {
 	index: HashMap<String, String>,
 }
 
 impl AccessibleAssets {
+	/// Assets that are served from the local files of `index`, which is keyed by web path
+	#[cfg(test)]
+	pub fn new(index: HashMap<String, String>) -> AccessibleAssets {
+		AccessibleAssets { index }
+	}
+
 	pub fn get_local_path(&self, web_path: &str) -> Option<&str> {
 		self.index.get(web_path).map(|inner| inner.as_str())
 	}
 }
 
+/// The name that a remote asset is cached under. The extension of the url is kept, so that the content type of the cached file can be
+/// inferred when it is served.
+fn get_hashed_path(url: &Url) -> String {
+	let mut hasher = Sha256::new();
+	hasher.update(url.to_string().as_bytes());
+	let hash = hex::encode(hasher.finalize());
+
+	let file_name = url.path_segments().and_then(|segments| segments.last()).unwrap_or_default();
+
+	match file_name.rsplit_once('.') {
+		Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() => format!("{hash}.{extension}"),
+		_ => hash,
+	}
+}
+
+/// Compare the indexed `web_paths` to the ones that `client_bundle` references. Returns the indexed paths that are never referenced, and
+/// the referenced paths that are not indexed, both sorted.
+fn compare_references<'a>(web_paths: &'a HashSet<String>, client_bundle: &str) -> (Vec<&'a String>, Vec<String>) {
+	let referenced = find_referenced_web_paths(client_bundle);
+
+	let mut unused = web_paths.iter().filter(|web_path| !referenced.contains(*web_path)).collect::<Vec<_>>();
+	unused.sort();
+
+	let mut unindexed = referenced.into_iter().filter(|web_path| !web_paths.contains(web_path)).collect::<Vec<_>>();
+	unindexed.sort();
+
+	(unused, unindexed)
+}
+
+/// Find the string literals in `code` that look like asset web paths: absolute paths that end in a file extension
+fn find_referenced_web_paths(code: &str) -> HashSet<String> {
+	let mut paths = HashSet::new();
+
+	for literal in get_string_literals(code) {
+		if !literal.starts_with('/') || literal.starts_with("//") {
+			continue;
+		}
+
+		let has_extension = literal
+			.rsplit_once('/')
+			.and_then(|(_, file_name)| file_name.rsplit_once('.'))
+			.map(|(stem, extension)| !stem.is_empty() && !extension.is_empty() && extension.chars().all(|char| char.is_ascii_alphanumeric()))
+			.unwrap_or_default();
+
+		if has_extension && !literal.chars().any(|char| char.is_whitespace()) {
+			paths.insert(normalize_web_path(literal));
+		}
+	}
+
+	paths
+}
+
+/// The keywords after which a `/` starts a regex, rather than a division
+const REGEX_PRECEDING_KEYWORDS: &[&str] = &[
+	"return",
+	"typeof",
+	"instanceof",
+	"in",
+	"of",
+	"new",
+	"delete",
+	"void",
+	"throw",
+	"case",
+	"do",
+	"else",
+	"yield",
+	"await",
+];
+
+/// Tokenize `code` just enough to find the contents of its string literals, skipping comments and regexes, so that quotes inside of
+/// them are not mistaken for strings. Template literals are only included if they have no substitutions, and literals that contain
+/// escapes are skipped, because asset paths never need them.
+fn get_string_literals(code: &str) -> Vec<&str> {
+	let bytes = code.as_bytes();
+	let mut literals = Vec::new();
+	let mut index = 0;
+	let mut is_regex_allowed = true;
+	let mut brace_depth = 0_usize;
+	// the brace depth outside of each template substitution that is currently open
+	let mut template_depths = Vec::new();
+
+	while let Some(&byte) = bytes.get(index) {
+		match byte {
+			b'/' if bytes.get(index + 1) == Some(&b'/') => {
+				index = find_from(bytes, index, b"\n").unwrap_or(bytes.len());
+			}
+			b'/' if bytes.get(index + 1) == Some(&b'*') => {
+				index = find_from(bytes, index + 2, b"*/").map(|end| end + 2).unwrap_or(bytes.len());
+			}
+			b'/' if is_regex_allowed => {
+				index = skip_regex(bytes, index + 1);
+				is_regex_allowed = false;
+			}
+			b'"' | b'\'' => {
+				let end = skip_string(bytes, index + 1, byte);
+				let literal = &code[index + 1..end];
+
+				if !literal.contains('\\') {
+					literals.push(literal);
+				}
+
+				index = end + 1;
+				is_regex_allowed = false;
+			}
+			b'`' | b'}' => {
+				let is_template_continuation = byte == b'}' && brace_depth.checked_sub(1) == template_depths.last().copied();
+
+				if byte == b'}' && !is_template_continuation {
+					brace_depth = brace_depth.saturating_sub(1);
+					index += 1;
+					is_regex_allowed = false;
+					continue;
+				}
+
+				if is_template_continuation {
+					brace_depth -= 1;
+					template_depths.pop();
+				}
+
+				let (end, is_substitution) = skip_template_chunk(bytes, index + 1);
+
+				if is_substitution {
+					template_depths.push(brace_depth);
+					brace_depth += 1;
+					index = end + 2;
+					is_regex_allowed = true;
+				} else {
+					let literal = &code[index + 1..end];
+
+					if byte == b'`' && !literal.contains('\\') {
+						literals.push(literal);
+					}
+
+					index = end + 1;
+					is_regex_allowed = false;
+				}
+			}
+			b'{' => {
+				brace_depth += 1;
+				index += 1;
+				is_regex_allowed = true;
+			}
+			_ if is_identifier_byte(byte) => {
+				let start = index;
+
+				while bytes.get(index).copied().is_some_and(is_identifier_byte) {
+					index += 1;
+				}
+
+				is_regex_allowed = REGEX_PRECEDING_KEYWORDS.contains(&&code[start..index]);
+			}
+			_ if byte.is_ascii_whitespace() => index += 1,
+			b')' | b']' => {
+				index += 1;
+				is_regex_allowed = false;
+			}
+			_ => {
+				index += 1;
+				is_regex_allowed = true;
+			}
+		}
+	}
+
+	literals
+}
+
+/// Whether `byte` can be part of an identifier, keyword, or number. Every byte of a non-ascii character is counted, because they only
+/// appear in identifiers outside of strings and comments
+fn is_identifier_byte(byte: u8) -> bool {
+	byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'$' || !byte.is_ascii()
+}
+
+/// The index of the first occurrence of `pattern` in `bytes` at or after `start`
+fn find_from(bytes: &[u8], start: usize, pattern: &[u8]) -> Option<usize> {
+	bytes
+		.get(start..)?
+		.windows(pattern.len())
+		.position(|window| window == pattern)
+		.map(|position| start + position)
+}
+
+/// The index of the quote that closes the string that starts at `start`, or the end of `bytes` if it is never closed
+fn skip_string(bytes: &[u8], mut start: usize, quote: u8) -> usize {
+	while let Some(&byte) = bytes.get(start) {
+		match byte {
+			b'\\' => start += 2,
+			_ if byte == quote || byte == b'\n' => return start,
+			_ => start += 1,
+		}
+	}
+
+	bytes.len()
+}
+
+/// The index of the backtick that closes the template chunk that starts at `start`, or of the `$` that opens its next substitution,
+/// along with whether it was a substitution
+fn skip_template_chunk(bytes: &[u8], mut start: usize) -> (usize, bool) {
+	while let Some(&byte) = bytes.get(start) {
+		match byte {
+			b'\\' => start += 2,
+			b'`' => return (start, false),
+			b'$' if bytes.get(start + 1) == Some(&b'{') => return (start, true),
+			_ => start += 1,
+		}
+	}
+
+	(bytes.len(), false)
+}
+
+/// The index just past the regex whose body starts at `start`, including its flags
+fn skip_regex(bytes: &[u8], mut start: usize) -> usize {
+	let mut is_in_class = false;
+
+	while let Some(&byte) = bytes.get(start) {
+		start += 1;
+
+		match byte {
+			b'\\' => start += 1,
+			b'[' => is_in_class = true,
+			b']' => is_in_class = false,
+			b'/' if !is_in_class => break,
+			// regexes can't span lines, so this wasn't one
+			b'\n' => return start - 1,
+			_ => (),
+		}
+	}
+
+	while bytes.get(start).is_some_and(|byte| byte.is_ascii_alphanumeric()) {
+		start += 1;
+	}
+
+	start.min(bytes.len())
+}
+
 fn normalize_web_path(path: &str) -> String {
 	if path.starts_with("/") {
 		normalize_web_path(&path[1..])
@@ -207,7 +728,88 @@ fn normalize_web_path(path: &str) -> String {
 	}
 }
 
-async fn load_index(url: &Url) -> Result<Vec<Asset>> {
+/// Load the assets of an index, along with every problem found in it. A problem with one asset doesn't prevent the others from loading.
+async fn load_index(url: &Url, asset_root: Option<&Url>) -> Result<(Vec<Asset>, Vec<String>)> {
+	let format = IndexFormat::from_url(url)?;
+	let text = match format {
+		IndexFormat::Json => read_json_index(url).await?,
+		IndexFormat::Yaml | IndexFormat::Toml => read_index(url).await?,
+	};
+	let mut value_array = parse_index(format, &text)?;
+
+	let mut assets = Vec::<Asset>::new();
+	let mut problems = Vec::new();
+	let mut local_paths = HashMap::new();
+	let mut web_paths = HashMap::new();
+
+	for (index, value) in value_array.drain(..).enumerate() {
+		let asset = match Asset::from_json(url, asset_root, value) {
+			Ok(asset) => asset,
+			Err(error) => {
+				problems.push(format!("Asset #{index} is invalid: {error:#}"));
+				continue;
+			}
+		};
+
+		if let Some(first_index) = local_paths.get(&asset.url) {
+			problems.push(format!("Asset #{index} has the same local path as asset #{first_index}"));
+			continue;
+		}
+
+		if let Some(first_index) = web_paths.get(&asset.web_path) {
+			problems.push(format!("Asset #{index} has the same web path as asset #{first_index}"));
+			continue;
+		}
+
+		local_paths.insert(asset.url.clone(), index);
+		web_paths.insert(asset.web_path.clone(), index);
+		assets.push(asset);
+	}
+
+	Ok((assets, problems))
+}
+
+/// The format of an asset index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IndexFormat {
+	Json,
+	Yaml,
+	Toml,
+}
+
+impl IndexFormat {
+	/// Choose the format based on the extension of the last segment of `url`'s path. It is an error for the extension to be missing or
+	/// unsupported.
+	fn from_url(url: &Url) -> Result<IndexFormat> {
+		let file_name = url.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or_default();
+		let extension = file_name.rsplit_once('.').map(|(_, extension)| extension.to_lowercase());
+
+		match extension.as_deref() {
+			Some("json") => Ok(IndexFormat::Json),
+			Some("yaml" | "yml") => Ok(IndexFormat::Yaml),
+			Some("toml") => Ok(IndexFormat::Toml),
+			extension => {
+				let builder = match extension {
+					Some(extension) => Diagnostic::start("The asset index ")
+						.inline_code(url)
+						.text(" has the unsupported extension ")
+						.inline_code(format!(".{extension}")),
+					None => Diagnostic::start("The asset index ").inline_code(url).text(" has no extension"),
+				};
+
+				builder
+					.shift()
+					.text("Supported formats are ")
+					.join_map([".json", ".yaml", ".yml", ".toml"].iter(), |builder, extension| builder.inline_code(extension))
+					.build()
+					.err()
+			}
+		}
+	}
+}
+
+/// Read a json index through `deno info`, which caches remote indexes along with the runtime's modules
+async fn read_json_index(url: &Url) -> Result<String> {
 	let mut graph = InfoGraph::load(url).await?;
 	let module = graph.modules.drain(..).nth(0).ok_or(anyhow!(
 		"Expected there to be a single module (probably caused by a regression in `deno fmt`) when getting the graph for asset index"
@@ -220,18 +822,398 @@ async fn load_index(url: &Url) -> Result<Vec<Asset>> {
 	let local = module
 		.local
 		.ok_or_else(|| anyhow!("Expected a local file because there was no error. This is probably caused by a regression in `deno info`"))?;
-	let json = read_to_string(&local).await.with_context(|| format!("failed to read file at {local:?}"))?;
-	let value = from_str::<Value>(&json).context("Index is not valid json")?;
-	let mut value_array = match value {
-		Value::Array(inner) => inner,
-		_ => bail!("Asset index should be a json file containing an array"),
+
+	read_to_string(&local).await.with_context(|| format!("failed to read file at {local:?}"))
+}
+
+/// Read an index that deno can't load as a module, such as a yaml or toml index
+async fn read_index(url: &Url) -> Result<String> {
+	match url.scheme() {
+		"file" => {
+			let path = url.to_file_path().map_err(|_| anyhow!("failed to convert {url} into a path"))?;
+
+			read_to_string(&path).await.with_context(|| format!("failed to read file at {path:?}"))
+		}
+		"http" | "https" => {
+			let response = reqwest::get(url.clone())
+				.await
+				.and_then(|response| response.error_for_status())
+				.with_context(|| format!("failed to fetch {url}"))?;
+
+			response.text().await.with_context(|| format!("failed to read the response from {url}"))
+		}
+		scheme => bail!("Asset indexes can't be loaded from {scheme}: urls"),
+	}
+}
+
+/// Parses the text of an asset index into a list of raw asset values. A json or yaml index is an array of assets, and a toml index is
+/// an `assets` array of tables.
+fn parse_index(format: IndexFormat, text: &str) -> Result<Vec<Value>> {
+	let value = match format {
+		IndexFormat::Json => from_str::<Value>(text).context("Index is not valid json")?,
+		IndexFormat::Yaml => serde_yaml::from_str::<Value>(text).context("Index is not valid yaml")?,
+		IndexFormat::Toml => {
+			let mut table = toml::from_str::<Value>(text).context("Index is not valid toml")?;
+
+			match table.get_mut("assets") {
+				Some(assets) => assets.take(),
+				None => bail!("Asset index should be a toml file containing an `assets` array of tables"),
+			}
+		}
 	};
 
-	let assets = value_array
-		.drain(..)
-		.enumerate()
-		.map(|(index, value)| Asset::from_json(url, value).with_context(|| format!("Failed to deserialize asset #{index}")))
-		.collect::<Result<Vec<_>>>()?;
+	match value {
+		Value::Array(inner) => Ok(inner),
+		_ => bail!("Asset index should contain an array of assets"),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::TempDir;
+	use serde_json::json;
+	use std::{
+		fs::{create_dir_all, write},
+		time::Duration,
+	};
+	use tokio::time::timeout;
+
+	fn format_of(url: &str) -> Result<IndexFormat> {
+		IndexFormat::from_url(&Url::parse(url).unwrap())
+	}
+
+	#[test]
+	fn index_format_comes_from_the_last_path_segment() {
+		assert_eq!(format_of("https://example.com/assets.json").unwrap(), IndexFormat::Json);
+		assert_eq!(format_of("https://example.com/assets.yaml").unwrap(), IndexFormat::Yaml);
+		assert_eq!(format_of("https://example.com/assets.YML").unwrap(), IndexFormat::Yaml);
+		assert_eq!(format_of("file:///project/assets.toml").unwrap(), IndexFormat::Toml);
+		assert_eq!(format_of("https://example.com/assets.toml?version=2").unwrap(), IndexFormat::Toml);
+	}
+
+	#[test]
+	fn unsupported_index_extensions_are_errors() {
+		let txt_error = format_of("https://example.com/assets.txt").unwrap_err().to_string();
+		let ini_error = format_of("file:///project/assets.ini").unwrap_err().to_string();
 
-	Ok(assets)
+		assert!(txt_error.contains("unsupported extension `.txt`"));
+		assert!(txt_error.contains("`.json`"));
+		assert!(ini_error.contains("unsupported extension `.ini`"));
+		assert!(format_of("https://example.com/a.b/index").unwrap_err().to_string().contains("has no extension"));
+		assert!(format_of("https://example.com/assets").is_err());
+	}
+
+	fn get_web_paths(format: IndexFormat, text: &str) -> Vec<Value> {
+		parse_index(format, text).unwrap().iter().map(|asset| asset["webPath"].clone()).collect()
+	}
+
+	#[test]
+	fn indexes_are_parsed_in_every_format() {
+		let json = r#"[{ "sha256": "00", "localPath": "a.png", "webPath": "/a.png" }, { "sha256": "11", "localPath": "b.png", "webPath": "/b.png" }]"#;
+		let yaml = "- sha256: '00'\n  localPath: a.png\n  webPath: /a.png\n- sha256: '11'\n  localPath: b.png\n  webPath: /b.png\n";
+		let toml = "[[assets]]\nsha256 = '00'\nlocalPath = 'a.png'\nwebPath = '/a.png'\n\n[[assets]]\nsha256 = '11'\nlocalPath = 'b.png'\nwebPath = '/b.png'\n";
+		let expected = [Value::from("/a.png"), Value::from("/b.png")];
+
+		assert_eq!(get_web_paths(IndexFormat::Json, json), expected);
+		assert_eq!(get_web_paths(IndexFormat::Yaml, yaml), expected);
+		assert_eq!(get_web_paths(IndexFormat::Toml, toml), expected);
+	}
+
+	#[test]
+	fn toml_indexes_need_an_assets_array() {
+		assert!(parse_index(IndexFormat::Toml, "[[files]]\nsha256 = '00'\n").is_err());
+		assert!(parse_index(IndexFormat::Yaml, "sha256: '00'\n").is_err());
+	}
+
+	#[tokio::test]
+	async fn duplicate_local_paths_in_an_index_are_reported_before_loading() {
+		let directory = TempDir::new("duplicate_local_paths");
+		let hash = "00".repeat(32);
+		let index = directory.write(
+			"assets.yaml",
+			format!(
+				"- sha256: '{hash}'\n  localPath: a.png\n  webPath: /a.png\n- sha256: '{hash}'\n  localPath: b.png\n  webPath: /b.png\n- sha256: '{hash}'\n  localPath: a.png\n  webPath: /c.png\n"
+			),
+		);
+		let mut loader = AssetsLoader::default();
+		let mut diagnostic_list = DiagnosticList::new();
+
+		loader.register_index_url(Url::from_file_path(&index).unwrap());
+		loader.load(&mut diagnostic_list, &Arc::new(Semaphore::new(1))).await.unwrap();
+
+		let messages = diagnostic_list.get_messages();
+
+		assert_eq!(messages.len(), 1);
+		assert!(messages[0].contains("Invalid asset index at"));
+		assert!(messages[0].contains("Asset #2 has the same local path as asset #0"));
+		// the duplicate is dropped, and the rest of the index is still loaded
+		assert_eq!(
+			loader.assets.iter().map(|asset| asset.web_path.as_str()).collect::<Vec<_>>(),
+			["/a.png", "/b.png"]
+		);
+	}
+
+	#[test]
+	fn only_string_literals_are_found() {
+		let code = r#"
+			// don't count "quotes" in comments
+			const a = "/images/a.png", b = 'it\'s', c = `/images/c.svg`;
+			/* or 'in block comments' */
+			const pattern = /["']/g, ratio = a.length / 2 / 3;
+			const d = `${a}/images/not-a-literal.png`, e = { f: `${{ g: "/images/g.png" }.g}` };
+			return /'/.test(c) ? "/fonts/h.woff2" : '/images/i.webp';
+		"#;
+
+		assert_eq!(
+			get_string_literals(code),
+			["/images/a.png", "/images/c.svg", "/images/g.png", "/fonts/h.woff2", "/images/i.webp"]
+		);
+	}
+
+	#[test]
+	fn unreferenced_assets_are_reported() {
+		let web_paths = HashSet::from(["/images/used.png".to_string(), "/images/unused.png".to_string()]);
+		let bundle = r#"render({ src: "/images/used.png" }); render({ src: '/images/missing.png' });"#;
+
+		let (unused, unindexed) = compare_references(&web_paths, bundle);
+
+		assert_eq!(unused, ["/images/unused.png"]);
+		assert_eq!(unindexed, ["/images/missing.png"]);
+	}
+
+	#[tokio::test]
+	async fn relative_local_paths_resolve_under_the_asset_root_rather_than_next_to_the_index() {
+		let directory = TempDir::new("asset_root");
+		let sha256 = "00".repeat(32);
+		let index_path = directory.write(
+			"indexes/assets.json",
+			json!([
+				{ "sha256": sha256, "localPath": "images/logo.svg", "webPath": "/logo.svg" },
+				{ "sha256": sha256, "localPath": "https://example.com/icon.svg", "webPath": "/icon.svg" },
+			])
+			.to_string(),
+		);
+		let mut loader = AssetsLoader::default();
+		loader.register_index_url(Url::from_file_path(index_path).unwrap());
+		loader.set_asset_root(Url::from_file_path(directory.path().join("content")).unwrap());
+
+		let mut diagnostic_list = DiagnosticList::new();
+		loader.load(&mut diagnostic_list, &Arc::new(Semaphore::new(1))).await.unwrap();
+		diagnostic_list.flush("load assets").unwrap();
+
+		let urls = loader.assets.iter().map(|asset| asset.url.clone()).collect::<Vec<_>>();
+
+		assert_eq!(
+			urls,
+			[
+				Url::from_file_path(directory.path().join("content/images/logo.svg")).unwrap(),
+				Url::parse("https://example.com/icon.svg").unwrap()
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn fresh_assets_count_as_processed_without_transferring_bytes() {
+		let directory = TempDir::new("asset_progress");
+		let writer = Writer::new(directory.path().join("out"));
+
+		let assets = [("fresh.txt", "fresh"), ("stale.txt", "stale!")]
+			.into_iter()
+			.map(|(name, contents)| {
+				let path = directory.write(&format!("source/{name}"), contents);
+
+				Asset {
+					sha256: Sha256::digest(contents).to_vec(),
+					url: Url::from_file_path(path).unwrap(),
+					web_path: format!("/{name}"),
+					headers: HashMap::new(),
+				}
+			})
+			.collect::<Vec<_>>();
+
+		let mut cache = BuildCache::empty(&writer);
+		let fresh_path = writer.get_full_path(assets[0].url.to_string());
+		create_dir_all(fresh_path.parent().unwrap()).unwrap();
+		write(&fresh_path, "fresh").unwrap();
+		cache.record(fresh_path, &assets[0].sha256);
+
+		let loader = AssetsLoader { assets, ..Default::default() };
+		let reports = std::sync::Mutex::new(Vec::new());
+		let on_progress = |progress: AssetProgress| reports.lock().unwrap().push((progress.processed, progress.total, progress.bytes_transferred));
+		let mut diagnostic_list = DiagnosticList::new();
+
+		loader
+			.write(
+				&writer,
+				&mut diagnostic_list,
+				AssetsLoaderWriteOptions {
+					cache: Some(&mut cache),
+					on_progress: Some(&on_progress),
+					..Default::default()
+				},
+			)
+			.await
+			.unwrap();
+
+		diagnostic_list.flush("write assets").unwrap();
+		assert_eq!(reports.into_inner().unwrap(), [(1, 2, 0), (2, 2, 6)]);
+	}
+
+	#[tokio::test]
+	async fn failures_are_reported_in_the_order_of_the_assets() {
+		let directory = TempDir::new("asset_order");
+		let writer = Writer::new(directory.path().join("out"));
+
+		// the first asset is the largest, so that it is likely to be the last to finish
+		let large = "a".repeat(1 << 20);
+		let assets = [
+			("first.txt", Some(large.as_str()), false),
+			("second.txt", Some("ok"), true),
+			("third.txt", None, true),
+			("fourth.txt", Some("wrong"), false),
+		]
+		.into_iter()
+		.map(|(name, contents, is_hash_correct)| {
+			let path = match contents {
+				Some(contents) => directory.write(&format!("source/{name}"), contents),
+				None => directory.path().join("source").join(name),
+			};
+
+			Asset {
+				sha256: if is_hash_correct {
+					Sha256::digest(contents.unwrap_or_default()).to_vec()
+				} else {
+					Vec::new()
+				},
+				url: Url::from_file_path(path).unwrap(),
+				web_path: format!("/{name}"),
+				headers: HashMap::new(),
+			}
+		})
+		.collect::<Vec<_>>();
+
+		let loader = AssetsLoader { assets, ..Default::default() };
+		let mut diagnostic_list = DiagnosticList::new();
+
+		loader
+			.write(
+				&writer,
+				&mut diagnostic_list,
+				AssetsLoaderWriteOptions {
+					concurrency: NonZeroUsize::new(4),
+					..Default::default()
+				},
+			)
+			.await
+			.unwrap();
+
+		let failed_assets = diagnostic_list
+			.get_messages()
+			.iter()
+			.map(|message| {
+				["first", "second", "third", "fourth"]
+					.into_iter()
+					.find(|name| message.contains(&format!("{name}.txt")))
+			})
+			.collect::<Vec<_>>();
+
+		assert_eq!(failed_assets, [Some("first"), Some("third"), Some("fourth")]);
+	}
+
+	#[tokio::test]
+	async fn permanent_download_failures_are_not_retried() {
+		use std::sync::atomic::{AtomicUsize, Ordering};
+		use tokio::{
+			io::{AsyncReadExt, AsyncWriteExt},
+			net::TcpListener,
+		};
+
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = Url::parse(&format!("http://{}/missing.txt", listener.local_addr().unwrap())).unwrap();
+		let request_count = Arc::new(AtomicUsize::new(0));
+		let server_request_count = request_count.clone();
+
+		let server = tokio::spawn(async move {
+			loop {
+				let (mut stream, _) = listener.accept().await.unwrap();
+				let mut buffer = [0; 1024];
+
+				server_request_count.fetch_add(1, Ordering::SeqCst);
+				let _ = stream.read(&mut buffer).await;
+				let _ = stream
+					.write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+					.await;
+			}
+		});
+
+		let directory = TempDir::new("asset_not_found");
+		let writer = Writer::new(directory.path());
+		let retry_policy = RetryPolicy {
+			retries: 3,
+			base_delay: Duration::from_millis(1),
+		};
+		let outcome = write_asset(&writer, "missing.txt", &url, &[], &HashMap::new(), retry_policy).await;
+		server.abort();
+
+		assert!(outcome.is_err());
+		assert_eq!(request_count.load(Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test]
+	async fn connection_errors_are_retried_with_exponential_backoff() {
+		// nothing listens on a port once its listener is dropped, so connecting to it is refused
+		let address = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+		let url = Url::parse(&format!("http://{address}/asset.txt")).unwrap();
+
+		let directory = TempDir::new("asset_refused");
+		let writer = Writer::new(directory.path());
+		let retry_policy = RetryPolicy {
+			retries: 2,
+			base_delay: Duration::from_millis(50),
+		};
+		let started = Instant::now();
+		let outcome = write_asset(&writer, "asset.txt", &url, &[], &HashMap::new(), retry_policy).await;
+
+		assert!(outcome.is_err());
+		// 50ms before the first retry, and 100ms before the second
+		assert!(started.elapsed() >= Duration::from_millis(150));
+		assert!(is_transient_download_error(&reqwest::Client::new().get(url).send().await.unwrap_err().into()));
+		assert!(!is_transient_download_error(&anyhow!("the hash doesn't match")));
+	}
+
+	#[tokio::test]
+	async fn indexes_are_loaded_within_the_job_limit_and_registered_in_order() {
+		let directory = TempDir::new("concurrent_indexes");
+		let hash = "00".repeat(32);
+		let index_urls = (0..4)
+			.map(|index| {
+				let path = directory.write(
+					&format!("assets_{index}.yaml"),
+					format!("- sha256: '{hash}'\n  localPath: {index}.png\n  webPath: /{index}.png\n"),
+				);
+
+				Url::from_file_path(path).unwrap()
+			})
+			.collect::<Vec<_>>();
+		let load = |jobs: usize| {
+			let index_urls = index_urls.clone();
+
+			async move {
+				let mut loader = AssetsLoader::default();
+
+				for url in index_urls {
+					loader.register_index_url(url);
+				}
+
+				loader.load(&mut DiagnosticList::new(), &Arc::new(Semaphore::new(jobs))).await.unwrap();
+				loader.assets.iter().map(|asset| asset.web_path.clone()).collect::<Vec<_>>()
+			}
+		};
+
+		// without a permit, no index can be loaded
+		assert!(timeout(Duration::from_millis(100), load(0)).await.is_err());
+		assert_eq!(load(1).await, ["/0.png", "/1.png", "/2.png", "/3.png"]);
+		assert_eq!(load(4).await, load(1).await);
+	}
 }
@@ -1,48 +1,341 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, Context, Error, Result};
+use clap::ValueEnum;
+use colored::Colorize;
+use deno_doc::Location;
 use deno_graph::source::MemoryLoader;
-use log::{error, info};
+use log::{error, info, warn};
+use serde_json::json;
+use std::{fmt::Write, num::NonZeroUsize, path::Path, sync::Arc};
+use tokio::{fs::write, sync::Semaphore, try_join};
 use url::Url;
 
 use crate::{
 	asset_loader::AssetsLoader,
-	bundle::{BundleParams, Bundler},
-	collect::Collection,
-	diagnostic::DiagnosticList,
-	engine::Engine,
+	bundle::{BundleParams, Bundler, DefinePlugin},
+	collect::{Collection, CollectionError, GraphFormat},
+	diagnostic::{record_warning, DiagnosticList},
+	engine::{BindingsParams, Engine, EngineBindings},
+	gen_schema::get_protocol_schema,
+	gen_ts::get_bundle_declarations,
 	inspect::Inspector,
-	module_loader::load_modules,
+	module_loader::{load_modules, load_stdin_modules, ModuleAllowlist, ModuleLoadError, ModuleLoadErrorKind, ResolvedRuntime},
+	writer::JsonStyle,
 };
 
+/// The number of times that loading the runtime's modules is attempted when it fails due to a transient error
+const MODULE_LOAD_ATTEMPTS: usize = 3;
+
 #[derive(Debug, Clone, Copy)]
 pub struct BuildOptions<'a> {
 	pub runtime: &'a Url,
+	/// The source of the runtime, if it was read from stdin. See `load_stdin_modules`
+	pub runtime_source: Option<&'a str>,
 	pub bundler: &'a Url,
 	pub engine_url: &'a Url,
-	pub engine: Engine,
+	/// The engines to generate bindings for. The runtime is collected once, no matter how many engines there are
+	pub engines: &'a [Engine],
+	pub bindings_header: Option<&'a str>,
+	/// Split the rust bindings into a file per component. See `RustGen::with_split_files`
+	pub bindings_split: bool,
+	/// Format the rust bindings with rustfmt. See `BindingsParams::format`
+	pub format_bindings: bool,
+	pub defines: &'a [(String, String)],
+	pub print_graph: Option<GraphFormat>,
+	pub verify_bindings: bool,
+	/// If set, only errors from items declared in these files will fail the build. All other errors are reported as warnings.
+	pub changed_files: Option<&'a [Url]>,
+	/// If set, a JSON description of every event and action key is written here. See `Collection::get_protocol`
+	pub protocol_path: Option<&'a Path>,
+	/// If set, a JSON Schema of the whole protocol is written here. See `get_protocol_schema`
+	pub schema_path: Option<&'a Path>,
+	/// If set, the generated entry module is written here before it is bundled. See `BundleParams::entry_path`
+	pub entry_path: Option<&'a Path>,
+	/// The maximum number of build tasks, such as bundling and loading asset indexes, that can run at once
+	pub jobs: NonZeroUsize,
+	/// Generate a `bundle.d.ts` that types the bundle. See `gen_ts::get_bundle_declarations`
+	pub emit_declarations: bool,
+	/// Headers to send with every remote asset download
+	pub asset_headers: &'a [(String, String)],
+	/// If set, relative asset paths are resolved against this, rather than against their index. See `AssetsLoader::set_asset_root`
+	pub asset_root: Option<&'a Url>,
+	/// Collect the contents of exported namespaces. See `Collection::enable_namespaces`
+	pub namespaces: bool,
+	/// The schemes that modules may be loaded from. Empty to allow any scheme. See `ModuleAllowlist`
+	pub allowed_module_schemes: &'a [String],
+	/// The hosts that modules may be loaded from. Empty to allow any host. See `ModuleAllowlist`
+	pub allowed_hosts: &'a [String],
+	/// Overrides the style of every JSON artifact. If unset, each artifact uses the style that suits it
+	pub json_style: Option<JsonStyle>,
+	/// Globs of files whose exports are not collected. See `Collection::exclude`
+	pub exclude: &'a [String],
+	/// Overrides the nesting depth at which type conversion fails. See `Collection::set_max_depth`
+	pub max_depth: Option<usize>,
+	/// Warn about components that share a name with an element that the frontend treats specially. See `Collection::check_reserved_names`
+	pub check_reserved_names: bool,
+	/// Names to reserve in addition to `collect::DEFAULT_RESERVED_NAMES`
+	pub reserved_names: &'a [String],
+	/// Fail when differently shaped types would be generated under the same name. See `Collection::deny_type_name_conflicts`
+	pub deny_type_name_conflicts: bool,
+	/// The stages of the build to run. Empty to run every stage. See `BuildOptions::runs_stage`
+	pub stages: &'a [BuildStage],
+}
+
+impl BuildOptions<'_> {
+	/// Whether `stage` is part of this build. The runtime is always collected and checked, but the outputs of stages that are not run
+	/// are neither produced nor written, so previous outputs are left in place.
+	pub fn runs_stage(&self, stage: BuildStage) -> bool {
+		self.stages.is_empty() || self.stages.contains(&stage)
+	}
+}
+
+/// A part of the build that can be run on its own. See `BuildOptions::stages`
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum BuildStage {
+	/// Generate and write the engine bindings
+	Bindings,
+	/// Bundle the runtime, then write it along with the files that load and type it
+	Bundle,
+	/// Load and write the assets
+	Assets,
+}
+
+/// The format that `list_components` prints in
+#[derive(Debug, ValueEnum, Clone, Copy, Default)]
+pub enum ListFormat {
+	/// One component per line, in aligned columns
+	#[default]
+	Text,
+	Json,
 }
 
 pub struct Build {
-	pub client_bundle: String,
-	pub bindings: String,
+	/// `None` if the bundle stage was not run
+	pub client_bundle: Option<String>,
+	pub bindings: EngineBindings,
+	pub bundle_declarations: Option<String>,
 	pub assets_loader: AssetsLoader,
 }
 
-pub async fn build(diagnostic_list: &mut DiagnosticList, options: BuildOptions<'_>) -> Result<Build> {
+/// Load, collect, and check the runtime. The returned collection may contain errors
+pub async fn load_collection(options: BuildOptions<'_>) -> Result<(Collection, Bundler)> {
 	let mut memory_loader = MemoryLoader::default();
 	let mut bundler = Bundler::default();
 	let mut collection = Collection::default();
 
-	load_modules(options.runtime, &mut memory_loader, &mut bundler).await?;
+	bundler.register_plugin(DefinePlugin::new(options.defines, options.runtime)?);
+
+	if options.namespaces {
+		collection.enable_namespaces();
+	}
+
+	if !options.exclude.is_empty() {
+		collection.exclude(options.exclude)?;
+	}
+
+	if let Some(max_depth) = options.max_depth {
+		collection.set_max_depth(max_depth);
+	}
+
+	if options.check_reserved_names {
+		collection.check_reserved_names(options.reserved_names);
+	}
+
+	if options.deny_type_name_conflicts {
+		collection.deny_type_name_conflicts();
+	}
+
+	let allowlist = ModuleAllowlist::new(options.allowed_module_schemes, options.allowed_hosts, options.runtime);
+	let mut attempt = 1;
+
+	loop {
+		let result = match options.runtime_source {
+			Some(source) => load_stdin_modules(options.runtime, source, &mut memory_loader, &mut bundler, &allowlist).await,
+			None => load_modules(options.runtime, &mut memory_loader, &mut bundler, &allowlist).await,
+		};
+		let error = match result {
+			Ok(()) => break,
+			Err(error) => error,
+		};
+
+		if !should_retry_module_load(&error, attempt) {
+			return Err(error);
+		}
+
+		warn!("{error:?}");
+		info!("Retrying module load (attempt {} of {MODULE_LOAD_ATTEMPTS})", attempt + 1);
+		attempt += 1;
+	}
+
 	info!("Loaded runtime");
 
 	collection.collect(&options.runtime, &memory_loader).await?;
 	collection.check_components();
 
-	let errors = collection.get_errors();
-	let error_count = errors.len();
+	if let Some(format) = options.print_graph {
+		println!("{}", collection.get_graph(format, options.json_style.unwrap_or(JsonStyle::Compact))?);
+	}
+
+	Ok((collection, bundler))
+}
+
+/// Whether a module load that failed with `error` on `attempt` should be tried again. Only network failures are retried, as anything
+/// else will just fail again
+fn should_retry_module_load(error: &Error, attempt: usize) -> bool {
+	attempt < MODULE_LOAD_ATTEMPTS && ModuleLoadError::kind_of(error) == Some(ModuleLoadErrorKind::Transient)
+}
+
+/// Collect the runtime, then print every type and function that could not be resolved, grouped by the reason. Unlike `build`, this
+/// does not fail if there are unresolved types, so that they can be triaged incrementally.
+pub async fn report_unresolved(options: BuildOptions<'_>) -> Result<()> {
+	let (collection, _) = load_collection(options).await?;
+	let errors = collection.get_categorized_errors();
+	collection.print_ignored_summary();
+
+	if collection.get_errors().is_empty() {
+		info!("Every type and function in the runtime was resolved");
+
+		return Ok(());
+	}
+
+	print!("{}", get_unresolved_report(&errors));
+	info!("Found {} unresolved item{}", errors.len(), if errors.len() == 1 { "" } else { "s" });
+
+	Ok(())
+}
+
+/// Every error in `errors`, under a heading for each category. `errors` must already be sorted by category.
+fn get_unresolved_report(errors: &[CollectionError]) -> String {
+	let mut report = String::new();
+	let mut current_category = None;
 
 	for error in errors {
-		error!("{:?}", error);
+		if current_category != Some(error.category) {
+			let count = errors.iter().filter(|other| other.category == error.category).count();
+
+			writeln!(report, "\n{} ({count})", error.category.to_string().bold().underline()).unwrap();
+			current_category = Some(error.category);
+		}
+
+		writeln!(report, "\n{:?}", error.error).unwrap();
+	}
+
+	report
+}
+
+/// Collect the runtime, then print every component along with its render function and the number of events and actions that it has.
+/// Collection errors are not reported, so that the listing works even if unrelated parts of the runtime are broken.
+pub async fn list_components(options: BuildOptions<'_>, format: ListFormat) -> Result<()> {
+	let (collection, _) = load_collection(options).await?;
+
+	print!("{}", get_component_listing(&collection, format, options.json_style)?);
+
+	Ok(())
+}
+
+/// Every component of `collection`, sorted by name, along with its render function and the number of events and actions that it has
+fn get_component_listing(collection: &Collection, format: ListFormat, json_style: Option<JsonStyle>) -> Result<String> {
+	let mut components = collection.get_component_info();
+	components.sort_by_key(|(name, _)| *name);
+
+	match format {
+		ListFormat::Text => {
+			let name_width = components.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+			let render_width = components.iter().map(|(_, info)| info.render_name.len()).max().unwrap_or_default();
+			let mut listing = String::new();
+
+			for (name, info) in components {
+				writeln!(
+					listing,
+					"{name:name_width$}  {:render_width$}  {} events  {} actions",
+					info.render_name,
+					info.events.len(),
+					info.actions.len()
+				)?;
+			}
+
+			Ok(listing)
+		}
+		ListFormat::Json => {
+			let components = components
+				.iter()
+				.map(|(name, info)| {
+					json!({
+						"name": name,
+						"renderName": info.render_name,
+						"eventCount": info.events.len(),
+						"actionCount": info.actions.len(),
+					})
+				})
+				.collect::<Vec<_>>();
+
+			Ok(format!("{}\n", json_style.unwrap_or(JsonStyle::Pretty).serialize(&components)?))
+		}
+	}
+}
+
+/// Collect the runtime, then print every function that it must export, one per line. See `Collection::required_functions`
+pub async fn list_functions(options: BuildOptions<'_>) -> Result<()> {
+	let (collection, _) = load_collection(options).await?;
+
+	for function in collection.required_functions() {
+		println!("{function}");
+	}
+
+	Ok(())
+}
+
+/// Collect both the runtime and an older version of it, `previous_runtime`, then print the differences between their components as
+/// JSON. Fails if any of the differences are breaking. See `Collection::diff`
+pub async fn report_diff(options: BuildOptions<'_>, previous_runtime: &ResolvedRuntime) -> Result<()> {
+	let (collection, _) = load_collection(options).await?;
+	let (previous_collection, _) = load_collection(BuildOptions {
+		runtime: &previous_runtime.url,
+		runtime_source: previous_runtime.stdin_source.as_deref(),
+		print_graph: None,
+		..options
+	})
+	.await?;
+
+	let diff = previous_collection.diff(&collection);
+	println!("{}", options.json_style.unwrap_or(JsonStyle::Pretty).serialize(&diff)?);
+
+	let breaking_changes = diff.get_breaking_changes();
+
+	if !breaking_changes.is_empty() {
+		bail!(
+			"found {} breaking change{} since {}",
+			breaking_changes.len(),
+			if breaking_changes.len() == 1 { "" } else { "s" },
+			previous_runtime.url
+		);
+	}
+
+	info!("Found {} changes, none of which are breaking", diff.changes.len());
+
+	Ok(())
+}
+
+/// Collect the runtime and validate it, without bundling it or generating bindings. Fails if the runtime has errors, in the same way
+/// that `build` would.
+pub async fn check(options: BuildOptions<'_>) -> Result<()> {
+	let (collection, _) = load_collection(options).await?;
+	let mut diagnostic_list = DiagnosticList::new();
+
+	validate_collection(&mut diagnostic_list, &collection, options)
+}
+
+/// Report the errors of `collection`, then inspect it. Fails if any of the errors are in changed files, or if the inspection failed
+pub fn validate_collection(diagnostic_list: &mut DiagnosticList, collection: &Collection, options: BuildOptions<'_>) -> Result<()> {
+	let mut error_count = 0;
+
+	for error in collection.get_categorized_errors() {
+		if is_changed(options.changed_files, error.location) {
+			error!("{:?}", error.error);
+			error_count += 1;
+		} else {
+			record_warning(error.location.map(|location| location.filename.as_str()));
+			warn!("{:?}", error.error);
+		}
 	}
 
 	if error_count > 0 {
@@ -55,31 +348,353 @@ pub async fn build(diagnostic_list: &mut DiagnosticList, options: BuildOptions<'
 
 	info!("Mounted runtime");
 
-	let inspector = Inspector::new(&collection);
+	let inspector = Inspector::new(collection);
 	inspector.inspect(diagnostic_list);
 
 	diagnostic_list.flush("validate runtime")?;
 	info!("Validated runtime");
 
-	let client_bundle = bundler
-		.bundle(BundleParams {
-			bundler_url: options.bundler,
-			runtime_url: options.runtime,
-			collection: &collection,
-		})
-		.await?;
-	info!("Bundled runtime");
+	Ok(())
+}
+
+pub async fn build(diagnostic_list: &mut DiagnosticList, options: BuildOptions<'_>) -> Result<Build> {
+	let (mut collection, bundler) = load_collection(options).await?;
+	validate_collection(diagnostic_list, &collection, options)?;
+
+	if let Some(path) = options.protocol_path {
+		let protocol = options.json_style.unwrap_or(JsonStyle::Pretty).serialize(&collection.get_protocol())?;
+
+		write(path, protocol)
+			.await
+			.with_context(|| format!("failed to write the protocol to {path:?}"))?;
+		info!("Emitted protocol");
+	}
+
+	if let Some(path) = options.schema_path {
+		let schema = options.json_style.unwrap_or(JsonStyle::Pretty).serialize(&get_protocol_schema(&collection))?;
+
+		write(path, schema).await.with_context(|| format!("failed to write the schema to {path:?}"))?;
+		info!("Emitted schema");
+	}
 
-	let bindings = options.engine.get_bindings(&collection)?;
+	let bindings = get_engine_bindings(&collection, options)?;
+	let bundle_declarations = if options.emit_declarations && options.runs_stage(BuildStage::Bundle) {
+		Some(get_bundle_declarations(&collection)?)
+	} else {
+		None
+	};
 
-	let mut assets_loader = collection.finish();
-	assets_loader.load(diagnostic_list).await.context("Failed to load assets")?;
-	diagnostic_list.flush("load assets")?;
-	info!("Loaded assets");
+	// bundling and asset loading are independent, so they share the job limit and run at the same time
+	let semaphore = Arc::new(Semaphore::new(options.jobs.get()));
+	let mut assets_loader = collection.take_assets_loader();
+
+	if let Some(asset_root) = options.asset_root {
+		assets_loader.set_asset_root(asset_root.clone());
+	}
+
+	let bundle = async {
+		if !options.runs_stage(BuildStage::Bundle) {
+			return Ok(None);
+		}
+
+		let _permit = semaphore.acquire().await?;
+		let client_bundle = bundler
+			.bundle(BundleParams {
+				bundler_url: options.bundler,
+				runtime_url: options.runtime,
+				collection: &collection,
+				entry_path: options.entry_path,
+			})
+			.await?;
+		info!("Bundled runtime");
+
+		Ok::<_, Error>(Some(client_bundle))
+	};
+	let load_assets = async {
+		if !options.runs_stage(BuildStage::Assets) {
+			return Ok(());
+		}
+
+		assets_loader.load(diagnostic_list, &semaphore).await.context("Failed to load assets")?;
+		diagnostic_list.flush("load assets")?;
+		info!("Loaded assets");
+
+		Ok::<_, Error>(())
+	};
+
+	let (client_bundle, ()) = try_join!(bundle, load_assets)?;
+
+	// only a bundle and assets from the same build can be compared
+	if let (Some(client_bundle), true) = (&client_bundle, options.runs_stage(BuildStage::Assets)) {
+		assets_loader.check_references(client_bundle);
+	}
+
+	collection.print_ignored_summary();
 
 	Ok(Build {
 		client_bundle,
 		bindings,
+		bundle_declarations,
 		assets_loader,
 	})
 }
+
+/// Generate the bindings of every engine in `options.engines`. Empty if the bindings stage is not run
+pub fn get_engine_bindings(collection: &Collection, options: BuildOptions<'_>) -> Result<EngineBindings> {
+	let mut bindings = Vec::with_capacity(options.engines.len());
+	let engines = if options.runs_stage(BuildStage::Bindings) { options.engines } else { &[] };
+
+	for engine in engines {
+		let engine_bindings = engine.get_bindings(
+			collection,
+			BindingsParams {
+				header: options.bindings_header,
+				split: options.bindings_split,
+				format: options.format_bindings,
+			},
+		)?;
+
+		if options.verify_bindings {
+			engine.verify_bindings(&engine_bindings)?;
+		}
+
+		bindings.push((*engine, engine_bindings));
+	}
+
+	Ok(EngineBindings(bindings))
+}
+
+/// Errors without a location can't be attributed to a file, so they are always considered changed
+fn is_changed(changed_files: Option<&[Url]>, location: Option<&Location>) -> bool {
+	let (changed_files, location) = match (changed_files, location) {
+		(Some(changed_files), Some(location)) => (changed_files, location),
+		_ => return true,
+	};
+
+	changed_files.iter().any(|file| file.as_str() == location.filename)
+}
+
+/// The options of a build of `source`, as if it was read from stdin, that only runs `stages`. Bundling would fail, as the bundler
+/// doesn't exist, and so would loading assets, as the index doesn't exist
+#[cfg(test)]
+pub fn get_options<'a>(runtime: &'a Url, bundler: &'a Url, source: &'a str, stages: &'a [BuildStage]) -> BuildOptions<'a> {
+	BuildOptions {
+		runtime,
+		runtime_source: Some(source),
+		bundler,
+		engine_url: bundler,
+		engines: &[Engine::Rust],
+		bindings_header: None,
+		bindings_split: false,
+		format_bindings: false,
+		defines: &[],
+		print_graph: None,
+		verify_bindings: false,
+		changed_files: None,
+		protocol_path: None,
+		schema_path: None,
+		entry_path: None,
+		jobs: NonZeroUsize::MIN,
+		emit_declarations: false,
+		asset_headers: &[],
+		asset_root: None,
+		namespaces: false,
+		allowed_module_schemes: &[],
+		allowed_hosts: &[],
+		json_style: None,
+		exclude: &[],
+		max_depth: None,
+		check_reserved_names: false,
+		reserved_names: &[],
+		deny_type_name_conflicts: false,
+		stages,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		collect::{collect_source, collect_virtual_modules},
+		engine::{Bindings, BindingsWriter},
+		module_loader::VirtualModule,
+		test_util::TempDir,
+		writer::Writer,
+	};
+	use anyhow::anyhow;
+	use serde_json::{from_str, Value};
+	use std::fs::read_to_string;
+
+	const RUNTIME: &str = "/**\n * @feature_component_index\n */\nexport interface Component {\n\tid: number\n}\n\n/**\n * @component\n * @assets ./missing_assets.json\n */\nexport interface Button {\n\tlabel: string\n}\n";
+
+	#[tokio::test]
+	async fn components_are_listed_with_their_render_functions_and_key_counts() {
+		let collection = collect_source(
+			"/**\n * @feature_event_key\n */\nexport type EventKey<T> = { eventPath: string[] }\n\n/**\n * @component CardView\n */\nexport interface Card {\n\ttitle: string\n}\n\n/**\n * @component\n */\nexport interface Button {\n\tonClick: EventKey<string>\n\tonHover: EventKey<string>\n}\n",
+		)
+		.await;
+
+		assert_eq!(
+			get_component_listing(&collection, ListFormat::Text, None).unwrap(),
+			"Button  ButtonRender  2 events  0 actions\nCard    CardView      0 events  0 actions\n"
+		);
+
+		let listing = from_str::<Value>(&get_component_listing(&collection, ListFormat::Json, None).unwrap()).unwrap();
+
+		assert_eq!(
+			listing,
+			json!([
+				{ "name": "Button", "renderName": "ButtonRender", "eventCount": 2, "actionCount": 0 },
+				{ "name": "Card", "renderName": "CardView", "eventCount": 0, "actionCount": 0 },
+			])
+		);
+	}
+
+	#[tokio::test]
+	async fn only_building_bindings_skips_the_bundle_and_assets() {
+		let runtime = Url::parse("stdin:///runtime.tsx").unwrap();
+		let bundler = Url::parse("file:///missing/bundler.ts").unwrap();
+		let mut diagnostic_list = DiagnosticList::new();
+		let output = build(&mut diagnostic_list, get_options(&runtime, &bundler, RUNTIME, &[BuildStage::Bindings]))
+			.await
+			.unwrap();
+
+		assert!(output.client_bundle.is_none());
+		assert!(output.bundle_declarations.is_none());
+
+		match &output.bindings.0[..] {
+			[(Engine::Rust, Bindings::File(bindings))] => assert!(bindings.contains("pub struct Button")),
+			bindings => panic!("expected a single file of rust bindings, but got {bindings:?}"),
+		}
+	}
+
+	#[tokio::test]
+	async fn bindings_for_several_engines_come_from_one_build() {
+		let runtime = Url::parse("stdin:///runtime.tsx").unwrap();
+		let bundler = Url::parse("file:///missing/bundler.ts").unwrap();
+		let options = BuildOptions {
+			engines: &[Engine::Rust, Engine::TypeScript],
+			..get_options(&runtime, &bundler, RUNTIME, &[BuildStage::Bindings])
+		};
+		let output = build(&mut DiagnosticList::new(), options).await.unwrap();
+
+		match &output.bindings.0[..] {
+			[(Engine::Rust, Bindings::File(rust)), (Engine::TypeScript, Bindings::File(typescript))] => {
+				assert!(rust.contains("pub struct Button"));
+				assert!(typescript.contains("export interface Button {\n\tlabel: string\n}\n"));
+			}
+			bindings => panic!("expected rust and then typescript bindings, but got {bindings:?}"),
+		}
+
+		let directory = TempDir::new("several_engines");
+		let writer = BindingsWriter::new(Vec::from([
+			(Engine::Rust, Writer::new(directory.path()).into_file_writer("bindings.rs")),
+			(Engine::TypeScript, Writer::new(directory.path()).into_file_writer("bindings.ts")),
+		]));
+		writer.write(output.bindings).await.unwrap();
+
+		assert!(read_to_string(directory.path().join("bindings.rs")).unwrap().contains("pub struct Button"));
+		assert!(read_to_string(directory.path().join("bindings.ts"))
+			.unwrap()
+			.contains("export interface Button"));
+	}
+
+	#[tokio::test]
+	async fn the_entry_is_emitted_before_it_is_bundled() {
+		let dir = TempDir::new("emit_entry");
+		let entry_path = dir.path().join("entry.js");
+		let runtime = Url::parse("stdin:///runtime.tsx").unwrap();
+		let bundler = Url::parse("file:///missing/bundler.ts").unwrap();
+		let options = BuildOptions {
+			entry_path: Some(&entry_path),
+			..get_options(&runtime, &bundler, RUNTIME, &[BuildStage::Bundle])
+		};
+
+		// the bundler doesn't exist, but the entry is written before it is run
+		assert!(build(&mut DiagnosticList::new(), options).await.is_err());
+
+		let entry = read_to_string(&entry_path).unwrap();
+
+		assert!(entry.contains("import { createStarter, ButtonRender,  } from 'stdin:///runtime.tsx'"));
+		assert!(entry.contains("if (component.type === 'Button') return { func: ButtonRender, params: component.def }"));
+		assert!(entry.contains("namespace.sendEvent = async (key, data) => {"));
+		assert!(entry.contains("fetch(namespace.engineUrl, {"));
+	}
+
+	#[tokio::test]
+	async fn the_unresolved_report_lists_every_erroring_kind_under_its_category() {
+		let mut collection = collect_source(
+			"export interface Form {\n\tsubmit(): void\n}\nexport interface Dialog {\n\tclose(): void\n}\nexport interface Page {\n\theader: Header\n}\n",
+		)
+		.await;
+		collection.meet_all_dependencies();
+
+		let report = get_unresolved_report(&collection.get_categorized_errors());
+		let conversions = report.find("Failed kind conversions").unwrap();
+		let missing = report.find("Missing types").unwrap();
+
+		assert!(conversions < missing);
+		assert!(report[conversions..missing].contains("Method `submit` is not supported"));
+		assert!(report[conversions..missing].contains("Method `close` is not supported"));
+		assert!(report[missing..].contains("Missing type `Header`"));
+	}
+
+	#[tokio::test]
+	async fn only_errors_from_changed_files_fail_validation() {
+		let runtime_url = Url::parse("file:///runtime.ts").unwrap();
+		let button_url = Url::parse("file:///button.ts").unwrap();
+		let card_url = Url::parse("file:///card.ts").unwrap();
+		let collection = collect_virtual_modules(&[
+			VirtualModule {
+				specifier: runtime_url.clone(),
+				source: "export * from './button.ts'\nexport * from './card.ts'\n".to_string(),
+				dependencies: Vec::from([("./button.ts".to_string(), button_url.clone()), ("./card.ts".to_string(), card_url.clone())]),
+			},
+			VirtualModule {
+				specifier: button_url.clone(),
+				source: "export interface Button {\n\tclick(): void\n}\n".to_string(),
+				dependencies: Vec::new(),
+			},
+			VirtualModule {
+				specifier: card_url.clone(),
+				source: "export interface Card {\n\ttitle: string\n}\n".to_string(),
+				dependencies: Vec::new(),
+			},
+		])
+		.await;
+		let bundler = Url::parse("file:///missing/bundler.ts").unwrap();
+		let validate = |changed_files: &[Url]| {
+			let options = BuildOptions {
+				changed_files: Some(changed_files),
+				..get_options(&runtime_url, &bundler, "", &[])
+			};
+
+			validate_collection(&mut DiagnosticList::new(), &collection, options)
+		};
+
+		assert!(validate(&[card_url.clone()]).is_ok());
+
+		let error = validate(&[button_url, card_url]).unwrap_err().to_string();
+
+		assert_eq!(error, "could not mount runtime due to 1 previous error");
+	}
+
+	#[test]
+	fn only_transient_module_load_failures_are_retried() {
+		let specifier = Url::parse("https://example.com/mod.ts").unwrap();
+		let not_found = anyhow!(ModuleLoadError::classify(Some(specifier.clone()), "Module not found: 404 Not Found"));
+		let reset = anyhow!(ModuleLoadError::classify(Some(specifier), "error sending request: connection reset by peer"));
+
+		assert!(!should_retry_module_load(&not_found, 1));
+		assert!(should_retry_module_load(&reset, 1));
+		assert!(should_retry_module_load(&reset.context("failed to load the runtime"), MODULE_LOAD_ATTEMPTS - 1));
+		assert!(!should_retry_module_load(&anyhow!("unclassified"), 1));
+	}
+
+	#[test]
+	fn transient_failures_stop_being_retried_after_the_last_attempt() {
+		let reset = anyhow!(ModuleLoadError::classify(None, "connection reset"));
+
+		assert!(!should_retry_module_load(&reset, MODULE_LOAD_ATTEMPTS));
+	}
+}
@@ -1,10 +1,21 @@
-use aho_corasick::AhoCorasick;
-use anyhow::{bail, Result};
+use aho_corasick::{AhoCorasick, MatchKind};
+use anyhow::{anyhow, bail, Context, Result};
+use log::info;
 use serde::Serialize;
 use serde_json::to_string;
-use std::{collections::HashMap, env, fmt::Write, path::PathBuf, process::Stdio};
-use tokio::{io::AsyncWriteExt, process::Command};
-use url::Url;
+use std::{
+	collections::HashMap,
+	env,
+	fmt::{Debug, Write},
+	path::{Path, PathBuf},
+	process::Stdio,
+};
+use tokio::{
+	fs::{read_to_string, write},
+	io::AsyncWriteExt,
+	process::Command,
+};
+use url::{Origin, Url};
 
 use crate::collect::Collection;
 
@@ -14,17 +25,112 @@ const RUNTIME_ENTRY: &str = include_str!("runtime_entry.js");
 struct BundleManifest {
 	resolutions: HashMap<Url, HashMap<String, Url>>,
 	source_files: HashMap<Url, PathBuf>,
+	/// Sources that should be used instead of reading from `source_files`. Filled by plugins
+	sources: HashMap<Url, String>,
+}
+
+/// A custom stage in the bundling pipeline. Plugins are run before the bundler is invoked, with these ordering guarantees:
+///
+/// 1. `resolve` is run for every dependency of every module, by each matching plugin in the order of registration. Each plugin
+///    sees the resolution given by the previous plugin.
+/// 2. `load` is run for every module. The first matching plugin to return a source wins. If none do, the source is read from disk.
+/// 3. `transform` is run on every loaded module, by each matching plugin in the order of registration. Each plugin receives the
+///    output of the previous one.
+///
+/// A plugin is only consulted for a module if `BundlePlugin::matches` returns true for that module's specifier.
+pub trait BundlePlugin: Debug {
+	/// Whether this plugin should be consulted for the module at `specifier`
+	fn matches(&self, specifier: &Url) -> bool;
+
+	/// Resolve `dependency`, as it was imported by `host`, to a different module than `resolved`
+	fn resolve(&self, _host: &Url, _dependency: &str, _resolved: &Url) -> Option<Url> {
+		None
+	}
+
+	/// Provide the source of `specifier`, instead of it being read from disk
+	fn load(&self, _specifier: &Url) -> Option<String> {
+		None
+	}
+
+	/// Transform the source of `specifier`
+	fn transform(&self, _specifier: &Url, source: String) -> Result<String> {
+		Ok(source)
+	}
+}
+
+/// A plugin that replaces tokens with values in the modules of the runtime. Only whole identifiers are replaced, so `API_URL` is left
+/// alone in `MY_API_URL` and `config.API_URL`. Only local modules and modules from the runtime's origin are transformed, so dependencies
+/// from elsewhere, such as a cdn, are bundled as they are.
+#[derive(Debug)]
+pub struct DefinePlugin {
+	automaton: AhoCorasick,
+	values: Vec<String>,
+	runtime_origin: Origin,
+}
+
+impl DefinePlugin {
+	pub fn new(definitions: &[(String, String)], runtime: &Url) -> Result<DefinePlugin> {
+		let automaton = AhoCorasick::builder()
+			.match_kind(MatchKind::LeftmostLongest)
+			.build(definitions.iter().map(|(token, _)| token))
+			.context("failed to build a matcher for the defined tokens")?;
+
+		Ok(DefinePlugin {
+			automaton,
+			values: definitions.iter().map(|(_, value)| value.clone()).collect(),
+			runtime_origin: runtime.origin(),
+		})
+	}
+}
+
+impl BundlePlugin for DefinePlugin {
+	fn matches(&self, specifier: &Url) -> bool {
+		// the origins of local urls are opaque, so they never equal each other
+		let is_local = matches!(specifier.scheme(), "file" | "stdin") || specifier.origin() == self.runtime_origin;
+
+		!self.values.is_empty() && is_local
+	}
+
+	fn transform(&self, _: &Url, source: String) -> Result<String> {
+		let is_identifier_byte = |byte: u8| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'$';
+		let bytes = source.as_bytes();
+		let mut output = String::with_capacity(source.len());
+		let mut copied_until = 0;
+
+		for found in self.automaton.find_iter(&source) {
+			let preceding = &bytes[..found.start()];
+			// a property of another value is accessed through a single dot, but a spread has three
+			let is_property = preceding.ends_with(b".") && !preceding.ends_with(b"...");
+			let is_whole = !preceding.last().map(|byte| is_identifier_byte(*byte)).unwrap_or(false)
+				&& !bytes.get(found.end()).map(|byte| is_identifier_byte(*byte)).unwrap_or(false);
+
+			if !is_whole || is_property {
+				continue;
+			}
+
+			output.push_str(&source[copied_until..found.start()]);
+			output.push_str(&self.values[found.pattern().as_usize()]);
+			copied_until = found.end();
+		}
+
+		output.push_str(&source[copied_until..]);
+
+		Ok(output)
+	}
 }
 
 pub struct BundleParams<'a> {
 	pub bundler_url: &'a Url,
 	pub runtime_url: &'a Url,
 	pub collection: &'a Collection,
+	/// If set, the generated entry module is written here before it is bundled
+	pub entry_path: Option<&'a Path>,
 }
 
 #[derive(Debug, Default)]
 pub struct Bundler {
 	manifest: BundleManifest,
+	plugins: Vec<Box<dyn BundlePlugin>>,
 }
 
 impl Bundler {
@@ -42,14 +148,46 @@ impl Bundler {
 		self.manifest.source_files.insert(source.into(), file.into());
 	}
 
-	pub async fn bundle(self, params: BundleParams<'_>) -> Result<String> {
+	/// Register the source of a module that has no file on disk, such as a runtime that was read from stdin
+	pub fn register_source(&mut self, source: impl Into<Url>, code: impl Into<String>) {
+		self.manifest.sources.insert(source.into(), code.into());
+	}
+
+	/// The paths of every module that was loaded from a `file:` url
+	pub fn get_local_files(&self) -> Vec<PathBuf> {
+		self.manifest
+			.source_files
+			.keys()
+			.filter(|specifier| specifier.scheme() == "file")
+			.filter_map(|specifier| specifier.to_file_path().ok())
+			.collect()
+	}
+
+	/// Add a plugin to the end of the plugin pipeline. See `BundlePlugin` for ordering guarantees
+	pub fn register_plugin(&mut self, plugin: impl BundlePlugin + 'static) {
+		self.plugins.push(Box::new(plugin));
+	}
+
+	pub async fn bundle(mut self, params: BundleParams<'_>) -> Result<String> {
+		self.run_plugins().await.context("Failed to run bundle plugins")?;
+
 		let imports = {
 			let mut js = String::new();
 
 			write!(js, "import {{ createStarter, ")?;
 
-			for (_, info) in params.collection.get_component_info() {
-				write!(js, "{}, ", info.render_name)?;
+			// render functions inside of a namespace are reached through the namespace, so only the namespace is imported
+			let mut import_names = params
+				.collection
+				.get_component_info()
+				.iter()
+				.map(|(_, info)| info.render_name.split('.').next().unwrap_or_default())
+				.collect::<Vec<_>>();
+			import_names.sort();
+			import_names.dedup();
+
+			for name in import_names {
+				write!(js, "{name}, ")?;
 			}
 
 			write!(js, " }} from '{}'", params.runtime_url)?;
@@ -75,9 +213,89 @@ impl Bundler {
 
 		let entry = AhoCorasick::new(&["\"IMPORTS\"", "\"COMPONENT_CASES\""])?.replace_all(RUNTIME_ENTRY, &[imports, component_cases]);
 
+		if let Some(path) = params.entry_path {
+			write(path, &entry)
+				.await
+				.with_context(|| format!("failed to write the runtime entry to {path:?}"))?;
+			info!("Emitted runtime entry to {path:?}");
+		}
+
 		self.run_bundle_command(params.bundler_url, entry).await
 	}
 
+	async fn run_plugins(&mut self) -> Result<()> {
+		if self.plugins.is_empty() {
+			return Ok(());
+		}
+
+		for (host, dependencies) in &mut self.manifest.resolutions {
+			for (dependency, resolved) in dependencies.iter_mut() {
+				for plugin in &self.plugins {
+					if !plugin.matches(resolved) {
+						continue;
+					}
+
+					if let Some(new_resolution) = plugin.resolve(host, dependency, resolved) {
+						*resolved = new_resolution;
+					}
+				}
+			}
+		}
+
+		// modules without a source file, such as a runtime read from stdin, only have a registered source
+		let mut specifiers = self
+			.manifest
+			.source_files
+			.keys()
+			.chain(self.manifest.sources.keys())
+			.cloned()
+			.collect::<Vec<_>>();
+		specifiers.sort();
+		specifiers.dedup();
+
+		for dependencies in self.manifest.resolutions.values() {
+			for resolved in dependencies.values() {
+				if !specifiers.contains(resolved) {
+					specifiers.push(resolved.clone());
+				}
+			}
+		}
+
+		for specifier in specifiers {
+			let matching_plugins = self.plugins.iter().filter(|plugin| plugin.matches(&specifier)).collect::<Vec<_>>();
+
+			if matching_plugins.is_empty() {
+				continue;
+			}
+
+			let mut source = match matching_plugins.iter().find_map(|plugin| plugin.load(&specifier)) {
+				Some(source) => source,
+				None if self.manifest.sources.contains_key(&specifier) => self.manifest.sources[&specifier].clone(),
+				None => {
+					let file = self
+						.manifest
+						.source_files
+						.get(&specifier)
+						.ok_or_else(|| anyhow!("{specifier} was resolved by a plugin, but no plugin loaded it"))?;
+
+					read_to_string(file)
+						.await
+						.with_context(|| format!("failed to read {file:?}, the source file for {specifier}"))?
+				}
+			};
+
+			for plugin in matching_plugins {
+				source = plugin
+					.transform(&specifier, source)
+					.with_context(|| format!("failed to transform {specifier}"))?;
+			}
+
+			self.manifest.sources.insert(specifier, source);
+		}
+
+		Ok(())
+	}
+
 	async fn run_bundle_command(self, bundler_url: &Url, entry_code: impl Into<String>) -> Result<String> {
 		let mut command = Command::new("deno");
 
@@ -107,3 +325,32 @@ impl Bundler {
 		Ok(String::from_utf8(output.stdout)?)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn define(token: &str, value: &str) -> (String, String) {
+		(token.to_string(), value.to_string())
+	}
+
+	#[tokio::test]
+	async fn defines_replace_whole_identifiers_in_runtime_modules() {
+		let runtime = Url::parse("https://example.com/runtime/mod.ts").unwrap();
+		let dependency = Url::parse("https://cdn.example.org/lib.js").unwrap();
+		let source = "const url = API_URL + MY_API_URL + API_URL_2 + config.API_URL\nexport const debug = [...DEBUG, DEBUG]";
+		let mut bundler = Bundler::default();
+
+		bundler.register_source(runtime.clone(), source);
+		bundler.register_source(dependency.clone(), source);
+		bundler.register_dependency(&runtime, "https://cdn.example.org/lib.js", dependency.clone());
+		bundler.register_plugin(DefinePlugin::new(&[define("API_URL", "'https://api.example.com'"), define("DEBUG", "[false]")], &runtime).unwrap());
+		bundler.run_plugins().await.unwrap();
+
+		assert_eq!(
+			bundler.manifest.sources[&runtime],
+			"const url = 'https://api.example.com' + MY_API_URL + API_URL_2 + config.API_URL\nexport const debug = [...[false], [false]]"
+		);
+		assert_eq!(bundler.manifest.sources[&dependency], source);
+	}
+}
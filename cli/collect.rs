@@ -1,17 +1,33 @@
 use anyhow::{anyhow, Context, Error, Result};
-use deno_doc::{js_doc::JsDocTag, DocNodeKind, DocParser, DocParserOptions, Location};
-use deno_graph::{source::MemoryLoader, BuildOptions, CapturingModuleAnalyzer, GraphKind, ModuleGraph};
-use log::{debug, trace};
-use std::collections::{HashMap, HashSet};
+use clap::ValueEnum;
+use deno_doc::{js_doc::JsDocTag, DocNode, DocNodeKind, DocParser, DocParserOptions, Location};
+use deno_graph::{source::Loader, BuildOptions, CapturingModuleAnalyzer, GraphKind, ModuleGraph};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use inflector::Inflector;
+use log::{debug, info, trace};
+use objection::{
+	APP_READY_EVENT, ERROR_ACTION, EVENT_ERROR_ACTION, MOUNT_ACTION, PATH_PREFIXES_ACTION, REQUEST_ERROR_ACTION, ROOT_ACTIONS, ROOT_EVENTS,
+	SESSION_ERROR_ACTION,
+};
+use serde_json::{json, Value};
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	fmt::{self, Display, Write},
+	iter, mem,
+};
 use url::Url;
 
 use crate::{
 	asset_loader::{AssetKind, AssetsLoader},
-	convert::{convert_interface, convert_ts_type, Conversion, ConvertInterfaceParams, ConvertTsTypeParams, Kind},
+	convert::{convert_enum, convert_interface, convert_ts_type, Conversion, ConvertInterfaceParams, ConvertTsTypeParams, Kind, DEFAULT_MAX_DEPTH},
 	diagnostic::{Diagnostic, DiagnosticList},
-	writer::Writer,
+	gen_ts::gen_kind,
+	writer::{JsonStyle, Writer},
 };
 
+#[cfg(test)]
+use crate::module_loader::{register_virtual_modules, VirtualModule};
+
 #[derive(Debug)]
 pub struct KindDefinition<'a> {
 	pub name: &'a str,
@@ -30,6 +46,101 @@ struct InternalKindDefinition {
 pub struct ComponentInfo {
 	/// The name that the renderer has chosen to use to refer to the function that renders this component
 	pub render_name: String,
+	/// The event key properties of this component, by property name
+	pub events: HashMap<String, KeyInfo>,
+	/// The action key properties of this component, by property name
+	pub actions: HashMap<String, KeyInfo>,
+}
+
+#[derive(Debug)]
+pub struct KeyInfo {
+	/// The data type of the key, as it was written in the runtime
+	pub data_type: String,
+	/// The names of the types that the data type references
+	pub references: Vec<String>,
+}
+
+/// The category of an error encountered during collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CollectionErrorCategory {
+	/// A kind could not be converted
+	KindConversion,
+	/// A kind was referenced, but it was never declared
+	MissingDependency,
+	/// A function that the runtime must export was not exported
+	MissingFunction,
+	/// An error that is not tied to a single name, such as a missing component index
+	Other,
+}
+
+impl Display for CollectionErrorCategory {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CollectionErrorCategory::KindConversion => write!(f, "Failed kind conversions"),
+			CollectionErrorCategory::MissingDependency => write!(f, "Missing types"),
+			CollectionErrorCategory::MissingFunction => write!(f, "Missing functions"),
+			CollectionErrorCategory::Other => write!(f, "Other errors"),
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct CollectionError<'a> {
+	pub category: CollectionErrorCategory,
+	/// The name of the kind or function that caused this error. Only `None` for `CollectionErrorCategory::Other`
+	pub name: Option<&'a str>,
+	/// Where the item that caused this error was declared. `None` if the item was never declared, or the error is not tied to a single
+	/// declaration
+	pub location: Option<&'a Location>,
+	pub error: &'a Error,
+}
+
+/// A kind of export that is not collected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum IgnoredExport {
+	Class,
+	ModuleDoc,
+	Namespace,
+	Variable,
+}
+
+impl IgnoredExport {
+	fn describe(&self, count: usize) -> String {
+		let (singular, plural) = match self {
+			IgnoredExport::Class => ("class", "classes"),
+			IgnoredExport::ModuleDoc => ("module doc", "module docs"),
+			IgnoredExport::Namespace => ("namespace", "namespaces"),
+			IgnoredExport::Variable => ("variable", "variables"),
+		};
+
+		format!("{count} {}", if count == 1 { singular } else { plural })
+	}
+
+	/// How support for this kind of export can be enabled, if it can be
+	fn hint(&self) -> Option<&'static str> {
+		match self {
+			IgnoredExport::Namespace => Some("namespaces can be collected with --namespaces"),
+			_ => None,
+		}
+	}
+}
+
+/// The function that every runtime must export, which starts the runtime with its initial component tree
+pub const STARTER_FUNCTION_NAME: &str = "createStarter";
+
+/// Elements that the frontend parses or mounts specially, such that a component of the same name could be confused with them. See
+/// `Collection::check_reserved_names`
+pub const DEFAULT_RESERVED_NAMES: &[&str] = &[
+	"base", "body", "head", "html", "iframe", "link", "math", "meta", "noscript", "script", "slot", "style", "svg", "template", "textarea", "title",
+];
+
+/// The format that the type dependency graph can be printed in
+#[derive(Debug, ValueEnum, Clone, Copy, Default)]
+pub enum GraphFormat {
+	/// Graphviz DOT
+	#[default]
+	Dot,
+	Json,
 }
 
 #[derive(Debug, Default)]
@@ -40,21 +151,39 @@ pub struct Collection {
 	component_index_name: Option<String>,
 	kinds: HashMap<String, InternalKindDefinition>,
 	erroring_kinds: HashMap<String, Error>,
+	missing_kinds: HashSet<String>,
 	components: HashMap<String, ComponentInfo>,
+	locations: HashMap<String, Location>,
 	functions: HashSet<String>,
+	/// The name of the type that each function's first parameter references, if it has a first parameter that references a type
+	function_param_types: HashMap<String, Option<String>>,
 	erroring_functions: HashMap<String, Error>,
 	other_diagnostics: Vec<Error>,
+	collect_namespaces: bool,
+	exclude: Option<GlobSet>,
+	ignored_exports: BTreeMap<IgnoredExport, usize>,
+	/// Names that were marked with a `@deprecated` jsdoc tag, along with the tag's message
+	deprecations: HashMap<String, Option<String>>,
+	/// Lowercased names that components should not share. `None` if the check is disabled
+	reserved_names: Option<HashSet<String>>,
+	/// See `Collection::set_max_depth`
+	max_depth: Option<usize>,
+	/// See `Collection::deny_type_name_conflicts`
+	deny_type_name_conflicts: bool,
 }
 
 impl Collection {
-	pub async fn collect(&mut self, runtime_url: &Url, memory_loader: &MemoryLoader) -> Result<()> {
+	/// Collect every export of the runtime at `runtime_url`. Modules are loaded through `loader`, which must be able to load every
+	/// module that the runtime depends on. The CLI uses a `MemoryLoader` that is filled by `load_modules`, but any loader will do, such as
+	/// one that caches or sandboxes fetches.
+	pub async fn collect(&mut self, runtime_url: &Url, loader: &dyn Loader) -> Result<()> {
 		let analyzer = CapturingModuleAnalyzer::default();
 		let mut graph = ModuleGraph::new(GraphKind::TypesOnly);
 
 		let diagnostics = graph
 			.build(
 				Vec::from([runtime_url.clone()]),
-				memory_loader,
+				loader,
 				BuildOptions {
 					module_analyzer: &analyzer,
 					..Default::default()
@@ -66,9 +195,16 @@ impl Collection {
 			println!("{}", diagnostic);
 		}
 
+		self.collect_from_graph(&graph, &analyzer, runtime_url)
+	}
+
+	/// Collect every export of the runtime at `runtime_url` from a graph that has already been built, such as one held by a language
+	/// server. `analyzer` must be the analyzer that `graph` was built with, and `graph` must contain the runtime along with all of its
+	/// dependencies.
+	pub fn collect_from_graph(&mut self, graph: &ModuleGraph, analyzer: &CapturingModuleAnalyzer, runtime_url: &Url) -> Result<()> {
 		let parser = DocParser::new(
-			&graph,
-			&analyzer,
+			graph,
+			analyzer,
 			DocParserOptions {
 				diagnostics: true,
 				private: false,
@@ -80,6 +216,8 @@ impl Collection {
 		// TODO print these as they outline missing dependencies and missing return types
 		let _diagnostics = parser.take_diagnostics();
 
+		let nodes = nodes.iter().filter(|node| !self.is_excluded(&node.location)).collect::<Vec<_>>();
+
 		for node in &nodes {
 			self.consider_js_doc_tags(&node.name, &node.js_doc.tags, &node.location).with_context(|| {
 				Diagnostic::start("Encountered an issue while analyzing jsdoc tags")
@@ -105,134 +243,136 @@ impl Collection {
 		}
 
 		for node in nodes {
-			let name = node.name.clone();
+			self.collect_node(node.name.clone(), node, None)?;
+		}
 
-			match node.kind {
-				DocNodeKind::Function => {
-					self.functions.insert(name);
-				}
-				DocNodeKind::Class => Diagnostic::start("Classes are not a supported type of export and will be ignored")
-					.shift()
-					.location(&node.location)
-					.build()
-					.print_warn(),
-				DocNodeKind::Enum => {
-					Diagnostic::start("Enums are not a supported type of export and will be ignored. Use a keyed or string literal union instead")
-						.shift()
-						.location(&node.location)
-						.build()
-						.print_warn()
-				}
-				DocNodeKind::Import => (), // TODO we should figure out how to handle the "import item as anotherItem" cases
-				DocNodeKind::ModuleDoc => {
-					Diagnostic::start("Module docs are ignored. To document a specific component, place the doc comment on that component's interface")
-						.shift()
-						.location(&node.location)
-						.build()
-						.print_warn()
-				}
-				DocNodeKind::Interface => {
-					let conversion = convert_interface(ConvertInterfaceParams {
-						interface: node.interface_def.as_ref().ok_or(anyhow!("Bad deno_doc output: expected interface def."))?,
-						location: &node.location,
-						component: self.components.get_mut(&name),
-						action_key_type_name: self.action_key_type_name.as_deref(),
-						event_key_type_name: self.event_key_type_name.as_deref(),
-					});
+		Ok(())
+	}
 
-					match conversion {
-						Ok(Conversion { kind, dependencies }) => {
-							self.kinds.insert(
-								name,
-								InternalKindDefinition {
-									comment: node.js_doc.doc.clone(),
-									kind,
-									dependencies,
-								},
-							);
-						}
-						Err(error) => {
-							self.erroring_kinds.insert(
-								name,
-								error.context(
-									Diagnostic::start("Failed to convert interface ")
-										.inline_code(&node.name)
-										.shift()
-										.location(&node.location)
-										.build(),
-								),
-							);
-						}
-					};
-				}
-				DocNodeKind::Namespace => Diagnostic::start("Namespaces are not supported and will be ignored")
-					.shift()
-					.location(&node.location)
-					.build()
-					.print_warn(),
-				DocNodeKind::TypeAlias => {
-					let type_alias = node
-						.type_alias_def
-						.as_ref()
-						.ok_or(anyhow!("Bad deno_doc output: expected type alias def for node of kind type alias."))?;
-
-					if !type_alias.type_params.is_empty() {
-						self.erroring_kinds.insert(
-							name,
-							Diagnostic::start("Type parameters are not supported")
-								.shift()
-								.location(&node.location)
-								.build()
-								.error(),
-						);
-					} else {
-						let conversion = convert_ts_type(ConvertTsTypeParams {
-							ts_type: &type_alias.ts_type,
-							location: &node.location,
-							component: self.components.get_mut(&node.name),
-							action_key_type_name: self.action_key_type_name.as_deref(),
-							event_key_type_name: self.event_key_type_name.as_deref(),
-						});
-
-						match conversion {
-							Ok(Conversion { kind, dependencies }) => {
-								self.kinds.insert(
-									name,
-									InternalKindDefinition {
-										comment: node.js_doc.doc.clone(),
-										kind,
-										dependencies,
-									},
-								);
-							}
-							Err(error) => {
-								self.erroring_kinds.insert(
-									name.clone(),
-									error.context(
-										Diagnostic::start("Failed to convert type alias ")
-											.inline_code(&name)
-											.shift()
-											.location(&node.location)
-											.build()
-											.error(),
-									),
-								);
-							}
-						};
-					}
+	/// Descend into exported namespaces, collecting their contents under namespace-qualified names, such as `UI.Button`. By default,
+	/// namespaces are ignored.
+	pub fn enable_namespaces(&mut self) {
+		self.collect_namespaces = true;
+	}
+
+	/// Fail to convert types that are nested more deeply than `max_depth`, rather than recursing further. Defaults to
+	/// `convert::DEFAULT_MAX_DEPTH`.
+	pub fn set_max_depth(&mut self, max_depth: usize) {
+		self.max_depth = Some(max_depth);
+	}
+
+	/// Warn about components whose name or render function matches a reserved name, ignoring case. The reserved names are
+	/// `DEFAULT_RESERVED_NAMES` along with `extra_names`. By default, this check is disabled.
+	pub fn check_reserved_names(&mut self, extra_names: &[String]) {
+		let names = DEFAULT_RESERVED_NAMES.iter().map(|name| name.to_string()).chain(extra_names.iter().cloned());
+
+		self.reserved_names = Some(names.map(|name| name.to_lowercase()).collect());
+	}
+
+	/// Fail the build when differently shaped types would be generated under the same name, rather than only warning. See
+	/// `Collection::check_type_name_conflicts`
+	pub fn deny_type_name_conflicts(&mut self) {
+		self.deny_type_name_conflicts = true;
+	}
+
+	/// Ignore every export declared in a file whose path matches one of these globs, such as `**/*.stories.ts`. Excluded files are
+	/// still bundled if the runtime imports them.
+	pub fn exclude(&mut self, patterns: &[String]) -> Result<()> {
+		let mut builder = GlobSetBuilder::new();
+
+		for pattern in patterns {
+			builder.add(Glob::new(pattern).with_context(|| format!("invalid exclude pattern `{pattern}`"))?);
+		}
+
+		self.exclude = Some(builder.build()?);
+
+		Ok(())
+	}
+
+	fn is_excluded(&self, location: &Location) -> bool {
+		let exclude = match &self.exclude {
+			Some(exclude) => exclude,
+			None => return false,
+		};
+
+		match Url::parse(&location.filename) {
+			Ok(url) => exclude.is_match(url.path()),
+			Err(_) => exclude.is_match(&location.filename),
+		}
+	}
+
+	/// A warning for every use of a deprecated kind by a kind that is still reachable from a component, and for every deprecated
+	/// component, as the component index still references those
+	fn get_deprecation_warnings(&self) -> Vec<Diagnostic> {
+		let mut usages = Vec::new();
+
+		for (name, def) in &self.kinds {
+			for dependency in &def.dependencies {
+				if self.deprecations.contains_key(dependency) {
+					usages.push((dependency.as_str(), Some(name.as_str())));
 				}
-				DocNodeKind::Variable => Diagnostic::start(
-					"Exported variables are not supported and will be ignored. If you want to export a component render \
-					function, `export function` instead",
-				)
-				.shift()
-				.location(&node.location)
-				.build()
-				.print_error(),
 			}
 		}
 
-		Ok(())
+		for name in self.components.keys() {
+			if self.deprecations.contains_key(name) {
+				usages.push((name.as_str(), None));
+			}
+		}
+
+		usages.sort();
+		usages.dedup();
+
+		let mut warnings = Vec::new();
+
+		for (deprecated_name, user_name) in usages {
+			let mut builder = Diagnostic::start("").inline_code(deprecated_name).text(" is deprecated");
+
+			if let Some(Some(message)) = self.deprecations.get(deprecated_name) {
+				builder = builder.text(": ").text(message.trim());
+			}
+
+			builder = match user_name {
+				Some(user_name) => builder.shift().text("Referenced by ").inline_code(user_name),
+				None => builder.shift().text("It is still a component, so it is referenced by the component index"),
+			};
+
+			if let Some(location) = self.locations.get(user_name.unwrap_or(deprecated_name)) {
+				builder = builder.shift().location(location);
+			}
+
+			warnings.push(builder.build());
+		}
+
+		warnings
+	}
+
+	/// Print how many exports of each kind were ignored during collection, so that they don't get lost among other warnings
+	pub fn print_ignored_summary(&self) {
+		if let Some(summary) = self.get_ignored_summary() {
+			info!("{summary}");
+		}
+	}
+
+	/// A summary such as "Ignored 2 classes, 1 variable", along with how to collect the ignored exports where that is possible. `None`
+	/// if nothing was ignored
+	fn get_ignored_summary(&self) -> Option<String> {
+		if self.ignored_exports.is_empty() {
+			return None;
+		}
+
+		let counts = self.ignored_exports.iter().map(|(export, count)| export.describe(*count)).collect::<Vec<_>>();
+		let hints = self.ignored_exports.keys().filter_map(|export| export.hint()).collect::<Vec<_>>();
+
+		Some(if hints.is_empty() {
+			format!("Ignored {}", counts.join(", "))
+		} else {
+			format!("Ignored {} ({})", counts.join(", "), hints.join("; "))
+		})
+	}
+
+	fn ignore(&mut self, export: IgnoredExport) {
+		*self.ignored_exports.entry(export).or_default() += 1;
 	}
 
 	pub fn get_component_index_name(&self) -> Option<&str> {
@@ -250,14 +390,14 @@ impl Collection {
 		self.prune_names(unreachable_names.iter().map(|item| item.as_str()));
 		self.meet_all_dependencies();
 
-		if !self.functions.contains("createStarter") {
+		if !self.functions.contains(STARTER_FUNCTION_NAME) {
 			self.erroring_functions.insert(
-				"createStarter".to_string(),
+				STARTER_FUNCTION_NAME.to_string(),
 				Diagnostic::start("Missing function")
-					.inline_code("createStarter")
+					.inline_code(STARTER_FUNCTION_NAME)
 					.shift()
 					.text("All renderers must export a")
-					.inline_code("createStarter")
+					.inline_code(STARTER_FUNCTION_NAME)
 					.text("function")
 					.build()
 					.error(),
@@ -277,9 +417,38 @@ impl Collection {
 						.build()
 						.error(),
 				);
+
+				continue;
+			}
+
+			if let Some(warning) = self.get_render_param_warning(name, component) {
+				warning.print_warn();
+			}
+		}
+
+		for warning in self.get_deprecation_warnings() {
+			warning.print_warn();
+		}
+
+		for warning in self.get_reserved_name_warnings() {
+			warning.print_warn();
+		}
+
+		self.check_type_name_conflicts();
+
+		let mut component_names = self.components.keys().collect::<Vec<_>>();
+		component_names.sort();
+
+		for name in component_names {
+			if let Some(warning) = self.get_empty_component_warning(name, &self.components[name]) {
+				warning.print_warn();
 			}
 		}
 
+		if let Some(warning) = self.get_unused_key_types_warning() {
+			warning.print_warn();
+		}
+
 		if let Some(name) = &self.component_index_name {
 			debug!("Found `{name}` as the component index");
 		} else {
@@ -295,10 +464,191 @@ impl Collection {
 		}
 	}
 
+	/// A warning if the component `name` has no props, events, or actions. Such a component renders the same thing every time, which
+	/// usually means that its fields failed to convert
+	fn get_empty_component_warning(&self, name: &str, component: &ComponentInfo) -> Option<Diagnostic> {
+		let has_props = match self.kinds.get(name).map(|def| &def.kind) {
+			Some(Kind::Object { properties }) => !properties.is_empty(),
+			Some(_) => true,
+			None => false,
+		};
+
+		if has_props || !component.events.is_empty() || !component.actions.is_empty() {
+			return None;
+		}
+
+		let mut builder = Diagnostic::start("Component ").inline_code(name).text(" has no props, events, or actions");
+
+		if let Some(location) = self.locations.get(name) {
+			builder = builder.shift().location(location);
+		}
+
+		if let Some(error) = self.erroring_kinds.get(name) {
+			builder = builder.shift().text("Its interface failed to convert: ").text(format!("{error:#}"));
+		}
+
+		Some(builder.build())
+	}
+
+	/// A warning if the first parameter of the render function of the component `name` is typed as something other than the component
+	fn get_render_param_warning(&self, name: &str, component: &ComponentInfo) -> Option<Diagnostic> {
+		// untyped or destructured parameters can't be checked, and are assumed to be correct
+		let param_type = match self.function_param_types.get(&component.render_name) {
+			Some(Some(param_type)) => param_type,
+			_ => return None,
+		};
+
+		// within a namespace, the parameter type may be referenced by its unqualified name
+		if param_type == name || name.ends_with(&format!(".{param_type}")) {
+			return None;
+		}
+
+		let mut builder = Diagnostic::start("The first parameter of ")
+			.inline_code(&component.render_name)
+			.text(" is typed as ")
+			.inline_code(param_type)
+			.text(", but it renders ")
+			.inline_code(name);
+
+		if let Some(location) = self.locations.get(name) {
+			builder = builder.shift().location(location);
+		}
+
+		Some(builder.build())
+	}
+
+	/// Key types that no component uses usually mean that the event and action properties were typed with something else, or that
+	/// their interfaces are missing a @component tag
+	fn get_unused_key_types_warning(&self) -> Option<Diagnostic> {
+		let has_key_types = self.event_key_type_name.is_some() || self.action_key_type_name.is_some();
+		let has_keys = self
+			.components
+			.values()
+			.any(|component| !component.events.is_empty() || !component.actions.is_empty());
+
+		if !has_key_types || has_keys {
+			return None;
+		}
+
+		let warning = Diagnostic::start("Event and action key types were declared, but no component has an event or action property")
+			.shift()
+			.text("Verify that event and action properties are typed with the ")
+			.join_map(self.event_key_type_name.iter().chain(self.action_key_type_name.iter()), |builder, name| {
+				builder.inline_code(name)
+			})
+			.text(" types, and that the interfaces that declare them are tagged with @component")
+			.build();
+
+		Some(warning)
+	}
+
+	/// A warning for every component whose name or render function matches a reserved name. Empty if the check is disabled. See
+	/// `Collection::check_reserved_names`
+	fn get_reserved_name_warnings(&self) -> Vec<Diagnostic> {
+		let reserved_names = match &self.reserved_names {
+			Some(reserved_names) => reserved_names,
+			None => return Vec::new(),
+		};
+
+		let mut warnings = Vec::new();
+		let mut components = self.components.iter().collect::<Vec<_>>();
+		components.sort_by_key(|(name, _)| name.as_str());
+
+		for (name, component) in components {
+			// namespaced names can't collide, because they are always referenced through their namespace
+			let render_name = component.render_name.rsplit('.').next().unwrap_or_default();
+			let collision = match [name.as_str(), render_name]
+				.into_iter()
+				.find(|name| reserved_names.contains(&name.to_lowercase()))
+			{
+				Some(collision) => collision,
+				None => continue,
+			};
+
+			let mut builder = Diagnostic::start("Component ")
+				.inline_code(name)
+				.text(" shares the name ")
+				.inline_code(collision)
+				.text(" with an element that the frontend treats specially, which can cause it to be mounted incorrectly")
+				.shift()
+				.text("Rename it, or disable this check with --no-reserved-name-check");
+
+			if let Some(location) = self.locations.get(name) {
+				builder = builder.shift().location(location);
+			}
+
+			warnings.push(builder.build());
+		}
+
+		warnings
+	}
+
+	/// Report the names that more than one differently shaped type would be generated under. Inline types are named after where
+	/// they are declared, such as `FooOptions` for the `options` prop of `Foo`, so they can collide with each other, or with an exported
+	/// type. Bindings are generated into a single flat module, which only keeps the first type of each name.
+	fn check_type_name_conflicts(&mut self) {
+		let mut generated_names = BTreeMap::new();
+
+		for def in self.get_kinds() {
+			if Some(def.name) != self.component_index_name.as_deref() {
+				collect_generated_names(def.name.to_string(), def.name.to_string(), def.kind, &mut generated_names);
+			}
+		}
+
+		for (name, shapes) in generated_names {
+			if shapes.len() < 2 {
+				continue;
+			}
+
+			let mut builder = Diagnostic::start("Differently shaped types are all generated as ")
+				.inline_code(&name)
+				.text(", so only one of them will be correct");
+
+			for (shape, declarations) in &shapes {
+				builder = builder
+					.shift()
+					.join_map(declarations.iter(), |builder, declaration| builder.inline_code(declaration))
+					.text(" is ")
+					.inline_code(shape);
+			}
+
+			let diagnostic = builder
+				.shift()
+				.text(
+					"Export one of them as its own type with a unique name, or move the components into separate namespaces and collect them \
+					with --namespaces",
+				)
+				.build();
+
+			if self.deny_type_name_conflicts {
+				self.other_diagnostics.push(diagnostic.error());
+			} else {
+				diagnostic.print_warn();
+			}
+		}
+	}
+
 	pub fn get_comment(&self, kind_name: &str) -> Option<&str> {
 		self.kinds.get(kind_name).map(|item| item.comment.as_deref()).flatten()
 	}
 
+	pub fn get_kind(&self, name: &str) -> Option<&Kind> {
+		self.kinds.get(name).map(|def| &def.kind)
+	}
+
+	/// Every function that the runtime must export: the starter, along with the render function of every component. Sorted and
+	/// deduplicated.
+	pub fn required_functions(&self) -> Vec<String> {
+		let mut functions = iter::once(STARTER_FUNCTION_NAME.to_string())
+			.chain(self.components.values().map(|component| component.render_name.clone()))
+			.collect::<Vec<_>>();
+
+		functions.sort();
+		functions.dedup();
+
+		functions
+	}
+
 	pub fn get_component_info(&self) -> Vec<(&str, &ComponentInfo)> {
 		self.components.iter().map(|(name, info)| (name.as_str(), info)).collect()
 	}
@@ -360,27 +710,42 @@ impl Collection {
 		for name in names {
 			self.kinds.remove(name);
 			self.erroring_kinds.remove(name);
+			self.missing_kinds.remove(name);
 		}
 	}
 
 	pub fn meet_all_dependencies(&mut self) {
 		let mut missing = HashMap::<String, Vec<String>>::new();
 
-		for (name, InternalKindDefinition { dependencies, .. }) in &self.kinds {
-			for dependency in dependencies {
-				if self.kinds.contains_key(dependency) || self.erroring_kinds.contains_key(dependency) {
-					continue;
-				}
+		let kind_dependencies = self
+			.kinds
+			.iter()
+			.flat_map(|(name, InternalKindDefinition { dependencies, .. })| dependencies.iter().map(move |dependency| (name.clone(), dependency)));
 
-				if let Some(dependents) = missing.get_mut(dependency) {
-					dependents.push(name.clone());
-				} else {
-					missing.insert(dependency.clone(), Vec::from([name.clone()]));
-				}
+		// event and action data types are checked on their own, so that a typo'd data type is reported against the key that uses it
+		let key_dependencies = self.components.iter().flat_map(|(name, component)| {
+			let events = component.events.iter().map(move |(key, info)| (format!("{name}.{key} event"), info));
+			let actions = component.actions.iter().map(move |(key, info)| (format!("{name}.{key} action"), info));
+
+			events
+				.chain(actions)
+				.flat_map(|(dependent, info)| info.references.iter().map(move |reference| (dependent.clone(), reference)))
+		});
+
+		for (dependent, dependency) in kind_dependencies.chain(key_dependencies) {
+			if self.kinds.contains_key(dependency) || self.erroring_kinds.contains_key(dependency) {
+				continue;
+			}
+
+			let dependents = missing.entry(dependency.clone()).or_default();
+
+			if !dependents.contains(&dependent) {
+				dependents.push(dependent);
 			}
 		}
 
 		for (name, dependents) in missing {
+			self.missing_kinds.insert(name.clone());
 			self.erroring_kinds.insert(
 				name.clone(),
 				Diagnostic::start("Missing type ")
@@ -395,14 +760,140 @@ impl Collection {
 	}
 
 	pub fn get_errors(&self) -> Vec<&Error> {
-		let kind_errors = self
-			.erroring_kinds
-			.values()
-			.chain(self.erroring_functions.values())
-			.chain(self.other_diagnostics.iter())
+		self.get_categorized_errors().into_iter().map(|error| error.error).collect()
+	}
+
+	/// Get all errors, tagged with their category, and sorted by category, then by name
+	pub fn get_categorized_errors(&self) -> Vec<CollectionError> {
+		let kind_errors = self.erroring_kinds.iter().map(|(name, error)| CollectionError {
+			category: if self.missing_kinds.contains(name) {
+				CollectionErrorCategory::MissingDependency
+			} else {
+				CollectionErrorCategory::KindConversion
+			},
+			name: Some(name.as_str()),
+			location: self.locations.get(name),
+			error,
+		});
+		let function_errors = self.erroring_functions.iter().map(|(name, error)| CollectionError {
+			category: CollectionErrorCategory::MissingFunction,
+			name: Some(name.as_str()),
+			// a missing render function is attributed to the component that requires it
+			location: self
+				.components
+				.iter()
+				.find(|(_, component)| &component.render_name == name)
+				.and_then(|(component_name, _)| self.locations.get(component_name)),
+			error,
+		});
+		let other_errors = self.other_diagnostics.iter().map(|error| CollectionError {
+			category: CollectionErrorCategory::Other,
+			name: None,
+			location: None,
+			error,
+		});
+
+		let mut errors = kind_errors.chain(function_errors).chain(other_errors).collect::<Vec<_>>();
+		errors.sort_by(|a, b| a.category.cmp(&b.category).then(a.name.cmp(&b.name)));
+
+		errors
+	}
+
+	/// Describe every event and action key that the runtime can produce, including the built-in keys that every runtime uses. Component keys
+	/// have dynamic paths, because event paths are scoped by the engine and action paths are generated when the key is created.
+	pub fn get_protocol(&self) -> Value {
+		let index_name = self.component_index_name.as_deref();
+		let get_root_key = |name: &str| {
+			let (data_type, references): (Value, Vec<&str>) = match name {
+				APP_READY_EVENT => (json!("{ token?: string }"), Vec::new()),
+				MOUNT_ACTION => (json!(index_name), index_name.into_iter().collect()),
+				ERROR_ACTION | REQUEST_ERROR_ACTION | SESSION_ERROR_ACTION => (json!("string"), Vec::new()),
+				EVENT_ERROR_ACTION => (json!("{ index: number; message: string }"), Vec::new()),
+				PATH_PREFIXES_ACTION => (json!("Record<string, string[]>"), Vec::new()),
+				_ => (json!("unknown"), Vec::new()),
+			};
+
+			json!({
+				"component": null,
+				"name": name,
+				"dataType": data_type,
+				"references": references,
+				"path": [name],
+				"isDynamic": false,
+			})
+		};
+		let mut events = ROOT_EVENTS.iter().map(|name| get_root_key(name)).collect::<Vec<_>>();
+		let mut actions = ROOT_ACTIONS.iter().map(|name| get_root_key(name)).collect::<Vec<_>>();
+
+		let mut components = self.components.iter().collect::<Vec<_>>();
+		components.sort_by_key(|(name, _)| name.as_str());
+
+		for (component_name, component) in components {
+			for (keys, output) in [(&component.events, &mut events), (&component.actions, &mut actions)] {
+				let mut keys = keys.iter().collect::<Vec<_>>();
+				keys.sort_by_key(|(name, _)| name.as_str());
+
+				output.extend(keys.into_iter().map(|(name, info)| {
+					json!({
+						"component": component_name,
+						"name": name,
+						"dataType": info.data_type,
+						"references": info.references,
+						"path": null,
+						"isDynamic": true,
+					})
+				}));
+			}
+		}
+
+		json!({ "events": events, "actions": actions })
+	}
+
+	/// Render the type dependency graph. Components are drawn as boxes, and erroring kinds are drawn in red
+	pub fn get_graph(&self, format: GraphFormat, json_style: JsonStyle) -> Result<String> {
+		let mut nodes = self
+			.kinds
+			.keys()
+			.map(|name| (name.as_str(), if self.components.contains_key(name) { "component" } else { "kind" }))
+			.chain(self.erroring_kinds.keys().map(|name| (name.as_str(), "erroring_kind")))
 			.collect::<Vec<_>>();
+		nodes.sort();
 
-		kind_errors
+		let mut edges = self
+			.kinds
+			.iter()
+			.flat_map(|(name, def)| def.dependencies.iter().map(move |dependency| (name.as_str(), dependency.as_str())))
+			.collect::<Vec<_>>();
+		edges.sort();
+		edges.dedup();
+
+		match format {
+			GraphFormat::Dot => {
+				let mut dot = String::from("digraph runtime {\n");
+
+				for (name, node_kind) in nodes {
+					let attributes = match node_kind {
+						"component" => " [shape=box]",
+						"erroring_kind" => " [color=red, fontcolor=red]",
+						_ => "",
+					};
+
+					writeln!(dot, "\t{name:?}{attributes};").unwrap();
+				}
+
+				for (from, to) in edges {
+					writeln!(dot, "\t{from:?} -> {to:?};").unwrap();
+				}
+
+				dot.push('}');
+
+				Ok(dot)
+			}
+			GraphFormat::Json => json_style.serialize(&json!({
+				"nodes": nodes.iter().map(|(name, kind)| json!({ "name": name, "kind": kind })).collect::<Vec<Value>>(),
+				"edges": edges.iter().map(|(from, to)| json!({ "from": from, "to": to })).collect::<Vec<Value>>(),
+			})),
+		}
 	}
 
 	pub fn get_kinds(&self) -> Vec<KindDefinition> {
@@ -421,20 +912,271 @@ impl Collection {
 		kinds
 	}
 
-	pub fn finish(self) -> AssetsLoader {
-		self.assets_loader
+	/// Take the assets loader, leaving an empty one in its place, so that assets can be loaded while the collection is still in use
+	pub fn take_assets_loader(&mut self) -> AssetsLoader {
+		mem::take(&mut self.assets_loader)
 	}
 
-	fn consider_js_doc_tags(&mut self, node_name: &str, tags: &[JsDocTag], location: &Location) -> Result<()> {
-		let mut component = None;
+	/// Collect a single node under `name`. If the node is inside of a namespace, `namespace` is the qualified name of the namespace,
+	/// along with the unqualified names of everything declared in it, so that references to siblings can be qualified.
+	fn collect_node(&mut self, name: String, node: &DocNode, namespace: Option<(&str, &HashSet<String>)>) -> Result<()> {
+		let is_kind = matches!(node.kind, DocNodeKind::Interface | DocNodeKind::TypeAlias | DocNodeKind::Enum);
+
+		// re-exports can surface the same name from multiple modules, and last-write-wins would silently drop one of the types
+		if is_kind && (self.kinds.contains_key(&name) || self.erroring_kinds.contains_key(&name)) {
+			let existing_location = self.locations.get(&name);
+
+			// the same declaration, reached through more than one re-export
+			if existing_location == Some(&node.location) {
+				return Ok(());
+			}
+
+			let mut builder = Diagnostic::start("The name ")
+				.inline_code(&name)
+				.text(" is exported by more than one declaration. Rename one of them, so that each exported type has a unique name");
+
+			if let Some(existing_location) = existing_location {
+				builder = builder.shift().location(existing_location);
+			}
+
+			self.kinds.remove(&name);
+			self.erroring_kinds.insert(name, builder.shift().location(&node.location).build().error());
+
+			return Ok(());
+		}
+
+		self.locations.insert(name.clone(), node.location.clone());
+
+		match node.kind {
+			DocNodeKind::Function => {
+				let param_type = node
+					.function_def
+					.as_ref()
+					.and_then(|def| def.params.first())
+					.and_then(|param| param.ts_type.as_ref())
+					.and_then(|ts_type| ts_type.type_ref.as_ref())
+					.map(|type_ref| type_ref.type_name.clone());
+
+				self.function_param_types.insert(name.clone(), param_type);
+				self.functions.insert(name);
+			}
+			DocNodeKind::Class => {
+				self.ignore(IgnoredExport::Class);
+
+				Diagnostic::start("Classes are not a supported type of export and will be ignored")
+					.shift()
+					.location(&node.location)
+					.build()
+					.print_warn()
+			}
+			DocNodeKind::Enum => {
+				let conversion = convert_enum(
+					node.enum_def.as_ref().ok_or(anyhow!("Bad deno_doc output: expected enum def."))?,
+					&node.location,
+				);
+
+				match conversion {
+					Ok(Conversion { kind, dependencies }) => {
+						self.kinds.insert(
+							name,
+							InternalKindDefinition {
+								comment: node.js_doc.doc.clone(),
+								kind,
+								dependencies,
+							},
+						);
+					}
+					Err(error) => {
+						self.erroring_kinds.insert(
+							name,
+							error.context(
+								Diagnostic::start("Failed to convert enum ")
+									.inline_code(&name)
+									.shift()
+									.location(&node.location)
+									.build(),
+							),
+						);
+					}
+				};
+			}
+			DocNodeKind::Import => (), // TODO we should figure out how to handle the "import item as anotherItem" cases
+			DocNodeKind::ModuleDoc => {
+				self.ignore(IgnoredExport::ModuleDoc);
+
+				Diagnostic::start("Module docs are ignored. To document a specific component, place the doc comment on that component's interface")
+					.shift()
+					.location(&node.location)
+					.build()
+					.print_warn()
+			}
+			DocNodeKind::Interface => {
+				let conversion = convert_interface(ConvertInterfaceParams {
+					interface: node.interface_def.as_ref().ok_or(anyhow!("Bad deno_doc output: expected interface def."))?,
+					location: &node.location,
+					component: self.components.get_mut(&name),
+					action_key_type_name: self.action_key_type_name.as_deref(),
+					event_key_type_name: self.event_key_type_name.as_deref(),
+					max_depth: self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+				});
+
+				match conversion {
+					Ok(Conversion { mut kind, mut dependencies }) => {
+						self.qualify_references(&name, &mut kind, &mut dependencies, namespace);
+						self.kinds.insert(
+							name,
+							InternalKindDefinition {
+								comment: node.js_doc.doc.clone(),
+								kind,
+								dependencies,
+							},
+						);
+					}
+					Err(error) => {
+						self.erroring_kinds.insert(
+							name,
+							error.context(
+								Diagnostic::start("Failed to convert interface ")
+									.inline_code(&name)
+									.shift()
+									.location(&node.location)
+									.build(),
+							),
+						);
+					}
+				};
+			}
+			DocNodeKind::Namespace => match (&node.namespace_def, self.collect_namespaces) {
+				(Some(namespace_def), true) => {
+					let siblings = namespace_def.elements.iter().map(|element| element.name.clone()).collect::<HashSet<_>>();
+
+					for element in &namespace_def.elements {
+						self.consider_js_doc_tags(&format!("{name}.{}", element.name), &element.js_doc.tags, &element.location)
+							.with_context(|| {
+								Diagnostic::start("Encountered an issue while analyzing jsdoc tags")
+									.shift()
+									.location(&element.location)
+									.build()
+							})?;
+					}
+
+					for element in &namespace_def.elements {
+						self.collect_node(format!("{name}.{}", element.name), element, Some((name.as_str(), &siblings)))?;
+					}
+				}
+				_ => {
+					self.ignore(IgnoredExport::Namespace);
+
+					Diagnostic::start("Namespaces are ignored unless namespace collection is enabled (see --namespaces)")
+						.shift()
+						.location(&node.location)
+						.build()
+						.print_warn()
+				}
+			},
+			DocNodeKind::TypeAlias => {
+				let type_alias = node
+					.type_alias_def
+					.as_ref()
+					.ok_or(anyhow!("Bad deno_doc output: expected type alias def for node of kind type alias."))?;
+
+				if !type_alias.type_params.is_empty() {
+					self.erroring_kinds.insert(
+						name,
+						Diagnostic::start("Type parameters are not supported")
+							.shift()
+							.location(&node.location)
+							.build()
+							.error(),
+					);
+				} else {
+					let conversion = convert_ts_type(ConvertTsTypeParams {
+						ts_type: &type_alias.ts_type,
+						location: &node.location,
+						component: self.components.get_mut(&name),
+						action_key_type_name: self.action_key_type_name.as_deref(),
+						event_key_type_name: self.event_key_type_name.as_deref(),
+						depth: 0,
+						max_depth: self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH),
+					});
+
+					match conversion {
+						Ok(Conversion { mut kind, mut dependencies }) => {
+							self.qualify_references(&name, &mut kind, &mut dependencies, namespace);
+							self.kinds.insert(
+								name,
+								InternalKindDefinition {
+									comment: node.js_doc.doc.clone(),
+									kind,
+									dependencies,
+								},
+							);
+						}
+						Err(error) => {
+							self.erroring_kinds.insert(
+								name.clone(),
+								error.context(
+									Diagnostic::start("Failed to convert type alias ")
+										.inline_code(&name)
+										.shift()
+										.location(&node.location)
+										.build()
+										.error(),
+								),
+							);
+						}
+					};
+				}
+			}
+			DocNodeKind::Variable => {
+				self.ignore(IgnoredExport::Variable);
+
+				Diagnostic::start(
+					"Exported variables are not supported and will be ignored. If you want to export a component render \
+					function, `export function` instead",
+				)
+				.shift()
+				.location(&node.location)
+				.build()
+				.print_error()
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Qualify references to the siblings of a namespaced node, so that `Color` inside of `namespace UI` becomes `UI.Color`
+	fn qualify_references(&mut self, name: &str, kind: &mut Kind, dependencies: &mut [String], namespace: Option<(&str, &HashSet<String>)>) {
+		let (namespace, siblings) = match namespace {
+			Some(namespace) => namespace,
+			None => return,
+		};
+
+		qualify_kind(kind, namespace, siblings);
+		qualify_names(dependencies, namespace, siblings);
+
+		if let Some(component) = self.components.get_mut(name) {
+			for info in component.events.values_mut().chain(component.actions.values_mut()) {
+				qualify_names(&mut info.references, namespace, siblings);
+			}
+		}
+	}
+
+	fn consider_js_doc_tags(&mut self, node_name: &str, tags: &[JsDocTag], location: &Location) -> Result<()> {
+		let mut component = None;
 		let mut is_feature_action_key = false;
 		let mut is_feature_event_key = false;
 
 		for tag in tags {
+			if let JsDocTag::Deprecated { doc } = tag {
+				self.deprecations.insert(node_name.to_string(), doc.as_deref().map(|doc| doc.to_string()));
+			}
+
 			if let JsDocTag::Unsupported { value } = tag {
-				let mut words = value.split_whitespace().rev().collect::<Vec<_>>();
-				let label = words.pop().unwrap();
-				let context = words.pop();
+				let (label, context) = match parse_js_doc_tag(value) {
+					Some(tag) => tag,
+					None => continue,
+				};
 
 				if label == "@component" {
 					component = Some(context.map(|inner| inner.to_string()).unwrap_or(format!("{node_name}Render")));
@@ -468,9 +1210,601 @@ impl Collection {
 		}
 
 		if let Some(render_name) = component {
-			self.components.insert(node_name.to_string(), ComponentInfo { render_name });
+			self.components.insert(
+				node_name.to_string(),
+				ComponentInfo {
+					render_name,
+					events: HashMap::new(),
+					actions: HashMap::new(),
+				},
+			);
 		}
 
 		Ok(())
 	}
 }
+
+/// Split the value of a jsdoc tag, such as `@component CustomName`, into the name of the tag and its first argument, regardless of how
+/// much whitespace surrounds them. `None` if the value is blank.
+fn parse_js_doc_tag(value: &str) -> Option<(&str, Option<&str>)> {
+	let mut words = value.split_whitespace();
+	let label = words.next()?;
+
+	Some((label, words.next()))
+}
+
+fn qualify_names(names: &mut [String], namespace: &str, siblings: &HashSet<String>) {
+	for name in names {
+		if siblings.contains(name.as_str()) {
+			*name = format!("{namespace}.{name}");
+		}
+	}
+}
+
+fn qualify_kind(kind: &mut Kind, namespace: &str, siblings: &HashSet<String>) {
+	match kind {
+		Kind::Dynamic | Kind::String | Kind::Number | Kind::Bool | Kind::Null | Kind::StringEnum { .. } | Kind::ValueEnum { .. } => (),
+		Kind::Ref { name } => {
+			if siblings.contains(name.as_str()) {
+				*name = format!("{namespace}.{name}");
+			}
+		}
+		Kind::ActionKey { data_type } | Kind::EventKey { data_type } => qualify_kind(data_type, namespace, siblings),
+		Kind::List { of } => qualify_kind(of, namespace, siblings),
+		Kind::Map { key, value } => {
+			qualify_kind(key, namespace, siblings);
+			qualify_kind(value, namespace, siblings);
+		}
+		Kind::Tuple { items } => {
+			for item in items {
+				qualify_kind(item, namespace, siblings);
+			}
+		}
+		Kind::KeyedEnum { variants } => {
+			for variant in variants {
+				qualify_kind(&mut variant.kind, namespace, siblings);
+			}
+		}
+		Kind::Object { properties } => {
+			for property in properties {
+				qualify_kind(&mut property.kind, namespace, siblings);
+			}
+		}
+	}
+}
+
+/// Record the name that every named type within `kind` will be generated under, along with its shape and the path at which it is
+/// declared. `context_name` is the name of `kind` itself. Names are derived in the same way as `RustGen` derives them.
+fn collect_generated_names(context_name: String, path: String, kind: &Kind, names: &mut BTreeMap<String, BTreeMap<String, Vec<String>>>) {
+	match kind {
+		Kind::ActionKey { data_type } => collect_generated_names(format!("{context_name}ActionData"), path, data_type, names),
+		Kind::EventKey { data_type } => collect_generated_names(format!("{context_name}EventData"), path, data_type, names),
+		Kind::List { of } => collect_generated_names(format!("{context_name}Item"), path, of, names),
+		Kind::Map { key, value } => {
+			collect_generated_names(format!("{context_name}Key"), path.clone(), key, names);
+			collect_generated_names(format!("{context_name}Value"), path, value, names);
+		}
+		Kind::Tuple { items } => {
+			for (index, item) in items.iter().enumerate() {
+				collect_generated_names(format!("{context_name}Item{index}"), format!("{path}[{index}]"), item, names);
+			}
+		}
+		Kind::KeyedEnum { variants } => {
+			for variant in variants {
+				let variant_context_name = format!("{context_name}{}", variant.name);
+
+				collect_generated_names(variant_context_name, format!("{path}.{}", variant.name), &variant.kind, names);
+			}
+		}
+		Kind::Object { properties } => {
+			for property in properties {
+				let property_context_name = format!("{context_name}{}", property.name.to_pascal_case());
+
+				collect_generated_names(property_context_name, format!("{path}.{}", property.name), &property.kind, names);
+			}
+		}
+		_ => (),
+	}
+
+	// other kinds are written inline, so they don't take a name
+	let is_named = matches!(
+		kind,
+		Kind::StringEnum { .. } | Kind::ValueEnum { .. } | Kind::KeyedEnum { .. } | Kind::Object { .. }
+	);
+
+	if is_named {
+		names.entry(context_name).or_default().entry(gen_kind(kind)).or_default().push(path);
+	}
+}
+
+/// Collect `source` as the runtime, without touching the filesystem or invoking deno
+#[cfg(test)]
+pub async fn collect_source(source: &str) -> Collection {
+	collect_virtual_modules(&[VirtualModule {
+		specifier: Url::parse("file:///runtime.ts").unwrap(),
+		source: source.to_string(),
+		dependencies: Vec::new(),
+	}])
+	.await
+}
+
+/// Collect the first of `modules`, which may import the rest
+#[cfg(test)]
+pub async fn collect_virtual_modules(modules: &[VirtualModule]) -> Collection {
+	collect_virtual_modules_into(Collection::default(), modules).await
+}
+
+/// Like `collect_virtual_modules`, but into a `collection` that has already been configured, such as with
+/// `Collection::enable_namespaces`
+#[cfg(test)]
+pub async fn collect_virtual_modules_into(mut collection: Collection, modules: &[VirtualModule]) -> Collection {
+	use crate::bundle::Bundler;
+	use deno_graph::source::MemoryLoader;
+
+	let mut loader = MemoryLoader::default();
+
+	register_virtual_modules(modules, &mut loader, &mut Bundler::default());
+	collection.collect(&modules[0].specifier, &loader).await.unwrap();
+
+	collection
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::bundle::Bundler;
+	use deno_graph::source::{LoadFuture, LoadOptions, MemoryLoader};
+	use std::sync::Mutex;
+
+	/// Loads modules from a `MemoryLoader`, recording every specifier that was requested
+	#[derive(Default)]
+	struct RecordingLoader {
+		inner: MemoryLoader,
+		requested: Mutex<Vec<Url>>,
+	}
+
+	impl Loader for RecordingLoader {
+		fn load(&self, specifier: &Url, options: LoadOptions) -> LoadFuture {
+			self.requested.lock().unwrap().push(specifier.clone());
+
+			self.inner.load(specifier, options)
+		}
+	}
+
+	#[tokio::test]
+	async fn components_are_collected_across_virtual_modules() {
+		let runtime_url = Url::parse("file:///runtime.ts").unwrap();
+		let button_url = Url::parse("file:///button.ts").unwrap();
+		let collection = collect_virtual_modules(&[
+			VirtualModule {
+				specifier: runtime_url,
+				source: "export * from './button.ts'\n\n/**\n * @component\n */\nexport interface Label {\n\ttext: string\n}\n".to_string(),
+				dependencies: Vec::from([("./button.ts".to_string(), button_url.clone())]),
+			},
+			VirtualModule {
+				specifier: button_url,
+				source: "/**\n * @component CustomButton\n */\nexport interface Button {\n\tlabel: string\n}\n".to_string(),
+				dependencies: Vec::new(),
+			},
+		])
+		.await;
+		let mut components = collection
+			.get_component_info()
+			.into_iter()
+			.map(|(name, info)| (name, info.render_name.as_str()))
+			.collect::<Vec<_>>();
+		components.sort();
+
+		assert_eq!(components, [("Button", "CustomButton"), ("Label", "LabelRender")]);
+		assert!(collection.get_kind("Button").is_some());
+	}
+
+	#[tokio::test]
+	async fn custom_loaders_see_every_module_that_is_collected() {
+		let runtime_url = Url::parse("file:///runtime.ts").unwrap();
+		let button_url = Url::parse("file:///button.ts").unwrap();
+		let mut loader = RecordingLoader::default();
+		let mut collection = Collection::default();
+
+		register_virtual_modules(
+			&[
+				VirtualModule {
+					specifier: runtime_url.clone(),
+					source: "export * from './button.ts'\n".to_string(),
+					dependencies: Vec::from([("./button.ts".to_string(), button_url.clone())]),
+				},
+				VirtualModule {
+					specifier: button_url.clone(),
+					source: "/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n}\n".to_string(),
+					dependencies: Vec::new(),
+				},
+			],
+			&mut loader.inner,
+			&mut Bundler::default(),
+		);
+		collection.collect(&runtime_url, &loader).await.unwrap();
+
+		let mut requested = loader.requested.into_inner().unwrap();
+		requested.sort();
+		requested.dedup();
+
+		assert_eq!(requested, [button_url, runtime_url]);
+		assert!(collection.get_component_info().into_iter().any(|(name, _)| name == "Button"));
+	}
+
+	#[tokio::test]
+	async fn collecting_from_a_pre_built_graph_matches_collecting_from_scratch() {
+		let source = "/**\n * @feature_component_index\n */\nexport interface Component {\n\tid: number\n}\n\n/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n\tsize?: 'small' | 'large'\n}\n";
+		let runtime_url = Url::parse("file:///runtime.ts").unwrap();
+		let mut loader = MemoryLoader::default();
+		let analyzer = CapturingModuleAnalyzer::default();
+		let mut graph = ModuleGraph::new(GraphKind::TypesOnly);
+		let mut collection = Collection::default();
+
+		register_virtual_modules(
+			&[VirtualModule {
+				specifier: runtime_url.clone(),
+				source: source.to_string(),
+				dependencies: Vec::new(),
+			}],
+			&mut loader,
+			&mut Bundler::default(),
+		);
+		graph
+			.build(
+				Vec::from([runtime_url.clone()]),
+				&loader,
+				BuildOptions {
+					module_analyzer: &analyzer,
+					..Default::default()
+				},
+			)
+			.await;
+		collection.collect_from_graph(&graph, &analyzer, &runtime_url).unwrap();
+
+		let from_scratch = collect_source(source).await;
+		let describe = |collection: &Collection| {
+			let mut components = collection.get_component_info();
+			components.sort_by_key(|(name, _)| *name);
+
+			format!("{:?}", (collection.get_kinds(), components, collection.get_protocol()))
+		};
+
+		assert!(collection.get_kind("Button").is_some());
+		assert_eq!(describe(&collection), describe(&from_scratch));
+	}
+
+	#[tokio::test]
+	async fn the_same_name_exported_by_two_modules_is_a_conflict() {
+		let runtime_url = Url::parse("file:///runtime.ts").unwrap();
+		let first_url = Url::parse("file:///first.ts").unwrap();
+		let second_url = Url::parse("file:///second.ts").unwrap();
+		let collection = collect_virtual_modules(&[
+			VirtualModule {
+				specifier: runtime_url,
+				source: "export * from './first.ts'\nexport * from './second.ts'\n".to_string(),
+				dependencies: Vec::from([("./first.ts".to_string(), first_url.clone()), ("./second.ts".to_string(), second_url.clone())]),
+			},
+			VirtualModule {
+				specifier: first_url,
+				source: "export interface Button {\n\tlabel: string\n}\nexport interface Card {\n\ttitle: string\n}\n".to_string(),
+				dependencies: Vec::new(),
+			},
+			VirtualModule {
+				specifier: second_url,
+				source: "export interface Button {\n\ttext: string\n}\n".to_string(),
+				dependencies: Vec::new(),
+			},
+		])
+		.await;
+		let errors = collection.get_categorized_errors();
+		let conflict = format!("{:?}", errors.iter().find(|error| error.name == Some("Button")).unwrap().error);
+
+		assert!(conflict.contains("is exported by more than one declaration"));
+		assert!(conflict.contains("file:///first.ts:1:"));
+		assert!(conflict.contains("file:///second.ts:1:"));
+		assert!(collection.get_kind("Button").is_none());
+		assert!(collection.get_kind("Card").is_some());
+	}
+
+	#[tokio::test]
+	async fn excluded_files_contribute_no_components() {
+		let runtime_url = Url::parse("file:///runtime.ts").unwrap();
+		let button_url = Url::parse("file:///button.ts").unwrap();
+		let stories_url = Url::parse("file:///button.stories.ts").unwrap();
+		let mut collection = Collection::default();
+		collection.exclude(&["**/*.stories.ts".to_string()]).unwrap();
+
+		let mut collection = collect_virtual_modules_into(
+			collection,
+			&[
+				VirtualModule {
+					specifier: runtime_url,
+					source: "export * from './button.ts'\nexport * from './button.stories.ts'\n".to_string(),
+					dependencies: Vec::from([
+						("./button.ts".to_string(), button_url.clone()),
+						("./button.stories.ts".to_string(), stories_url.clone()),
+					]),
+				},
+				VirtualModule {
+					specifier: button_url,
+					source: "/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n}\n".to_string(),
+					dependencies: Vec::new(),
+				},
+				VirtualModule {
+					specifier: stories_url,
+					source: "/**\n * @component\n */\nexport interface ButtonStory {\n\tbutton: Button\n}\nexport function renderStory(): void {}\n"
+						.to_string(),
+					dependencies: Vec::new(),
+				},
+			],
+		)
+		.await;
+		collection.check_components();
+
+		let components = collection.get_component_info().into_iter().map(|(name, _)| name).collect::<Vec<_>>();
+
+		assert_eq!(components, ["Button"]);
+		assert!(collection.get_kind("ButtonStory").is_none());
+		assert!(collection.get_errors().iter().all(|error| !error.to_string().contains("ButtonStory")));
+	}
+
+	#[test]
+	fn js_doc_tags_are_split_into_their_name_and_first_argument() {
+		assert_eq!(parse_js_doc_tag("@component"), Some(("@component", None)));
+		assert_eq!(parse_js_doc_tag("@component CustomName"), Some(("@component", Some("CustomName"))));
+		assert_eq!(parse_js_doc_tag("  @component \t CustomName  \n"), Some(("@component", Some("CustomName"))));
+		assert_eq!(parse_js_doc_tag("@component CustomName and more"), Some(("@component", Some("CustomName"))));
+		assert_eq!(parse_js_doc_tag("@unknown value"), Some(("@unknown", Some("value"))));
+		assert_eq!(parse_js_doc_tag(" \t "), None);
+		assert_eq!(parse_js_doc_tag(""), None);
+	}
+
+	#[tokio::test]
+	async fn render_names_are_read_from_component_tags() {
+		let collection = collect_source(
+			"/**\n * @component\n */\nexport interface Plain {\n\tid: number\n}\n\n/**\n * @component   CustomRender  \n */\nexport interface Spaced {\n\tid: number\n}\n\n/**\n * @unknown Other\n */\nexport interface Untagged {\n\tid: number\n}\n",
+		)
+		.await;
+		let mut components = collection
+			.get_component_info()
+			.into_iter()
+			.map(|(name, info)| (name, info.render_name.as_str()))
+			.collect::<Vec<_>>();
+		components.sort();
+
+		assert_eq!(components, [("Plain", "PlainRender"), ("Spaced", "CustomRender")]);
+	}
+
+	#[tokio::test]
+	async fn differently_shaped_types_with_the_same_generated_name_conflict() {
+		let mut collection = collect_source(
+			"/**\n * @component\n */\nexport interface Menu {\n\titemOptions: { size: number }\n\tfooterOptions: { label: string }\n}\n\n/**\n * @component\n */\nexport interface MenuItem {\n\toptions: { label: string }\n}\n\n/**\n * @component\n */\nexport interface MenuFooter {\n\toptions: { label: string }\n}\n",
+		)
+		.await;
+		collection.deny_type_name_conflicts();
+		collection.check_components();
+
+		let conflicts = collection
+			.get_errors()
+			.into_iter()
+			.map(|error| error.to_string())
+			.filter(|message| message.contains("Differently shaped types"))
+			.collect::<Vec<_>>();
+
+		// `MenuFooterOptions` is generated twice, but with the same shape both times
+		assert_eq!(conflicts.len(), 1);
+		assert!(conflicts[0].contains("`MenuItemOptions`"));
+		assert!(conflicts[0].contains("`Menu.itemOptions`"));
+		assert!(conflicts[0].contains("`MenuItem.options`"));
+	}
+
+	#[tokio::test]
+	async fn missing_event_data_types_are_reported_against_their_key() {
+		let mut collection = collect_source(
+			"/**\n * @feature_event_key\n */\nexport type EventKey<T> = { eventPath: string[] }\n\n/**\n * @component\n */\nexport interface Form {\n\tonSubmit: EventKey<SubmitData>\n\tonReset: EventKey<ResetData>\n}\n\nexport interface ResetData {\n\tid: number\n}\n",
+		)
+		.await;
+		collection.meet_all_dependencies();
+
+		let missing = collection
+			.get_errors()
+			.into_iter()
+			.map(|error| error.to_string())
+			.filter(|message| message.contains("Missing type"))
+			.collect::<Vec<_>>();
+
+		assert_eq!(missing.len(), 1);
+		assert!(missing[0].contains("`SubmitData`"));
+		assert!(missing[0].contains("`Form.onSubmit event`"));
+	}
+
+	#[tokio::test]
+	async fn errors_are_tagged_with_their_category() {
+		let mut collection = collect_source(
+			"/**\n * @component\n */\nexport interface Button {\n\titem: MissingItem\n\tform: Broken\n}\n\nexport interface Broken {\n\tsubmit(): void\n}\n",
+		)
+		.await;
+		collection.check_components();
+
+		let errors = collection
+			.get_categorized_errors()
+			.into_iter()
+			.map(|error| (error.category, error.name))
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			errors,
+			[
+				(CollectionErrorCategory::KindConversion, Some("Broken")),
+				(CollectionErrorCategory::MissingDependency, Some("MissingItem")),
+				(CollectionErrorCategory::MissingFunction, Some("ButtonRender")),
+				(CollectionErrorCategory::MissingFunction, Some(STARTER_FUNCTION_NAME)),
+				(CollectionErrorCategory::Other, None),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn the_dot_graph_distinguishes_components_and_erroring_kinds() {
+		let collection = collect_source(
+			"/**\n * @component\n */\nexport interface Button {\n\tlabel: Label\n\tform: Broken\n}\n\nexport interface Label {\n\ttext: string\n}\n\nexport interface Broken {\n\tsubmit(): void\n}\n",
+		)
+		.await;
+		let dot = collection.get_graph(GraphFormat::Dot, JsonStyle::Pretty).unwrap();
+
+		assert!(dot.starts_with("digraph runtime {\n"));
+		assert!(dot.contains("\t\"Button\" [shape=box];\n"));
+		assert!(dot.contains("\t\"Label\";\n"));
+		assert!(dot.contains("\t\"Broken\" [color=red, fontcolor=red];\n"));
+		assert!(dot.contains("\t\"Button\" -> \"Broken\";\n"));
+		assert!(dot.contains("\t\"Button\" -> \"Label\";\n"));
+		assert!(!dot.contains("\"Label\" ->"));
+	}
+
+	#[tokio::test]
+	async fn key_types_that_no_component_uses_are_warned_about() {
+		let key_types = "/**\n * @feature_event_key\n */\nexport type EventKey<T> = { eventPath: string[] }\n\n/**\n * @feature_action_key\n */\nexport type ActionKey<T> = { actionPath: string[] }\n\n";
+		let unused = collect_source(&format!(
+			"{key_types}/**\n * @component\n */\nexport interface Button {{\n\tlabel: string\n}}\n"
+		))
+		.await;
+		let used = collect_source(&format!(
+			"{key_types}/**\n * @component\n */\nexport interface Button {{\n\tonClick: EventKey<null>\n}}\n"
+		))
+		.await;
+		let warning = unused.get_unused_key_types_warning().unwrap().to_string();
+
+		assert!(warning.contains("Event and action key types were declared, but no component has an event or action property"));
+		assert!(warning.contains("`EventKey`, `ActionKey`"));
+		assert!(used.get_unused_key_types_warning().is_none());
+		assert!(collect_source("export interface Button {\n\tlabel: string\n}\n")
+			.await
+			.get_unused_key_types_warning()
+			.is_none());
+	}
+
+	#[tokio::test]
+	async fn components_whose_only_field_failed_to_convert_are_warned_about() {
+		let collection = collect_source(
+			"/**\n * @component\n */\nexport interface Button {\n\tclick(): void\n}\n\n/**\n * @component\n */\nexport interface Card {\n\ttitle: string\n}\n",
+		)
+		.await;
+		let get_warning = |name: &str| {
+			let component = collection.get_component_info().into_iter().find(|(component, _)| *component == name).unwrap().1;
+
+			collection.get_empty_component_warning(name, component).map(|warning| warning.to_string())
+		};
+
+		let warning = get_warning("Button").unwrap();
+
+		assert!(warning.contains("Component `Button` has no props, events, or actions"));
+		assert!(warning.contains("file:///runtime.ts:4:"));
+		assert!(warning.contains("Method `click` is not supported"));
+		assert_eq!(get_warning("Card"), None);
+	}
+
+	#[tokio::test]
+	async fn deprecated_types_are_warned_about_where_they_are_used() {
+		let collection = collect_source(
+			"/**\n * @deprecated Use `Tone` instead\n */\nexport type Color = 'red' | 'blue'\n\n/**\n * @component\n */\nexport interface Button {\n\tcolor: Color\n}\n\n/**\n * @component\n */\nexport interface Card {\n\ttitle: string\n}\n",
+		)
+		.await;
+		let warnings = collection
+			.get_deprecation_warnings()
+			.iter()
+			.map(|warning| warning.to_string())
+			.collect::<Vec<_>>();
+
+		assert_eq!(warnings.len(), 1);
+		assert!(warnings[0].contains("`Color` is deprecated: Use `Tone` instead"));
+		assert!(warnings[0].contains("Referenced by `Button`"));
+		assert!(warnings[0].contains("file:///runtime.ts:9:"));
+	}
+
+	#[tokio::test]
+	async fn components_named_like_reserved_elements_are_warned_about_unless_the_check_is_disabled() {
+		let mut collection = collect_source(
+			"/**\n * @component\n */\nexport interface Slot {\n\tname: string\n}\n\n/**\n * @component\n */\nexport interface Card {\n\ttitle: string\n}\n\n/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n}\n",
+		)
+		.await;
+
+		assert!(collection.get_reserved_name_warnings().is_empty());
+
+		collection.check_reserved_names(&["CARD".to_string()]);
+		let warnings = collection
+			.get_reserved_name_warnings()
+			.iter()
+			.map(|warning| warning.to_string())
+			.collect::<Vec<_>>();
+
+		assert_eq!(warnings.len(), 2);
+		assert!(warnings[0].contains("Component `Card` shares the name `Card`"));
+		assert!(warnings[1].contains("Component `Slot` shares the name `Slot`"));
+		assert!(warnings[1].contains("file:///runtime.ts:4:"));
+	}
+
+	#[tokio::test]
+	async fn required_functions_are_the_starter_and_every_render_function() {
+		let collection = collect_source(
+			"/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n}\n\n/**\n * @component CardView\n */\nexport interface Card {\n\ttitle: string\n}\n\n/**\n * @component CardView\n */\nexport interface Panel {\n\ttitle: string\n}\n",
+		)
+		.await;
+
+		assert_eq!(collection.required_functions(), ["ButtonRender", "CardView", STARTER_FUNCTION_NAME]);
+	}
+
+	#[tokio::test]
+	async fn render_functions_that_take_another_type_are_warned_about() {
+		let collection = collect_source(
+			"/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n}\n\n/**\n * @component\n */\nexport interface Card {\n\ttitle: string\n}\n\n/**\n * @component\n */\nexport interface Badge {\n\tcount: number\n}\n\nexport function ButtonRender(props: Card): void {}\nexport function CardRender(props: Card = { title: '' }): void {}\nexport function BadgeRender(props): void {}\n",
+		)
+		.await;
+		let get_warning = |name: &str| {
+			let component = collection.get_component_info().into_iter().find(|(component, _)| *component == name).unwrap().1;
+
+			collection.get_render_param_warning(name, component).map(|warning| warning.to_string())
+		};
+
+		let warning = get_warning("Button").unwrap();
+
+		assert!(warning.contains("The first parameter of `ButtonRender` is typed as `Card`, but it renders `Button`"));
+		assert!(warning.contains("file:///runtime.ts:4:"));
+		assert_eq!(get_warning("Card"), None);
+		assert_eq!(get_warning("Badge"), None);
+	}
+
+	#[tokio::test]
+	async fn ignored_exports_are_counted_by_kind() {
+		let collection = collect_source(
+			"export class First {}\nexport class Second {}\nexport const limit = 3\nexport namespace Tools {\n\texport interface Tool {\n\t\tid: number\n\t}\n}\nexport interface Kept {\n\tid: number\n}\n",
+		)
+		.await;
+
+		assert_eq!(
+			collection.get_ignored_summary().as_deref(),
+			Some("Ignored 2 classes, 1 namespace, 1 variable (namespaces can be collected with --namespaces)")
+		);
+		assert_eq!(collect_source("export interface Kept {\n\tid: number\n}\n").await.get_ignored_summary(), None);
+	}
+
+	#[tokio::test]
+	async fn the_protocol_describes_every_root_key() {
+		let collection = collect_source("/**\n * @feature_component_index\n */\nexport interface Component {\n\tid: number\n}\n").await;
+		let protocol = collection.get_protocol();
+
+		for (names, keys) in [(ROOT_EVENTS, &protocol["events"]), (ROOT_ACTIONS, &protocol["actions"])] {
+			for name in names {
+				let key = keys.as_array().unwrap().iter().find(|key| key["name"] == *name);
+				let key = key.unwrap_or_else(|| panic!("{name} is missing from the protocol"));
+
+				assert_eq!(key["path"], json!([name]));
+				assert_ne!(key["dataType"], "unknown", "{name} has no data type");
+			}
+		}
+
+		let mount = protocol["actions"].as_array().unwrap().iter().find(|key| key["name"] == MOUNT_ACTION).unwrap();
+
+		assert_eq!(mount["dataType"], "Component");
+	}
+}
@@ -1,8 +1,30 @@
 use anyhow::{Context, Result};
-use deno_doc::{interface::InterfaceDef, ts_type::TsTypeDef, Location};
+use deno_doc::{
+	interface::InterfaceDef,
+	js_doc::JsDocTag,
+	r#enum::EnumDef,
+	ts_type::{LiteralDef, TsTypeDef},
+	Location,
+};
 use log::debug;
 
-use crate::{collect::ComponentInfo, diagnostic::Diagnostic};
+use crate::{
+	collect::{ComponentInfo, KeyInfo},
+	diagnostic::Diagnostic,
+};
+
+/// The name of the property that holds the entries matched by an interface's index signature
+const INDEX_SIGNATURE_PROPERTY_NAME: &str = "extra";
+
+/// The default nesting depth at which type conversion gives up. Far deeper than any type that is written by hand, but shallow enough
+/// to fail long before the stack overflows.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Keywords for the absence of a value. See `strip_nullish_variants` for how they are converted
+const NULLISH_KEYWORDS: &[&str] = &["undefined", "void", "null"];
+
+const FUNCTION_LIMITATION: &str = "Types shared with the engine must be serializable, and functions cannot be sent between the engine and \
+	the runtime. To notify the engine of something, use an event key instead";
 
 #[derive(Debug)]
 pub enum Kind {
@@ -11,14 +33,38 @@ pub enum Kind {
 	Number,
 	Bool,
 	Null,
-	ActionKey { data_type: Box<Kind> },
-	EventKey { data_type: Box<Kind> },
-	Ref { name: String },
-	List { of: Box<Kind> },
-	Tuple { items: Vec<Kind> },
-	StringEnum { variants: Vec<String> },
-	KeyedEnum { variants: Vec<EnumProperty> },
-	Object { properties: Vec<ObjectProperty> },
+	ActionKey {
+		data_type: Box<Kind>,
+	},
+	EventKey {
+		data_type: Box<Kind>,
+	},
+	Ref {
+		name: String,
+	},
+	List {
+		of: Box<Kind>,
+	},
+	Map {
+		key: Box<Kind>,
+		value: Box<Kind>,
+	},
+	Tuple {
+		items: Vec<Kind>,
+	},
+	StringEnum {
+		variants: Vec<String>,
+	},
+	/// A typescript enum, whose members are sent as their values, rather than as their names
+	ValueEnum {
+		variants: Vec<ValueEnumVariant>,
+	},
+	KeyedEnum {
+		variants: Vec<EnumProperty>,
+	},
+	Object {
+		properties: Vec<ObjectProperty>,
+	},
 }
 
 #[derive(Debug)]
@@ -28,12 +74,39 @@ pub struct EnumProperty {
 	pub kind: Kind,
 }
 
+#[derive(Debug)]
+pub struct ValueEnumVariant {
+	pub comment: Option<String>,
+	pub name: String,
+	pub value: EnumValue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnumValue {
+	String(String),
+	Number(f64),
+}
+
 #[derive(Debug)]
 pub struct ObjectProperty {
 	pub comment: Option<String>,
 	pub name: String,
 	pub kind: Kind,
 	pub is_optional: bool,
+	/// The property's fields are flattened into the parent object. Used for the catch-all map of an index signature.
+	pub is_flattened: bool,
+	/// Serde attributes that were requested with `@serde` jsdoc tags, which are added to the generated field
+	pub serde_attributes: Vec<SerdeAttribute>,
+}
+
+/// A serde field attribute that can be requested with a `@serde` jsdoc tag, such as `@serde flatten` or `@serde with=my_module`
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerdeAttribute {
+	Flatten,
+	Skip,
+	Default,
+	/// The path of a module that serializes and deserializes the field
+	With(String),
 }
 
 #[derive(Debug)]
@@ -48,6 +121,8 @@ pub struct ConvertInterfaceParams<'a> {
 	pub component: Option<&'a mut ComponentInfo>,
 	pub action_key_type_name: Option<&'a str>,
 	pub event_key_type_name: Option<&'a str>,
+	/// See `ConvertTsTypeParams::max_depth`
+	pub max_depth: usize,
 }
 
 pub fn convert_interface(params: ConvertInterfaceParams<'_>) -> Result<Conversion> {
@@ -57,6 +132,7 @@ pub fn convert_interface(params: ConvertInterfaceParams<'_>) -> Result<Conversio
 		mut component,
 		action_key_type_name,
 		event_key_type_name,
+		max_depth,
 	} = params;
 
 	let mut interface_dependencies = Vec::new();
@@ -70,22 +146,42 @@ pub fn convert_interface(params: ConvertInterfaceParams<'_>) -> Result<Conversio
 			.err();
 	}
 
-	if !interface.methods.is_empty() {
-		return Diagnostic::start(
-			"Methods are not supported in exported interfaces. If this is releated to private client-only functionality, consider \
-			inlining the methods in the render function",
-		)
-		.shift()
-		.location(location)
-		.build()
-		.err();
+	let signature_location = interface
+		.call_signatures
+		.first()
+		.map(|signature| &signature.location)
+		.or_else(|| interface.constructors.first().map(|constructor| &constructor.location));
+
+	if let Some(signature_location) = signature_location {
+		return Diagnostic::start("Interfaces with call or construct signatures are not supported")
+			.shift()
+			.text(FUNCTION_LIMITATION)
+			.shift()
+			.location(signature_location)
+			.build()
+			.err();
+	}
+
+	if let Some(method) = interface.methods.first() {
+		return Diagnostic::start("Method ")
+			.inline_code(&method.name)
+			.text(
+				" is not supported. Methods are not supported in exported interfaces. If this is releated to private client-only \
+				functionality, consider inlining the methods in the render function",
+			)
+			.shift()
+			.location(&method.location)
+			.build()
+			.err();
 	}
 
 	for property_def in &interface.properties {
 		let type_def = match &property_def.ts_type {
 			Some(def) => def,
 			None => {
-				return Diagnostic::start("Interface property does not have an associated type")
+				return Diagnostic::start("Interface property ")
+					.inline_code(&property_def.name)
+					.text(" does not have an associated type")
 					.shift()
 					.location(&property_def.location)
 					.build()
@@ -93,12 +189,21 @@ pub fn convert_interface(params: ConvertInterfaceParams<'_>) -> Result<Conversio
 			}
 		};
 
+		// `foo: T | undefined` means the same thing as `foo?: T`
+		let stripped_type_def = strip_nullish_variants(type_def);
+		let (type_def, is_nullable) = match &stripped_type_def {
+			Some(stripped_type_def) => (stripped_type_def, true),
+			None => (type_def, false),
+		};
+
 		let mut conversion = convert_ts_type(ConvertTsTypeParams {
 			ts_type: type_def,
 			location: &property_def.location,
 			component: component.as_deref_mut(),
 			action_key_type_name,
 			event_key_type_name,
+			depth: 1,
+			max_depth,
 		})
 		.with_context(|| {
 			Diagnostic::start("Failed to convert interface property ")
@@ -108,13 +213,139 @@ pub fn convert_interface(params: ConvertInterfaceParams<'_>) -> Result<Conversio
 				.build()
 		})?;
 
+		if let Some(component) = component.as_deref_mut() {
+			let keys = match &conversion.kind {
+				Kind::EventKey { .. } => Some(&mut component.events),
+				Kind::ActionKey { .. } => Some(&mut component.actions),
+				_ => None,
+			};
+
+			if let Some(keys) = keys {
+				let data_type = type_def
+					.type_ref
+					.as_ref()
+					.and_then(|type_ref| type_ref.type_params.as_ref())
+					.and_then(|type_params| type_params.first())
+					.map(|type_param| type_param.repr.clone())
+					.unwrap_or_default();
+
+				keys.insert(
+					property_def.name.to_string(),
+					KeyInfo {
+						data_type,
+						references: conversion.dependencies.clone(),
+					},
+				);
+			}
+		}
+
 		interface_dependencies.append(&mut conversion.dependencies);
 
 		properties.push(ObjectProperty {
 			comment: property_def.js_doc.doc.clone(),
 			name: property_def.name.to_string(),
 			kind: conversion.kind,
-			is_optional: property_def.optional,
+			is_optional: property_def.optional || is_nullable,
+			is_flattened: false,
+			serde_attributes: get_serde_attributes(&property_def.js_doc.tags, &property_def.location),
+		})
+	}
+
+	if let Some(index_signature) = interface.index_signatures.get(1) {
+		return Diagnostic::start("Interfaces with more than one index signature are not supported")
+			.shift()
+			.location(&index_signature.location)
+			.build()
+			.err();
+	}
+
+	if let Some(index_signature) = interface.index_signatures.first() {
+		let is_string_keyed = index_signature.params.len() == 1
+			&& index_signature.params[0]
+				.ts_type
+				.as_ref()
+				.and_then(|ts_type| ts_type.keyword.as_deref())
+				.map(|keyword| keyword == "string")
+				.unwrap_or_default();
+
+		if !is_string_keyed {
+			return Diagnostic::start("Only index signatures with a single ")
+				.inline_code("string")
+				.text(" key are supported")
+				.shift()
+				.location(&index_signature.location)
+				.build()
+				.err();
+		}
+
+		let value_type = match &index_signature.ts_type {
+			Some(def) => def,
+			None => {
+				return Diagnostic::start("Index signature does not have an associated type")
+					.shift()
+					.location(&index_signature.location)
+					.build()
+					.err();
+			}
+		};
+
+		if interface
+			.properties
+			.iter()
+			.any(|property_def| property_def.name == INDEX_SIGNATURE_PROPERTY_NAME)
+		{
+			return Diagnostic::start("Interface property ")
+				.inline_code(INDEX_SIGNATURE_PROPERTY_NAME)
+				.text(" conflicts with the property generated for the index signature. Rename it, or remove the index signature")
+				.shift()
+				.location(&index_signature.location)
+				.build()
+				.err();
+		}
+
+		let mut conversion = convert_ts_type(ConvertTsTypeParams {
+			ts_type: value_type,
+			location: &index_signature.location,
+			component: component.as_deref_mut(),
+			action_key_type_name,
+			event_key_type_name,
+			depth: 1,
+			max_depth,
+		})
+		.context("Failed to convert index signature")?;
+
+		for property in get_index_signature_conflicts(&properties, &conversion.kind) {
+			let property_def = match interface.properties.iter().find(|property_def| property_def.name == property.name) {
+				Some(property_def) => property_def,
+				None => continue,
+			};
+
+			Diagnostic::start("Interface property ")
+				.inline_code(&property_def.name)
+				.text(" has type ")
+				.inline_code(property_def.ts_type.as_ref().map(|ts_type| ts_type.repr.as_str()).unwrap_or_default())
+				.text(", but the index signature expects ")
+				.inline_code(&value_type.repr)
+				.shift()
+				.text("Named properties are generated as their own fields, but the types may not agree with TypeScript's view of the interface")
+				.shift()
+				.location(&property_def.location)
+				.build()
+				.print_warn();
+		}
+
+		interface_dependencies.append(&mut conversion.dependencies);
+
+		properties.push(ObjectProperty {
+			comment: index_signature.js_doc.doc.clone(),
+			name: INDEX_SIGNATURE_PROPERTY_NAME.to_string(),
+			kind: Kind::Map {
+				key: Box::new(Kind::String),
+				value: Box::new(conversion.kind),
+			},
+			is_optional: false,
+			is_flattened: true,
+			serde_attributes: Vec::new(),
 		})
 	}
 
@@ -124,12 +355,184 @@ pub fn convert_interface(params: ConvertInterfaceParams<'_>) -> Result<Conversio
 	})
 }
 
+/// The named properties whose types are not assignable to the value type of an interface's index signature, which typescript would
+/// reject
+fn get_index_signature_conflicts<'a>(properties: &'a [ObjectProperty], value_kind: &Kind) -> Vec<&'a ObjectProperty> {
+	properties
+		.iter()
+		.filter(|property| !property.is_flattened && !is_assignable_to(&property.kind, value_kind))
+		.collect()
+}
+
+/// Whether a value of kind `from` can be used where a value of kind `to` is expected. Named types are only compared by name.
+fn is_assignable_to(from: &Kind, to: &Kind) -> bool {
+	match (from, to) {
+		(_, Kind::Dynamic) => true,
+		(Kind::String, Kind::String) | (Kind::Number, Kind::Number) | (Kind::Bool, Kind::Bool) | (Kind::Null, Kind::Null) => true,
+		(Kind::StringEnum { .. }, Kind::String) => true,
+		(Kind::StringEnum { variants: from }, Kind::StringEnum { variants: to }) => from.iter().all(|variant| to.contains(variant)),
+		(Kind::Ref { name: from }, Kind::Ref { name: to }) => from == to,
+		(Kind::List { of: from }, Kind::List { of: to }) => is_assignable_to(from, to),
+		(Kind::Map { value: from, .. }, Kind::Map { value: to, .. }) => is_assignable_to(from, to),
+		(Kind::Tuple { items }, Kind::List { of }) => items.iter().all(|item| is_assignable_to(item, of)),
+		(Kind::Tuple { items: from }, Kind::Tuple { items: to }) => from.len() == to.len() && from.iter().zip(to).all(|(from, to)| is_assignable_to(from, to)),
+		(Kind::EventKey { .. }, Kind::EventKey { .. }) | (Kind::ActionKey { .. }, Kind::ActionKey { .. }) => true,
+		_ => false,
+	}
+}
+
+/// Remove the `undefined`, `void`, and `null` variants of a union, returning `None` if there are none to remove or if nothing else is
+/// in the union. Absent values are converted according to these rules:
+///
+/// - On an interface property, `T | undefined`, `T | null`, and `T | undefined | null` all make the property optional, so they become
+///   `Option<T>`, the same as `foo?: T`.
+/// - As the data type of an event or action key, a bare `void` or `undefined` becomes `()`.
+/// - Anywhere else, a bare `void` or `undefined`, or a union that includes an absent value, is an error.
+fn strip_nullish_variants(ts_type: &TsTypeDef) -> Option<TsTypeDef> {
+	let union = ts_type.union.as_ref()?;
+	let (nullish, mut rest) = union.iter().cloned().partition::<Vec<_>, _>(is_nullish);
+
+	if nullish.is_empty() || rest.is_empty() {
+		return None;
+	}
+
+	if rest.len() == 1 {
+		return rest.pop();
+	}
+
+	Some(TsTypeDef {
+		union: Some(rest),
+		..ts_type.clone()
+	})
+}
+
+fn is_nullish(ts_type: &TsTypeDef) -> bool {
+	ts_type
+		.keyword
+		.as_deref()
+		.map(|keyword| NULLISH_KEYWORDS.contains(&keyword))
+		.unwrap_or_default()
+}
+
+fn is_void(ts_type: &TsTypeDef) -> bool {
+	matches!(ts_type.keyword.as_deref(), Some("void" | "undefined"))
+}
+
+/// Read the directives of every `@serde` tag. Unknown directives are warned about and skipped.
+fn get_serde_attributes(tags: &[JsDocTag], location: &Location) -> Vec<SerdeAttribute> {
+	let mut attributes = Vec::new();
+
+	for tag in tags {
+		let value = match tag {
+			JsDocTag::Unsupported { value } => value,
+			_ => continue,
+		};
+
+		let mut words = value.split_whitespace();
+
+		if words.next() != Some("@serde") {
+			continue;
+		}
+
+		for directive in words {
+			let attribute = match directive.split_once('=') {
+				None if directive == "flatten" => SerdeAttribute::Flatten,
+				None if directive == "skip" => SerdeAttribute::Skip,
+				None if directive == "default" => SerdeAttribute::Default,
+				Some(("with", path)) if !path.is_empty() => SerdeAttribute::With(path.to_string()),
+				_ => {
+					Diagnostic::start("Unknown serde directive ")
+						.inline_code(directive)
+						.text(" will be ignored. Expected one of ")
+						.inline_code("flatten")
+						.text(", ")
+						.inline_code("skip")
+						.text(", ")
+						.inline_code("default")
+						.text(", or ")
+						.inline_code("with=path::to::module")
+						.shift()
+						.location(location)
+						.build()
+						.print_warn();
+
+					continue;
+				}
+			};
+
+			attributes.push(attribute);
+		}
+	}
+
+	attributes
+}
+
 pub struct ConvertTsTypeParams<'a> {
 	pub ts_type: &'a TsTypeDef,
 	pub location: &'a Location,
 	pub component: Option<&'a mut ComponentInfo>,
 	pub action_key_type_name: Option<&'a str>,
 	pub event_key_type_name: Option<&'a str>,
+	/// How deeply `ts_type` is nested inside of the type that is being converted. Zero for the type itself
+	pub depth: usize,
+	/// The nesting depth at which conversion fails, rather than continuing to recurse. See `DEFAULT_MAX_DEPTH`
+	pub max_depth: usize,
+}
+
+/// Convert a typescript enum. Members without an initializer follow the previous numeric member, as they do in typescript. Enums
+/// that mix string and numeric members are not supported.
+pub fn convert_enum(enum_def: &EnumDef, location: &Location) -> Result<Conversion> {
+	let mut variants = Vec::<ValueEnumVariant>::new();
+
+	for member in &enum_def.members {
+		let value = match member.init.as_ref().map(|init| init.literal.as_ref()) {
+			Some(Some(LiteralDef { string: Some(string), .. })) => EnumValue::String(string.clone()),
+			Some(Some(LiteralDef { number: Some(number), .. })) => EnumValue::Number(*number),
+			Some(_) => {
+				return Diagnostic::start("Failed to convert enum member ")
+					.inline_code(&member.name)
+					.text(". Only string and number literals are supported as initializers.")
+					.shift()
+					.location(&member.location)
+					.build()
+					.err()
+			}
+			None => match variants.last().map(|variant| &variant.value) {
+				None => EnumValue::Number(0.0),
+				Some(EnumValue::Number(previous)) => EnumValue::Number(previous + 1.0),
+				Some(EnumValue::String(_)) => {
+					return Diagnostic::start("Enum member ")
+						.inline_code(&member.name)
+						.text(" must have an initializer, because it follows a string member")
+						.shift()
+						.location(&member.location)
+						.build()
+						.err()
+				}
+			},
+		};
+
+		variants.push(ValueEnumVariant {
+			comment: member.js_doc.doc.clone(),
+			name: member.name.clone(),
+			value,
+		});
+	}
+
+	let string_count = variants.iter().filter(|variant| matches!(variant.value, EnumValue::String(_))).count();
+
+	if string_count != 0 && string_count != variants.len() {
+		return Diagnostic::start("Found an enum with both string and numeric members. This is not allowed.")
+			.shift()
+			.location(location)
+			.build()
+			.err();
+	}
+
+	Ok(Conversion {
+		kind: Kind::ValueEnum { variants },
+		dependencies: Vec::new(),
+	})
 }
 
 pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
@@ -139,8 +542,20 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 		mut component,
 		action_key_type_name,
 		event_key_type_name,
+		depth,
+		max_depth,
 	} = params;
 
+	if depth > max_depth {
+		return Diagnostic::start(format!("Type nesting exceeded {max_depth} levels"))
+			.shift()
+			.text("Simplify the type, or raise the limit with --max-depth")
+			.shift()
+			.location(location)
+			.build()
+			.err();
+	}
+
 	if let Some(keyword) = &ts_type.keyword {
 		if keyword == "string" {
 			return Ok(Conversion {
@@ -177,6 +592,22 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 			});
 		}
 
+		if keyword == "void" || keyword == "undefined" {
+			return Diagnostic::start("A bare ")
+				.inline_code(keyword)
+				.text(" type is only supported as the data type of an event or action key")
+				.shift()
+				.text("To make an interface property optional, mark it with ")
+				.inline_code("?")
+				.text(" or include ")
+				.inline_code(keyword)
+				.text(" in a union with its type")
+				.shift()
+				.location(location)
+				.build()
+				.err();
+		}
+
 		if keyword == "any" {
 			return Diagnostic::start("Use 'unknown' instead of 'any'").shift().location(location).build().err();
 		}
@@ -187,14 +618,28 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 	if let Some(type_ref) = &params.ts_type.type_ref {
 		let mut type_params = Vec::with_capacity(type_ref.type_params.as_ref().map(|params| params.len()).unwrap_or_default());
 
+		let is_key = [action_key_type_name, event_key_type_name].contains(&Some(type_ref.type_name.as_str()));
+
 		if let Some(ts_type_params) = &type_ref.type_params {
 			for ts_type in ts_type_params {
+				// a key that carries no data
+				if is_key && is_void(ts_type) {
+					type_params.push(Conversion {
+						kind: Kind::Null,
+						dependencies: Vec::new(),
+					});
+
+					continue;
+				}
+
 				type_params.push(convert_ts_type(ConvertTsTypeParams {
 					ts_type,
 					location,
 					component: component.as_deref_mut(),
 					action_key_type_name,
 					event_key_type_name,
+					depth: depth + 1,
+					max_depth,
 				})?);
 			}
 		}
@@ -247,6 +692,36 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 			}
 		}
 
+		// the keys of a record are always sent as strings, so only string keys can be read back
+		if type_ref.type_name == "Record" && type_params.len() == 2 {
+			let value = type_params.swap_remove(1);
+			let key = type_params.swap_remove(0);
+
+			// a reference is expected to be to a union of string literals
+			if !matches!(key.kind, Kind::String | Kind::StringEnum { .. } | Kind::Ref { .. }) {
+				return Diagnostic::start("The key type of ")
+					.inline_code("Record")
+					.text(" must be ")
+					.inline_code("string")
+					.text(" or a union of string literals")
+					.shift()
+					.location(location)
+					.build()
+					.err();
+			}
+
+			let mut dependencies = key.dependencies;
+			dependencies.extend(value.dependencies);
+
+			return Ok(Conversion {
+				kind: Kind::Map {
+					key: Box::new(key.kind),
+					value: Box::new(value.kind),
+				},
+				dependencies,
+			});
+		}
+
 		if type_ref.type_params.is_some() {
 			return Diagnostic::start("Type ")
 				.inline_code(&type_ref.type_name)
@@ -272,6 +747,8 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 			component,
 			action_key_type_name,
 			event_key_type_name,
+			depth: depth + 1,
+			max_depth,
 		})?;
 
 		return Ok(Conversion {
@@ -287,12 +764,26 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 		let mut items = Vec::new();
 
 		for ts_item in tuple {
+			if let Some(rest) = &ts_item.rest {
+				return Diagnostic::start("Found the rest element ")
+					.inline_code(format!("...{}", rest.repr))
+					.text(" in a tuple, but only fixed-length tuples are supported")
+					.shift()
+					.text("Use an array for a variable number of items")
+					.shift()
+					.location(location)
+					.build()
+					.err();
+			}
+
 			let mut inner_conversion = convert_ts_type(ConvertTsTypeParams {
 				ts_type: ts_item,
 				location,
 				component: component.as_deref_mut(),
 				action_key_type_name,
 				event_key_type_name,
+				depth: depth + 1,
+				max_depth,
 			})
 			.context("Failed to convert tuple")?;
 
@@ -307,6 +798,16 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 	}
 
 	if let Some(union) = &ts_type.union {
+		if let Some(nullish) = union.iter().find(|ts_type| is_nullish(ts_type)) {
+			return Diagnostic::start("Unions that include ")
+				.inline_code(&nullish.repr)
+				.text(" are only supported as the type of an interface property, where they make the property optional")
+				.shift()
+				.location(location)
+				.build()
+				.err();
+		}
+
 		let mut combined_dependencies = Vec::new();
 		let mut string_variants = Vec::new();
 		let mut keyed_variants = Vec::new();
@@ -380,6 +881,8 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 							component: component.as_deref_mut(),
 							action_key_type_name,
 							event_key_type_name,
+							depth: depth + 1,
+							max_depth,
 						})
 						.with_context(|| {
 							Diagnostic::start("Failed to convert property ")
@@ -456,7 +959,17 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 		});
 	}
 
-	if let Some(_) = &ts_type.type_literal {
+	if ts_type.fn_or_constructor.is_some() {
+		return Diagnostic::start("Function types are not supported")
+			.shift()
+			.text(FUNCTION_LIMITATION)
+			.shift()
+			.location(location)
+			.build()
+			.err();
+	}
+
+	if ts_type.type_literal.is_some() {
 		return Diagnostic::start("Object literals are not supported for types. Use an interface instead.")
 			.shift()
 			.location(location)
@@ -467,3 +980,179 @@ pub fn convert_ts_type(params: ConvertTsTypeParams<'_>) -> Result<Conversion> {
 	debug!("Encountered an unknown type: {:#?}", ts_type);
 	Diagnostic::start("Unsupported type").shift().location(location).build().err()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		collect::{collect_source, collect_virtual_modules_into, Collection},
+		module_loader::VirtualModule,
+	};
+	use url::Url;
+
+	#[tokio::test]
+	async fn index_signatures_are_flattened_next_to_named_properties() {
+		let collection = collect_source("export interface Scores {\n\t[name: string]: number\n\ttotal: number\n\tlabel?: number\n}\n").await;
+		let properties = match collection.get_kind("Scores") {
+			Some(Kind::Object { properties }) => properties,
+			kind => panic!("expected an object, but found {kind:?}"),
+		};
+		let fields = properties
+			.iter()
+			.map(|property| (property.name.as_str(), property.is_optional, property.is_flattened))
+			.collect::<Vec<_>>();
+
+		assert_eq!(fields, [("total", false, false), ("label", true, false), ("extra", false, true)]);
+		assert!(matches!(
+			&properties[2].kind,
+			Kind::Map { key, value } if matches!(**key, Kind::String) && matches!(**value, Kind::Number)
+		));
+	}
+
+	/// The full error chain that the collection has for `name`
+	fn get_error_chain(collection: &Collection, name: &str) -> String {
+		let errors = collection.get_categorized_errors();
+		let error = errors.iter().find(|error| error.name == Some(name)).expect("expected an error");
+
+		format!("{:?}", error.error)
+	}
+
+	#[tokio::test]
+	async fn functions_are_reported_with_the_serialization_limitation() {
+		let collection =
+			collect_source("export interface Callback {\n\t(value: number): void\n}\nexport interface Props {\n\tonChange: (value: number) => void\n}\n").await;
+		let callback_error = get_error_chain(&collection, "Callback");
+		let props_error = get_error_chain(&collection, "Props");
+
+		assert!(callback_error.contains("Interfaces with call or construct signatures are not supported"));
+		assert!(callback_error.contains(FUNCTION_LIMITATION));
+		assert!(props_error.contains("Function types are not supported"));
+		assert!(props_error.contains(FUNCTION_LIMITATION));
+	}
+
+	#[tokio::test]
+	async fn member_errors_point_at_the_offending_member() {
+		let collection = collect_source(
+			"export interface Props {\n\tlabel: string\n\tonChange: (value: number) => void\n}\nexport interface Form {\n\tid: number\n\tsubmit(): void\n}\n",
+		)
+		.await;
+		let props_error = get_error_chain(&collection, "Props");
+		let form_error = get_error_chain(&collection, "Form");
+
+		assert!(props_error.contains("Failed to convert interface property `onChange`"));
+		assert!(props_error.contains("file:///runtime.ts:3:"));
+		assert!(!props_error.contains("`label`"));
+		assert!(form_error.contains("Method `submit` is not supported"));
+		assert!(form_error.contains("file:///runtime.ts:7:"));
+	}
+
+	#[tokio::test]
+	async fn types_nested_deeper_than_the_max_depth_fail_to_convert() {
+		let source = "export interface Shallow {\n\ttags: string[][]\n}\nexport interface Deep {\n\ttags: string[][][]\n}\n";
+		let mut collection = Collection::default();
+		collection.set_max_depth(3);
+
+		let collection = collect_virtual_modules_into(
+			collection,
+			&[VirtualModule {
+				specifier: Url::parse("file:///runtime.ts").unwrap(),
+				source: source.to_string(),
+				dependencies: Vec::new(),
+			}],
+		)
+		.await;
+		let errors = collection.get_categorized_errors();
+		let deep_error = get_error_chain(&collection, "Deep");
+
+		assert!(deep_error.contains("Type nesting exceeded 3 levels"));
+		assert!(deep_error.contains("file:///runtime.ts:5:"));
+		assert!(errors.iter().all(|error| error.name != Some("Shallow")));
+		assert!(collect_source(source)
+			.await
+			.get_categorized_errors()
+			.iter()
+			.all(|error| error.name != Some("Deep")));
+	}
+
+	#[tokio::test]
+	async fn records_depend_on_their_values_and_reject_non_string_keys() {
+		let mut collection = collect_source(
+			"export interface Props {\n\tnested: Record<string, Record<string, Missing>>\n}\nexport interface Scores {\n\tbyId: Record<number, string>\n}\n",
+		)
+		.await;
+		collection.meet_all_dependencies();
+
+		assert!(get_error_chain(&collection, "Missing").contains("Props"));
+		assert!(get_error_chain(&collection, "Scores").contains("must be"));
+		assert!(matches!(
+			collection.get_kind("Props"),
+			Some(Kind::Object { properties }) if matches!(
+				&properties[0].kind,
+				Kind::Map { value, .. } if matches!(&**value, Kind::Map { value, .. } if matches!(&**value, Kind::Ref { name } if name == "Missing"))
+			)
+		));
+	}
+
+	#[tokio::test]
+	async fn tuples_convert_in_order_and_reject_rest_elements() {
+		let collection =
+			collect_source("export interface Marker {\n\tentry: [string, number, Foo]\n}\nexport interface Path {\n\tpoints: [string, ...number[]]\n}\n").await;
+		let properties = match collection.get_kind("Marker") {
+			Some(Kind::Object { properties }) => properties,
+			kind => panic!("expected an object, but found {kind:?}"),
+		};
+
+		assert!(matches!(
+			&properties[0].kind,
+			Kind::Tuple { items } if matches!(items.as_slice(), [Kind::String, Kind::Number, Kind::Ref { name }] if name == "Foo")
+		));
+		assert!(get_error_chain(&collection, "Path").contains("only fixed-length tuples are supported"));
+	}
+
+	#[tokio::test]
+	async fn absent_values_make_a_property_optional_only_once() {
+		let collection = collect_source(
+			"export interface Props {\n\ttitle?: string | null\n\tcount: number | undefined\n\tsize?: 'small' | 'large' | null\n\tlabel: string\n}\n",
+		)
+		.await;
+		let properties = match collection.get_kind("Props") {
+			Some(Kind::Object { properties }) => properties,
+			kind => panic!("expected an object, but found {kind:?}"),
+		};
+		let optional = properties
+			.iter()
+			.map(|property| (property.name.as_str(), property.is_optional))
+			.collect::<Vec<_>>();
+
+		assert_eq!(optional, [("title", true), ("count", true), ("size", true), ("label", false)]);
+		assert!(matches!(properties[0].kind, Kind::String));
+		assert!(matches!(properties[1].kind, Kind::Number));
+		assert!(matches!(&properties[2].kind, Kind::StringEnum { variants } if variants == &["small", "large"]));
+	}
+
+	#[tokio::test]
+	async fn named_properties_that_conflict_with_the_index_signature_are_warned_about() {
+		let collection = collect_source(
+			"export interface Props {\n\t[k: string]: number\n\tname: string\n\tcount: number\n\tsize?: number\n}\nexport interface Loose {\n\t[k: string]: \
+			 unknown\n\tname: string\n}\n",
+		)
+		.await;
+		let get_conflicts = |name: &str| match collection.get_kind(name) {
+			Some(Kind::Object { properties }) => {
+				let value_kind = match &properties.last().unwrap().kind {
+					Kind::Map { value, .. } => value,
+					kind => panic!("expected a map, but found {kind:?}"),
+				};
+
+				get_index_signature_conflicts(properties, value_kind)
+					.iter()
+					.map(|property| property.name.clone())
+					.collect::<Vec<_>>()
+			}
+			kind => panic!("expected an object, but found {kind:?}"),
+		};
+
+		assert_eq!(get_conflicts("Props"), ["name"]);
+		assert!(get_conflicts("Loose").is_empty());
+	}
+}
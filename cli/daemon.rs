@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use futures::{stream::FuturesUnordered, StreamExt};
+use log::{error, info};
+use notify::{recommended_watcher, Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+	collections::{BTreeSet, HashSet},
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+};
+use tokio::{
+	fs::remove_file,
+	io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+	net::{UnixListener, UnixStream},
+	select,
+	sync::Mutex,
+};
+
+use crate::{
+	build::{get_engine_bindings, load_collection, validate_collection, BuildOptions},
+	collect::Collection,
+	diagnostic::DiagnosticList,
+	engine::BindingsWriter,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct DaemonParams<'a> {
+	pub build_options: BuildOptions<'a>,
+	pub bindings_writer: &'a BindingsWriter,
+	pub socket_path: &'a Path,
+}
+
+/// A single request to the daemon. Requests are newline-delimited JSON, such as `{"id":1,"command":"check"}`
+#[derive(Debug, Deserialize)]
+struct Request {
+	/// Echoed back in the response, so that clients can match responses to requests
+	#[serde(default)]
+	id: Value,
+	command: Command,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+enum Command {
+	/// Collect the runtime, responding with every collection error
+	Check,
+	/// Validate the runtime and write the bindings of every engine, responding with whether they were written. The runtime is not
+	/// bundled, and no other outputs are written.
+	Bindings,
+}
+
+/// The collection that commands are run against, which is reused until one of the runtime's local files changes
+struct Loaded {
+	collection: Arc<Collection>,
+	stale: Arc<AtomicBool>,
+	/// Marks the collection as stale when a local file of the runtime changes. Kept alive for as long as the collection is used
+	_watcher: Option<RecommendedWatcher>,
+}
+
+struct Daemon<'a> {
+	params: DaemonParams<'a>,
+	loaded: Mutex<Option<Loaded>>,
+}
+
+impl<'a> Daemon<'a> {
+	fn new(params: DaemonParams<'a>) -> Daemon<'a> {
+		Daemon {
+			params,
+			loaded: Mutex::new(None),
+		}
+	}
+
+	/// Get the collection of the runtime, collecting it again only if it has changed since it was last collected. Requests that
+	/// arrive while the runtime is being collected wait for that collection, rather than starting their own.
+	async fn get_collection(&self) -> Result<Arc<Collection>> {
+		let mut loaded = self.loaded.lock().await;
+
+		if let Some(loaded) = loaded.as_ref() {
+			if !loaded.stale.load(Ordering::SeqCst) {
+				return Ok(loaded.collection.clone());
+			}
+
+			info!("The runtime changed since it was last collected");
+		}
+
+		let (collection, bundler) = load_collection(self.params.build_options).await?;
+		let stale = Arc::new(AtomicBool::new(false));
+		let watcher = watch_files(bundler.get_local_files(), stale.clone())?;
+		let collection = Arc::new(collection);
+
+		*loaded = Some(Loaded {
+			collection: collection.clone(),
+			stale,
+			_watcher: Some(watcher),
+		});
+
+		Ok(collection)
+	}
+}
+
+/// Listen on a unix socket at `socket_path`, running a command for every request. The process stays alive between requests, and the
+/// collected runtime is reused until one of its local files changes, so that clients like editor integrations don't pay the startup
+/// cost on every run. Connections are handled concurrently, but the requests of a single connection are answered in order.
+pub async fn run_daemon(params: DaemonParams<'_>) -> Result<()> {
+	let listener = bind(params.socket_path).await?;
+	info!("Daemon is listening on {:?}", params.socket_path);
+
+	serve(listener, &Daemon::new(params)).await
+}
+
+async fn bind(socket_path: &Path) -> Result<UnixListener> {
+	// a socket left behind by a previous daemon would prevent binding
+	if socket_path.exists() {
+		remove_file(socket_path)
+			.await
+			.with_context(|| format!("failed to remove the existing socket at {socket_path:?}"))?;
+	}
+
+	UnixListener::bind(socket_path).with_context(|| format!("failed to listen on {socket_path:?}"))
+}
+
+async fn serve(listener: UnixListener, daemon: &Daemon<'_>) -> Result<()> {
+	let mut connections = FuturesUnordered::new();
+
+	loop {
+		select! {
+			accepted = listener.accept() => {
+				let (stream, _) = accepted.context("failed to accept a daemon connection")?;
+
+				connections.push(handle_connection(stream, daemon));
+			}
+			Some(result) = connections.next() => {
+				if let Err(error) = result {
+					error!("{error:?}");
+				}
+			}
+		}
+	}
+}
+
+async fn handle_connection(stream: UnixStream, daemon: &Daemon<'_>) -> Result<()> {
+	let (reader, mut writer) = stream.into_split();
+	let mut lines = BufReader::new(reader).lines();
+
+	while let Some(line) = lines.next_line().await.context("failed to read from a daemon connection")? {
+		if line.trim().is_empty() {
+			continue;
+		}
+
+		let response = match serde_json::from_str::<Request>(&line) {
+			Ok(request) => match run_command(request.command, daemon).await {
+				Ok(result) => json!({ "id": request.id, "result": result }),
+				Err(error) => json!({ "id": request.id, "error": format!("{error:#}") }),
+			},
+			Err(error) => json!({ "id": null, "error": format!("invalid request: {error}") }),
+		};
+
+		let mut response = response.to_string();
+		response.push('\n');
+
+		writer.write_all(response.as_bytes()).await.context("failed to write to a daemon connection")?;
+	}
+
+	Ok(())
+}
+
+async fn run_command(command: Command, daemon: &Daemon<'_>) -> Result<Value> {
+	let collection = daemon.get_collection().await?;
+	let params = daemon.params;
+
+	match command {
+		Command::Check => {
+			let errors = collection
+				.get_categorized_errors()
+				.iter()
+				.map(|error| {
+					json!({
+						"category": error.category.to_string(),
+						"name": error.name,
+						"location": error.location.map(|location| json!({
+							"filename": location.filename,
+							"line": location.line,
+							"col": location.col,
+						})),
+						"message": format!("{:#}", error.error),
+					})
+				})
+				.collect::<Vec<_>>();
+
+			Ok(json!({ "errors": errors }))
+		}
+		Command::Bindings => {
+			let mut diagnostic_list = DiagnosticList::new();
+			validate_collection(&mut diagnostic_list, &collection, params.build_options)?;
+
+			params.bindings_writer.write(get_engine_bindings(&collection, params.build_options)?).await?;
+
+			Ok(json!({ "success": true }))
+		}
+	}
+}
+
+/// Set `stale` when any of `files` changes. Editors often save by replacing a file, so the directories of the files are watched, rather
+/// than the files themselves.
+fn watch_files(files: Vec<PathBuf>, stale: Arc<AtomicBool>) -> Result<RecommendedWatcher> {
+	let directories = files.iter().filter_map(|file| file.parent()).map(Path::to_path_buf).collect::<BTreeSet<_>>();
+	let files = files.into_iter().collect::<HashSet<_>>();
+
+	let mut watcher = recommended_watcher(move |event: notify::Result<Event>| {
+		// if events were missed, the files may have changed
+		let is_change = match event {
+			Ok(event) => !event.kind.is_access() && event.paths.iter().any(|path| files.contains(path)),
+			Err(_) => true,
+		};
+
+		if is_change {
+			stale.store(true, Ordering::SeqCst);
+		}
+	})
+	.context("failed to create a watcher for the runtime")?;
+
+	for directory in directories {
+		watcher
+			.watch(&directory, RecursiveMode::NonRecursive)
+			.with_context(|| format!("failed to watch {directory:?}"))?;
+	}
+
+	Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use serde_json::from_str;
+	use std::{env::temp_dir, num::NonZeroUsize, process, time::Duration};
+	use tokio::time::timeout;
+	use url::Url;
+
+	#[tokio::test]
+	async fn check_is_answered_while_another_connection_is_open() {
+		let socket_path = temp_dir().join(format!("objection_daemon_{}.sock", process::id()));
+		let url = Url::parse("file:///project/runtime.tsx").unwrap();
+		let bindings_writer = BindingsWriter::new(Vec::new());
+		let build_options = BuildOptions {
+			runtime: &url,
+			runtime_source: None,
+			bundler: &url,
+			engine_url: &url,
+			engines: &[],
+			bindings_header: None,
+			bindings_split: false,
+			format_bindings: false,
+			defines: &[],
+			print_graph: None,
+			verify_bindings: false,
+			changed_files: None,
+			protocol_path: None,
+			schema_path: None,
+			entry_path: None,
+			jobs: NonZeroUsize::MIN,
+			emit_declarations: false,
+			asset_headers: &[],
+			asset_root: None,
+			namespaces: false,
+			allowed_module_schemes: &[],
+			allowed_hosts: &[],
+			json_style: None,
+			exclude: &[],
+			max_depth: None,
+			check_reserved_names: false,
+			reserved_names: &[],
+			deny_type_name_conflicts: false,
+			stages: &[],
+		};
+
+		// a fresh collection is reused, so nothing is loaded from disk
+		let daemon = Daemon {
+			params: DaemonParams {
+				build_options,
+				bindings_writer: &bindings_writer,
+				socket_path: &socket_path,
+			},
+			loaded: Mutex::new(Some(Loaded {
+				collection: Arc::new(Collection::default()),
+				stale: Arc::new(AtomicBool::new(false)),
+				_watcher: None,
+			})),
+		};
+		let listener = bind(&socket_path).await.unwrap();
+
+		let client = async {
+			// an idle connection must not hold up the others
+			let _idle = UnixStream::connect(&socket_path).await.unwrap();
+			let (reader, mut writer) = UnixStream::connect(&socket_path).await.unwrap().into_split();
+			let mut lines = BufReader::new(reader).lines();
+
+			writer.write_all(b"{\"id\":1,\"command\":\"check\"}\nnot json\n").await.unwrap();
+
+			let check = lines.next_line().await.unwrap().unwrap();
+			let invalid = lines.next_line().await.unwrap().unwrap();
+
+			(from_str::<Value>(&check).unwrap(), from_str::<Value>(&invalid).unwrap())
+		};
+
+		let (check, invalid) = select! {
+			result = serve(listener, &daemon) => panic!("the daemon stopped: {result:?}"),
+			responses = timeout(Duration::from_secs(10), client) => responses.expect("the daemon didn't respond"),
+		};
+		remove_file(&socket_path).await.unwrap();
+
+		assert_eq!(check, json!({ "id": 1, "result": { "errors": [] } }));
+		assert_eq!(invalid["id"], Value::Null);
+		assert!(invalid["error"].as_str().unwrap().starts_with("invalid request"));
+	}
+}
@@ -2,8 +2,15 @@ use anstyle::Style;
 use anyhow::{Error, Result};
 use colored::Colorize;
 use deno_doc::Location;
-use log::{error, warn};
-use std::fmt::{Display, Write};
+use log::{error, info, warn};
+use std::{
+	collections::HashMap,
+	fmt::{Display, Write},
+	sync::Mutex,
+};
+
+/// The number of warnings that have been printed, keyed by the file that they were attributed to. See `print_warning_report`
+static WARNING_COUNTS: Mutex<Option<HashMap<Option<String>, usize>>> = Mutex::new(None);
 
 pub struct DiagnosticList {
 	diagnostics: Vec<Diagnostic>,
@@ -22,11 +29,16 @@ impl DiagnosticList {
 		self.diagnostics.push(Diagnostic::from_error(error))
 	}
 
+	#[cfg(test)]
+	pub fn get_messages(&self) -> Vec<String> {
+		self.diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect()
+	}
+
 	pub fn flush(&mut self, operation: impl Display) -> Result<()> {
 		let error_count = self.diagnostics.len();
 
 		for mut diagnostic in self.diagnostics.drain(..) {
-			diagnostic.0.push('\n');
+			diagnostic.message.push('\n');
 			diagnostic.print_error();
 		}
 
@@ -45,7 +57,11 @@ impl DiagnosticList {
 	}
 }
 
-pub struct Diagnostic(String);
+pub struct Diagnostic {
+	message: String,
+	/// The file of the first location in this diagnostic, if there is one
+	file: Option<String>,
+}
 
 impl Diagnostic {
 	pub fn from_error(error: Error) -> Diagnostic {
@@ -60,15 +76,19 @@ impl Diagnostic {
 			string = new_string;
 		}
 
-		Diagnostic(string)
+		Diagnostic { message: string, file: None }
 	}
 
 	pub fn start(initial_message: impl Display) -> DiagnosticBuilder {
-		DiagnosticBuilder::new(Diagnostic(String::new())).text(initial_message)
+		DiagnosticBuilder::new(Diagnostic {
+			message: String::new(),
+			file: None,
+		})
+		.text(initial_message)
 	}
 
 	pub fn error(self) -> Error {
-		Error::msg(self.0)
+		Error::msg(self.message)
 	}
 
 	pub fn err<T>(self) -> Result<T> {
@@ -80,13 +100,54 @@ impl Diagnostic {
 	}
 
 	pub fn print_warn(self) {
-		warn!("{}", self.0)
+		record_warning(self.file.as_deref());
+		warn!("{}", self.message)
 	}
 }
 
+/// Count a warning towards the warning report. Only needed for warnings that are not printed with `Diagnostic::print_warn`
+pub fn record_warning(file: Option<&str>) {
+	let mut counts = WARNING_COUNTS.lock().unwrap();
+
+	*counts.get_or_insert_with(HashMap::new).entry(file.map(|file| file.to_string())).or_default() += 1;
+}
+
+/// Every file that warnings were attributed to, along with its number of warnings. The files with the most warnings come first.
+pub fn get_warning_counts() -> Vec<(Option<String>, usize)> {
+	let counts = WARNING_COUNTS.lock().unwrap();
+	let mut rows = counts.iter().flatten().map(|(file, count)| (file.clone(), *count)).collect::<Vec<_>>();
+
+	rows.sort_by(|(a_file, a_count), (b_file, b_count)| b_count.cmp(a_count).then_with(|| a_file.cmp(b_file)));
+
+	rows
+}
+
+/// Print a table of every file that warnings were attributed to, sorted by the number of warnings, so that cleanup can be
+/// prioritized
+pub fn print_warning_report() {
+	let rows = get_warning_counts();
+
+	if rows.is_empty() {
+		info!("No warnings were reported");
+
+		return;
+	}
+
+	let total = rows.iter().map(|(_, count)| count).sum::<usize>();
+	let count_width = total.to_string().len().max("Warnings".len());
+
+	println!("\n{:>count_width$}  {}", "Warnings".bold(), "File".bold());
+
+	for (file, count) in rows {
+		println!("{count:>count_width$}  {}", file.as_deref().unwrap_or("(no location)"));
+	}
+
+	println!("{:>count_width$}  {}", total.to_string().bold(), "Total".bold());
+}
+
 impl Display for Diagnostic {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-		write!(f, "{}", self.0)
+		write!(f, "{}", self.message)
 	}
 }
 
@@ -99,32 +160,36 @@ pub struct DiagnosticBuilder {
 
 impl DiagnosticBuilder {
 	pub fn new(mut diagnostic: Diagnostic) -> DiagnosticBuilder {
-		write!(&mut diagnostic.0, "{FORE_STYLE}").unwrap();
+		write!(&mut diagnostic.message, "{FORE_STYLE}").unwrap();
 
 		DiagnosticBuilder { diagnostic, did_shift: false }
 	}
 
 	pub fn inline_code(mut self, code: impl Display) -> DiagnosticBuilder {
-		write!(&mut self.diagnostic.0, "`{code}`").unwrap();
+		write!(&mut self.diagnostic.message, "`{code}`").unwrap();
 
 		self
 	}
 
 	pub fn shift(mut self) -> DiagnosticBuilder {
-		write!(&mut self.diagnostic.0, "{FORE_STYLE:#}\n  {}", "--> ".bold().blue()).unwrap();
+		write!(&mut self.diagnostic.message, "{FORE_STYLE:#}\n  {}", "--> ".bold().blue()).unwrap();
 		self.did_shift = true;
 
 		self
 	}
 
 	pub fn text(mut self, text: impl Display) -> DiagnosticBuilder {
-		write!(&mut self.diagnostic.0, "{text}").unwrap();
+		write!(&mut self.diagnostic.message, "{text}").unwrap();
 
 		self
 	}
 
 	pub fn location(mut self, location: &Location) -> DiagnosticBuilder {
-		write!(&mut self.diagnostic.0, "{}:{}:{}", &location.filename, &location.line, &location.col).unwrap();
+		if self.diagnostic.file.is_none() {
+			self.diagnostic.file = Some(location.filename.clone());
+		}
+
+		write!(&mut self.diagnostic.message, "{}:{}:{}", &location.filename, &location.line, &location.col).unwrap();
 
 		self
 	}
@@ -140,7 +205,7 @@ impl DiagnosticBuilder {
 			if first {
 				first = false;
 			} else {
-				write!(&mut self.diagnostic.0, ", ").unwrap();
+				write!(&mut self.diagnostic.message, ", ").unwrap();
 			}
 
 			self = func(self, item)
@@ -149,3 +214,30 @@ impl DiagnosticBuilder {
 		self
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn warnings_are_counted_per_file_with_the_noisiest_files_first() {
+		for file in ["/report/b.ts", "/report/a.ts", "/report/c.ts", "/report/a.ts", "/report/c.ts", "/report/a.ts"] {
+			record_warning(Some(file));
+		}
+
+		// other tests may record warnings at the same time, so only the files of this test are considered
+		let counts = get_warning_counts()
+			.into_iter()
+			.filter(|(file, _)| file.as_deref().is_some_and(|file| file.starts_with("/report/")))
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			counts,
+			[
+				(Some("/report/a.ts".to_string()), 3),
+				(Some("/report/c.ts".to_string()), 2),
+				(Some("/report/b.ts".to_string()), 1)
+			]
+		);
+	}
+}
@@ -0,0 +1,208 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+	collect::{Collection, ComponentInfo},
+	convert::Kind,
+	gen_ts::gen_kind,
+};
+
+/// The API changes between two versions of a runtime. See `Collection::diff`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionDiff {
+	pub changes: Vec<Change>,
+}
+
+impl CollectionDiff {
+	pub fn get_breaking_changes(&self) -> Vec<&Change> {
+		self.changes.iter().filter(|change| change.is_breaking).collect()
+	}
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+	pub component: String,
+	pub item: ChangedItem,
+	/// The name of the prop, event, or action. `None` if the whole component changed
+	pub name: Option<String>,
+	pub kind: ChangeKind,
+	/// The type before the change. `None` if the item was added, or the item is a component
+	pub before: Option<String>,
+	/// The type after the change. `None` if the item was removed, or the item is a component
+	pub after: Option<String>,
+	/// Whether an engine that was written against the old version could fail to build or communicate with the new version
+	pub is_breaking: bool,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangedItem {
+	Component,
+	Prop,
+	Event,
+	Action,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ChangeKind {
+	Added,
+	Removed,
+	Changed,
+}
+
+/// The type of a single prop, event, or action, as it is compared between versions
+#[derive(Debug, PartialEq, Eq)]
+struct ItemType {
+	type_tokens: String,
+	is_optional: bool,
+}
+
+impl Collection {
+	/// Compare the components of `self`, the old version of a runtime, with those of `other`, the new version. Types are compared by
+	/// how they are written, so a change to a type that a prop references is not reported.
+	pub fn diff(&self, other: &Collection) -> CollectionDiff {
+		let old_components = get_components(self);
+		let new_components = get_components(other);
+		let names = old_components.keys().chain(new_components.keys()).collect::<BTreeSet<_>>();
+		let mut changes = Vec::new();
+
+		for name in names {
+			let (old, new) = match (old_components.get(name), new_components.get(name)) {
+				(Some(old), Some(new)) => (old, new),
+				(old, _) => {
+					changes.push(Change {
+						component: name.to_string(),
+						item: ChangedItem::Component,
+						name: None,
+						kind: if old.is_some() { ChangeKind::Removed } else { ChangeKind::Added },
+						before: None,
+						after: None,
+						is_breaking: old.is_some(),
+					});
+
+					continue;
+				}
+			};
+
+			for item in [ChangedItem::Prop, ChangedItem::Event, ChangedItem::Action] {
+				diff_items(&mut changes, name, item, get_items(self, name, old, item), get_items(other, name, new, item));
+			}
+		}
+
+		CollectionDiff { changes }
+	}
+}
+
+fn get_components(collection: &Collection) -> BTreeMap<&str, &ComponentInfo> {
+	collection.get_component_info().into_iter().collect()
+}
+
+/// Get the props, events, or actions of a component. Event and action keys are props too, but they are only reported as events
+/// and actions, so that their changes aren't reported twice.
+fn get_items(collection: &Collection, component: &str, info: &ComponentInfo, item: ChangedItem) -> BTreeMap<String, ItemType> {
+	let keys = match item {
+		ChangedItem::Event => &info.events,
+		ChangedItem::Action => &info.actions,
+		_ => {
+			let properties = match collection.get_kind(component) {
+				Some(Kind::Object { properties }) => properties,
+				_ => return BTreeMap::new(),
+			};
+
+			return properties
+				.iter()
+				.filter(|property| !info.events.contains_key(&property.name) && !info.actions.contains_key(&property.name))
+				.map(|property| {
+					let item_type = ItemType {
+						type_tokens: gen_kind(&property.kind),
+						is_optional: property.is_optional,
+					};
+
+					(property.name.clone(), item_type)
+				})
+				.collect();
+		}
+	};
+
+	keys.iter()
+		.map(|(name, key)| {
+			let item_type = ItemType {
+				type_tokens: key.data_type.clone(),
+				is_optional: false,
+			};
+
+			(name.clone(), item_type)
+		})
+		.collect()
+}
+
+fn diff_items(changes: &mut Vec<Change>, component: &str, item: ChangedItem, old: BTreeMap<String, ItemType>, new: BTreeMap<String, ItemType>) {
+	let names = old.keys().chain(new.keys()).cloned().collect::<BTreeSet<_>>();
+
+	for name in names {
+		let (kind, is_breaking) = match (old.get(&name), new.get(&name)) {
+			(Some(old), Some(new)) if old == new => continue,
+			(Some(_), Some(_)) => (ChangeKind::Changed, true),
+			// the engine can't set a prop that doesn't exist, or listen for an event that is never sent
+			(Some(_), None) => (ChangeKind::Removed, true),
+			// the engine must now set a new required prop, but nothing depends on a new event or action yet
+			(None, Some(new)) => (ChangeKind::Added, item == ChangedItem::Prop && !new.is_optional),
+			(None, None) => continue,
+		};
+
+		changes.push(Change {
+			component: component.to_string(),
+			item,
+			before: old.get(&name).map(describe_item_type),
+			after: new.get(&name).map(describe_item_type),
+			name: Some(name),
+			kind,
+			is_breaking,
+		});
+	}
+}
+
+fn describe_item_type(item_type: &ItemType) -> String {
+	if item_type.is_optional {
+		format!("{} | undefined", item_type.type_tokens)
+	} else {
+		item_type.type_tokens.clone()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::collect::collect_source;
+
+	#[tokio::test]
+	async fn removed_props_are_breaking_changes() {
+		let old = collect_source("/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n\tcolor: string\n}\n").await;
+		let new = collect_source("/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n\tsize?: number\n}\n").await;
+		let diff = old.diff(&new);
+		let changes = diff
+			.changes
+			.iter()
+			.map(|change| (change.item, change.name.as_deref(), change.kind, change.is_breaking))
+			.collect::<Vec<_>>();
+
+		assert_eq!(
+			changes,
+			[
+				(ChangedItem::Prop, Some("color"), ChangeKind::Removed, true),
+				(ChangedItem::Prop, Some("size"), ChangeKind::Added, false)
+			]
+		);
+
+		let breaking = diff.get_breaking_changes();
+
+		assert_eq!(breaking.len(), 1);
+		assert_eq!(breaking[0].component, "Button");
+		assert_eq!(breaking[0].before.as_deref(), Some("string"));
+		assert_eq!(breaking[0].after, None);
+		assert!(new.diff(&new).changes.is_empty());
+	}
+}
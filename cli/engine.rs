@@ -1,15 +1,32 @@
-use std::fmt::{self, Display};
+use std::{
+	collections::BTreeMap,
+	fmt::{self, Display},
+	io::Write,
+	path::PathBuf,
+	process::{Command, Stdio},
+	thread,
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use clap::ValueEnum;
 use log::info;
 
-use crate::{collect::Collection, gen_rust::RustGen};
+use crate::{
+	collect::Collection,
+	diagnostic::Diagnostic,
+	gen_go::GoGen,
+	gen_rust::RustGen,
+	gen_ts::TsGen,
+	writer::{BuildCache, FileWriter},
+};
 
-#[derive(Default, Debug, ValueEnum, Clone, Copy)]
+#[derive(Default, Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
 pub enum Engine {
 	#[default]
 	Rust,
+	#[value(name = "typescript")]
+	TypeScript,
+	Go,
 }
 
 impl Display for Engine {
@@ -18,19 +35,290 @@ impl Display for Engine {
 	}
 }
 
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BindingsParams<'a> {
+	/// Placed at the top of the generated bindings
+	pub header: Option<&'a str>,
+	/// Split rust bindings into a file per component, with the header at the top of each. See `RustGen::with_split_files`
+	pub split: bool,
+	/// Format rust bindings with rustfmt. See `format_rust`
+	pub format: bool,
+}
+
 impl Engine {
-	pub fn get_bindings(&self, collection: &Collection) -> Result<String> {
+	/// Generate the bindings for this engine. Only rust bindings can be split or formatted; other engines always generate a single file
+	pub fn get_bindings(&self, collection: &Collection, params: BindingsParams<'_>) -> Result<Bindings> {
+		let BindingsParams { header, split, format } = params;
+
 		match self {
 			Self::Rust => {
 				let mut gen = RustGen::new(collection)?;
+
+				if let Some(header) = header {
+					let header = header.trim_end().to_string();
+
+					gen = gen.with_postprocess(Box::new(move |output| format!("{header}\n\n{output}")));
+				}
+
+				if split {
+					gen = gen.with_split_files();
+				}
+
 				gen.gen()?;
 				info!("Generated rust engine bindings");
 
-				let output = gen.get_output();
+				let mut bindings = if split {
+					Bindings::Split(gen.get_split_output())
+				} else {
+					Bindings::File(gen.get_output())
+				};
 				info!("Formatted rust engine bindings");
 
-				Ok(output)
+				if format {
+					let sources = match &mut bindings {
+						Bindings::File(contents) => Vec::from([contents]),
+						Bindings::Split(files) => files.values_mut().collect(),
+					};
+
+					if format_rust(sources) {
+						info!("Formatted rust engine bindings with rustfmt");
+					}
+				}
+
+				Ok(bindings)
+			}
+			Self::TypeScript => {
+				let mut gen = TsGen::new(collection)?;
+
+				gen.gen()?;
+				info!("Generated typescript engine bindings");
+
+				let output = gen.get_output();
+
+				Ok(Bindings::File(match header {
+					Some(header) => format!("{}\n\n{output}", header.trim_end()),
+					None => output,
+				}))
+			}
+			Self::Go => {
+				let mut gen = GoGen::new(collection)?;
+
+				gen.gen()?;
+				info!("Generated go engine bindings");
+
+				let output = gen.get_output();
+
+				// comments above the package clause are allowed, so the header can go first
+				Ok(Bindings::File(match header {
+					Some(header) => format!("{}\n\n{output}", header.trim_end()),
+					None => output,
+				}))
+			}
+		}
+	}
+
+	/// Confirm that `bindings` are valid code for this engine. Catches generator bugs before the bindings reach the engine's build
+	pub fn verify_bindings(&self, bindings: &Bindings) -> Result<()> {
+		match self {
+			Self::Rust => {
+				for (path, contents) in bindings.get_files() {
+					syn::parse_file(contents)
+						.map_err(|error| {
+							let mut builder = Diagnostic::start("Generated rust bindings are not valid rust. This is a bug.");
+
+							if let Some(path) = path {
+								builder = builder.shift().text("In ").inline_code(path.display());
+							}
+
+							builder.shift().text(error).build().error()
+						})
+						.context("Failed to verify rust engine bindings")?;
+				}
+
+				info!("Verified rust engine bindings");
+
+				Ok(())
+			}
+			// there is no typescript parser available to the cli, so typescript bindings are trusted
+			Self::TypeScript => {
+				info!("Skipped verifying typescript engine bindings, as they can't be parsed by the cli");
+
+				Ok(())
+			}
+			// nor is there a go parser
+			Self::Go => {
+				info!("Skipped verifying go engine bindings, as they can't be parsed by the cli");
+
+				Ok(())
+			}
+		}
+	}
+}
+
+/// Format each of `sources` in place with the rustfmt on PATH, so that the bindings follow the style of the rest of the engine, as set
+/// by its rustfmt config. If rustfmt is missing or fails, the remaining sources are left as generated, with a warning. Returns whether
+/// every source was formatted.
+fn format_rust(sources: Vec<&mut String>) -> bool {
+	for source in sources {
+		match run_rustfmt(source) {
+			Ok(formatted) => *source = formatted,
+			Err(error) => {
+				Diagnostic::start("Rust bindings could not be formatted with rustfmt, so they will be written as generated")
+					.shift()
+					.text(format!("{error:#}"))
+					.shift()
+					.text("Install rustfmt with ")
+					.inline_code("rustup component add rustfmt")
+					.text(", or skip formatting with ")
+					.inline_code("--no-format")
+					.build()
+					.print_warn();
+
+				return false;
+			}
+		}
+	}
+
+	true
+}
+
+fn run_rustfmt(source: &str) -> Result<String> {
+	let mut child = Command::new("rustfmt")
+		.args(["--edition", "2021"])
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.context("failed to run rustfmt")?;
+
+	// writing from another thread keeps a full stdout pipe from blocking the write
+	let mut stdin = child.stdin.take().ok_or(anyhow!("failed to open the stdin of rustfmt"))?;
+	let input = source.to_string();
+	let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+	let output = child.wait_with_output().context("failed to wait for rustfmt")?;
+	writer
+		.join()
+		.map_err(|_| anyhow!("failed to write to rustfmt"))?
+		.context("failed to write to rustfmt")?;
+
+	if !output.status.success() {
+		bail!("rustfmt exited with {}: {}", output.status, String::from_utf8_lossy(&output.stderr).trim());
+	}
+
+	String::from_utf8(output.stdout).context("rustfmt wrote invalid utf-8")
+}
+
+/// The bindings generated for a single engine
+#[derive(Debug)]
+pub enum Bindings {
+	/// Written to the bindings path
+	File(String),
+	/// Written into the bindings path, which is a directory, keyed by their paths relative to it. See `RustGen::get_split_output`
+	Split(BTreeMap<PathBuf, String>),
+}
+
+impl Bindings {
+	/// Every file of the bindings, along with its relative path if the bindings are split
+	pub fn get_files(&self) -> Vec<(Option<&PathBuf>, &str)> {
+		match self {
+			Self::File(contents) => Vec::from([(None, contents.as_str())]),
+			Self::Split(files) => files.iter().map(|(path, contents)| (Some(path), contents.as_str())).collect(),
+		}
+	}
+}
+
+/// The bindings generated for each engine of a build. See `BuildOptions::engines`
+#[derive(Debug)]
+pub struct EngineBindings(pub Vec<(Engine, Bindings)>);
+
+/// Writes the bindings of each engine to that engine's file
+#[derive(Debug)]
+pub struct BindingsWriter {
+	writers: Vec<(Engine, FileWriter)>,
+}
+
+impl BindingsWriter {
+	pub fn new(writers: Vec<(Engine, FileWriter)>) -> BindingsWriter {
+		BindingsWriter { writers }
+	}
+
+	pub async fn write(&self, bindings: EngineBindings) -> Result<()> {
+		self.write_inner(bindings, None).await
+	}
+
+	/// Like `BindingsWriter::write`, but bindings that haven't changed since the last build are not rewritten. See `BuildCache`
+	pub async fn write_cached(&self, bindings: EngineBindings, cache: &mut BuildCache) -> Result<()> {
+		self.write_inner(bindings, Some(cache)).await
+	}
+
+	async fn write_inner(&self, bindings: EngineBindings, mut cache: Option<&mut BuildCache>) -> Result<()> {
+		for (engine, bindings) in bindings.0 {
+			let writer = match self.writers.iter().find(|(writer_engine, _)| *writer_engine == engine) {
+				Some((_, writer)) => writer,
+				None => continue,
+			};
+
+			for (path, contents) in bindings.get_files() {
+				let result = match (cache.as_deref_mut(), path) {
+					(Some(cache), Some(path)) => writer.write_child_cached(path, contents, cache).await,
+					(Some(cache), None) => writer.write_cached(contents, cache).await,
+					(None, Some(path)) => writer.write_child(path, contents).await,
+					(None, None) => writer.write(contents).await,
+				};
+
+				result.with_context(|| format!("failed to write the {engine} engine bindings"))?;
 			}
 		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::collect::collect_source;
+
+	const RUNTIME: &str = "/**\n * @feature_component_index\n */\nexport interface Component {\n\tid: number\n}\n";
+
+	#[tokio::test]
+	async fn headers_are_placed_at_the_top_of_the_bindings() {
+		let collection = collect_source(RUNTIME).await;
+		let header = "// Copyright Example Inc.\n#![allow(dead_code)]\n\n\n";
+		let bindings = Engine::Rust
+			.get_bindings(
+				&collection,
+				BindingsParams {
+					header: Some(header),
+					..Default::default()
+				},
+			)
+			.unwrap();
+
+		let output = match bindings {
+			Bindings::File(output) => output,
+			bindings => panic!("expected a single file of bindings, but got {bindings:?}"),
+		};
+
+		assert!(output.starts_with("// Copyright Example Inc.\n#![allow(dead_code)]\n\n"));
+		assert!(!output.starts_with("// Copyright Example Inc.\n#![allow(dead_code)]\n\n\n"));
+		assert!(output.contains("pub enum Component"));
+	}
+
+	#[test]
+	fn broken_rust_bindings_fail_verification() {
+		let valid = Bindings::File("pub struct Button { pub label: String }".to_string());
+		let broken = Bindings::Split(BTreeMap::from([
+			(PathBuf::from("mod.rs"), "pub mod button;".to_string()),
+			(PathBuf::from("button.rs"), "pub struct Button { pub type: String }".to_string()),
+		]));
+
+		assert!(Engine::Rust.verify_bindings(&valid).is_ok());
+
+		let error = format!("{:?}", Engine::Rust.verify_bindings(&broken).unwrap_err());
+
+		assert!(error.contains("Generated rust bindings are not valid rust"));
+		assert!(error.contains("`button.rs`"));
 	}
 }
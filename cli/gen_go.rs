@@ -0,0 +1,537 @@
+use anyhow::{anyhow, bail, Result};
+use inflector::Inflector;
+use log::debug;
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{
+	collect::Collection,
+	convert::{EnumProperty, EnumValue, Kind, ObjectProperty, SerdeAttribute, ValueEnumVariant},
+	diagnostic::Diagnostic,
+};
+
+/// The package that the generated bindings are declared in
+const PACKAGE_NAME: &str = "bindings";
+
+const PRELUDE: &str = "\
+import (
+	\"encoding/json\"
+	\"fmt\"
+)
+
+// EventKey is an event that could be triggered, where T is the data that the event will contain
+type EventKey[T any] struct {
+	EventPath   []string `json:\"eventPath\"`
+	DebugSymbol *string  `json:\"debugSymbol\"`
+	Version     *uint32  `json:\"version,omitempty\"`
+	Prefix      *string  `json:\"prefix,omitempty\"`
+}
+
+// ActionKey is an action that could be triggered, where T is the data that the action will contain
+type ActionKey[T any] struct {
+	ActionPath  []string `json:\"actionPath\"`
+	DebugSymbol *string  `json:\"debugSymbol\"`
+}
+
+// taggedVariant is the wire format of a keyed union, where Type is the name of the variant
+type taggedVariant struct {
+	Type string          `json:\"type\"`
+	Def  json.RawMessage `json:\"def\"`
+}
+
+func marshalTaggedVariant(name string, def any) ([]byte, error) {
+	encoded, err := json.Marshal(def)
+	if err != nil {
+		return nil, err
+	}
+
+	return json.Marshal(taggedVariant{Type: name, Def: encoded})
+}
+";
+
+/// A variant of a keyed union. See `GoGen::gen_tagged_union`
+struct TaggedVariant {
+	comment: Option<String>,
+	/// The name that the variant is sent as
+	name: String,
+	type_tokens: String,
+}
+
+pub struct GoGen<'a> {
+	collection: &'a Collection,
+	names_generated: HashSet<String>,
+	index_name: &'a str,
+	/// Named types, in the order that they were generated
+	items: Vec<String>,
+}
+
+impl GoGen<'_> {
+	pub fn new<'a>(collection: &'a Collection) -> Result<GoGen<'a>> {
+		let index_name = collection.get_component_index_name().ok_or(anyhow!(
+			"No component index was found during go code gen. This indicates a failure in the checking step"
+		))?;
+
+		Ok(GoGen {
+			collection,
+			index_name,
+			names_generated: HashSet::new(),
+			items: Vec::new(),
+		})
+	}
+
+	pub fn gen(&mut self) -> Result<()> {
+		self.gen_index();
+
+		for def in self.collection.get_kinds() {
+			if def.name == self.index_name {
+				continue;
+			}
+
+			debug!("Generating {}", def.name);
+			let name = get_type_name(def.name);
+
+			match def.kind {
+				Kind::StringEnum { .. } | Kind::ValueEnum { .. } | Kind::KeyedEnum { .. } | Kind::Object { .. } => {
+					self.gen_kind(&name, def.comment, def.kind)?;
+				}
+				kind => {
+					if !self.has_item(&name) {
+						let type_tokens = self.gen_kind(&name, None, kind)?;
+						let mut item = get_comment(def.comment);
+						writeln!(item, "type {name} = {type_tokens}").unwrap();
+
+						self.add_item(&name, item);
+					}
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn get_output(self) -> String {
+		let mut output = format!("package {PACKAGE_NAME}\n\n{PRELUDE}");
+
+		for item in self.items {
+			output.push('\n');
+			output.push_str(&item);
+		}
+
+		output
+	}
+
+	fn gen_index(&mut self) {
+		let name = get_type_name(self.index_name);
+		let variants = self
+			.collection
+			.get_component_info()
+			.into_iter()
+			.map(|(component, _)| TaggedVariant {
+				comment: self.collection.get_comment(component).map(|comment| comment.to_string()),
+				name: component.to_string(),
+				type_tokens: get_type_name(component),
+			})
+			.collect::<Vec<_>>();
+
+		self.gen_tagged_union(&name, self.collection.get_comment(self.index_name), &variants);
+	}
+
+	/// Get the go type of `kind`, generating a named type called `context_name` if the kind can't be written inline
+	fn gen_kind(&mut self, context_name: &str, comment: Option<&str>, kind: &Kind) -> Result<String> {
+		Ok(match kind {
+			Kind::Dynamic => "any".to_string(),
+			Kind::String => "string".to_string(),
+			Kind::Number => "float64".to_string(),
+			Kind::Bool => "bool".to_string(),
+			// a nil pointer is encoded as null
+			Kind::Null => "*struct{}".to_string(),
+			Kind::ActionKey { data_type } => format!("ActionKey[{}]", self.gen_kind(&format!("{context_name}ActionData"), None, data_type)?),
+			Kind::EventKey { data_type } => format!("EventKey[{}]", self.gen_kind(&format!("{context_name}EventData"), None, data_type)?),
+			Kind::Ref { name } => get_type_name(name),
+			Kind::List { of } => format!("[]{}", self.gen_kind(&format!("{context_name}Item"), None, of)?),
+			Kind::Map { key, value } => {
+				let key_tokens = self.gen_kind(&format!("{context_name}Key"), None, key)?;
+				let value_tokens = self.gen_kind(&format!("{context_name}Value"), None, value)?;
+
+				format!("map[{key_tokens}]{value_tokens}")
+			}
+			// go has no tuples, and a slice of mixed types can only hold `any`
+			Kind::Tuple { .. } => "[]any".to_string(),
+			Kind::StringEnum { variants } => {
+				if !self.has_item(context_name) {
+					let constants = variants
+						.iter()
+						.map(|variant| (None, format!("{context_name}{}", variant.to_pascal_case()), format!("{variant:?}")))
+						.collect::<Vec<_>>();
+
+					self.gen_constants(context_name, comment, "string", &constants);
+				}
+
+				context_name.to_string()
+			}
+			Kind::ValueEnum { variants } => {
+				if !self.has_item(context_name) {
+					self.gen_value_enum(context_name, comment, variants)?;
+				}
+
+				context_name.to_string()
+			}
+			Kind::KeyedEnum { variants } => {
+				if !self.has_item(context_name) {
+					self.gen_keyed_enum(context_name, comment, variants)?;
+				}
+
+				context_name.to_string()
+			}
+			Kind::Object { properties } => {
+				if !self.has_item(context_name) && self.index_name != context_name {
+					self.gen_struct(context_name, comment, properties)?;
+				}
+
+				context_name.to_string()
+			}
+		})
+	}
+
+	fn has_item(&self, name: &str) -> bool {
+		self.names_generated.contains(name)
+	}
+
+	fn add_item(&mut self, name: &str, item: String) {
+		self.names_generated.insert(name.to_string());
+		self.items.push(item);
+	}
+
+	/// Declare a named type with a typed constant for each of its values. `constants` are `(comment, name, value)`
+	fn gen_constants(&mut self, name: &str, comment: Option<&str>, underlying_type: &str, constants: &[(Option<&str>, String, String)]) {
+		let mut item = get_comment(comment);
+		writeln!(item, "type {name} {underlying_type}\n\nconst (").unwrap();
+
+		for (comment, constant_name, value) in constants {
+			push_comment(&mut item, *comment, "\t");
+			writeln!(item, "\t{constant_name} {name} = {value}").unwrap();
+		}
+
+		item.push_str(")\n");
+
+		self.add_item(name, item);
+	}
+
+	/// Members are named after the enum's members, but are sent as the members' values
+	fn gen_value_enum(&mut self, context_name: &str, comment: Option<&str>, variants: &[ValueEnumVariant]) -> Result<()> {
+		let is_numeric = variants.iter().any(|variant| matches!(variant.value, EnumValue::Number(_)));
+
+		if is_numeric && variants.iter().any(|variant| matches!(variant.value, EnumValue::String(_))) {
+			bail!("Enum {context_name} mixes string and number values, which can't be represented by a single go type");
+		}
+
+		let constants = variants
+			.iter()
+			.map(|variant| {
+				let value = match &variant.value {
+					EnumValue::String(value) => format!("{value:?}"),
+					EnumValue::Number(value) => value.to_string(),
+				};
+
+				(variant.comment.as_deref(), format!("{context_name}{}", variant.name), value)
+			})
+			.collect::<Vec<_>>();
+
+		self.gen_constants(context_name, comment, if is_numeric { "float64" } else { "string" }, &constants);
+
+		Ok(())
+	}
+
+	fn gen_keyed_enum(&mut self, context_name: &str, comment: Option<&str>, variants: &[EnumProperty]) -> Result<()> {
+		let variants = variants
+			.iter()
+			.map(|variant| {
+				let type_tokens = self.gen_kind(&format!("{context_name}{}Def", variant.name), variant.comment.as_deref(), &variant.kind)?;
+
+				Ok(TaggedVariant {
+					comment: variant.comment.clone(),
+					name: variant.name.clone(),
+					type_tokens,
+				})
+			})
+			.collect::<Result<Vec<_>>>()?;
+
+		self.gen_tagged_union(context_name, comment, &variants);
+
+		Ok(())
+	}
+
+	/// Generate a keyed union, which is sent as `{ type, def }`. The union is a struct that holds one of its variants behind an
+	/// interface, so that it can be encoded and decoded with `encoding/json`. Each variant is a struct that wraps its `Def`.
+	fn gen_tagged_union(&mut self, name: &str, comment: Option<&str>, variants: &[TaggedVariant]) {
+		let interface_name = format!("{name}Variant");
+		let marker_name = format!("is{name}Variant");
+		let mut item = get_comment(comment);
+
+		write!(
+			item,
+			"\
+type {name} struct {{
+	Variant {interface_name}
+}}
+
+// {interface_name} is implemented by every variant of {name}
+type {interface_name} interface {{
+	{marker_name}()
+}}
+"
+		)
+		.unwrap();
+
+		for variant in variants {
+			let variant_name = format!("{name}{}", get_type_name(&variant.name));
+
+			item.push('\n');
+			push_comment(&mut item, variant.comment.as_deref(), "");
+			write!(
+				item,
+				"\
+type {variant_name} struct {{
+	Def {}
+}}
+
+func ({variant_name}) {marker_name}() {{}}
+",
+				variant.type_tokens
+			)
+			.unwrap();
+		}
+
+		write!(
+			item,
+			"
+func (value {name}) MarshalJSON() ([]byte, error) {{
+	switch variant := value.Variant.(type) {{
+"
+		)
+		.unwrap();
+
+		for variant in variants {
+			let variant_name = format!("{name}{}", get_type_name(&variant.name));
+
+			writeln!(item, "\tcase {variant_name}:\n\t\treturn marshalTaggedVariant({:?}, variant.Def)", variant.name).unwrap();
+		}
+
+		write!(
+			item,
+			"\
+	}}
+
+	return nil, fmt.Errorf(\"%T is not a variant of {name}\", value.Variant)
+}}
+
+func (value *{name}) UnmarshalJSON(data []byte) error {{
+	var tagged taggedVariant
+	if err := json.Unmarshal(data, &tagged); err != nil {{
+		return err
+	}}
+
+	switch tagged.Type {{
+"
+		)
+		.unwrap();
+
+		for variant in variants {
+			let variant_name = format!("{name}{}", get_type_name(&variant.name));
+
+			write!(
+				item,
+				"\
+	case {:?}:
+		var variant {variant_name}
+		if err := json.Unmarshal(tagged.Def, &variant.Def); err != nil {{
+			return err
+		}}
+		value.Variant = variant
+",
+				variant.name
+			)
+			.unwrap();
+		}
+
+		write!(
+			item,
+			"\
+	default:
+		return fmt.Errorf(\"%q is not a variant of {name}\", tagged.Type)
+	}}
+
+	return nil
+}}
+"
+		)
+		.unwrap();
+
+		self.add_item(name, item);
+	}
+
+	fn gen_struct(&mut self, context_name: &str, comment: Option<&str>, properties: &[ObjectProperty]) -> Result<()> {
+		let mut fields = String::new();
+
+		for property in properties {
+			if property.is_flattened {
+				bail!("The index signature of {context_name} can't be represented in go, because encoding/json can't flatten maps into structs");
+			}
+
+			let field_name = property.name.to_pascal_case();
+			let type_tokens = self.gen_kind(&format!("{context_name}{field_name}"), property.comment.as_deref(), &property.kind)?;
+
+			push_comment(&mut fields, property.comment.as_deref(), "\t");
+
+			if property.serde_attributes.contains(&SerdeAttribute::Flatten) {
+				// encoding/json flattens the fields of embedded structs
+				writeln!(fields, "\t{type_tokens}").unwrap();
+
+				continue;
+			}
+
+			for attribute in &property.serde_attributes {
+				if let SerdeAttribute::With(path) = attribute {
+					Diagnostic::start("The ")
+						.inline_code(format!("@serde with={path}"))
+						.text(" tag of ")
+						.inline_code(format!("{context_name}.{}", property.name))
+						.text(" is ignored by go bindings")
+						.build()
+						.print_warn();
+				}
+			}
+
+			let tag = if property.serde_attributes.contains(&SerdeAttribute::Skip) {
+				"-".to_string()
+			} else if property.is_optional {
+				format!("{},omitempty", property.name)
+			} else {
+				property.name.clone()
+			};
+			let type_tokens = if property.is_optional { format!("*{type_tokens}") } else { type_tokens };
+
+			writeln!(fields, "\t{field_name} {type_tokens} `json:\"{tag}\"`").unwrap();
+		}
+
+		let mut item = get_comment(comment);
+		write!(item, "type {context_name} struct {{\n{fields}}}\n").unwrap();
+
+		self.add_item(context_name, item);
+
+		Ok(())
+	}
+}
+
+/// Go has no namespaces, so namespace-qualified names are joined. `UI.Button` becomes `UIButton`
+fn get_type_name(name: &str) -> String {
+	name.replace('.', "")
+}
+
+fn get_comment(comment: Option<&str>) -> String {
+	let mut output = String::new();
+	push_comment(&mut output, comment, "");
+
+	output
+}
+
+fn push_comment(output: &mut String, comment: Option<&str>, indent: &str) {
+	let comment = match comment {
+		Some(comment) if !comment.trim().is_empty() => comment,
+		_ => return,
+	};
+
+	for line in comment.lines() {
+		output.push_str(indent);
+		output.push_str("//");
+
+		if !line.is_empty() {
+			output.push(' ');
+			output.push_str(line);
+		}
+
+		output.push('\n');
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::collect::collect_source;
+
+	const EXPECTED_ITEMS: &str = "
+type Component struct {
+	Variant ComponentVariant
+}
+
+// ComponentVariant is implemented by every variant of Component
+type ComponentVariant interface {
+	isComponentVariant()
+}
+
+// A clickable button
+type ComponentButton struct {
+	Def Button
+}
+
+func (ComponentButton) isComponentVariant() {}
+
+func (value Component) MarshalJSON() ([]byte, error) {
+	switch variant := value.Variant.(type) {
+	case ComponentButton:
+		return marshalTaggedVariant(\"Button\", variant.Def)
+	}
+
+	return nil, fmt.Errorf(\"%T is not a variant of Component\", value.Variant)
+}
+
+func (value *Component) UnmarshalJSON(data []byte) error {
+	var tagged taggedVariant
+	if err := json.Unmarshal(data, &tagged); err != nil {
+		return err
+	}
+
+	switch tagged.Type {
+	case \"Button\":
+		var variant ComponentButton
+		if err := json.Unmarshal(tagged.Def, &variant.Def); err != nil {
+			return err
+		}
+		value.Variant = variant
+	default:
+		return fmt.Errorf(\"%q is not a variant of Component\", tagged.Type)
+	}
+
+	return nil
+}
+
+type ButtonSize string
+
+const (
+	ButtonSizeSmall ButtonSize = \"small\"
+	ButtonSizeLarge ButtonSize = \"large\"
+)
+
+// A clickable button
+type Button struct {
+	Label string `json:\"label\"`
+	Size *ButtonSize `json:\"size,omitempty\"`
+	Icon *Icon `json:\"icon,omitempty\"`
+}
+
+type Icon struct {
+	Name string `json:\"name\"`
+}
+";
+
+	#[tokio::test]
+	async fn components_and_their_types_are_generated_after_the_prelude() {
+		let collection = collect_source("/**\n * @feature_component_index\n */\nexport interface Component {\n\tid: number\n}\n\n/**\n * A clickable button\n * @component\n */\nexport interface Button {\n\tlabel: string\n\tsize?: 'small' | 'large'\n\ticon?: Icon\n}\n\nexport interface Icon {\n\tname: string\n}\n").await;
+		let mut gen = GoGen::new(&collection).unwrap();
+		gen.gen().unwrap();
+
+		let output = gen.get_output();
+		let items = output.strip_prefix(&format!("package {PACKAGE_NAME}\n\n{PRELUDE}")).unwrap();
+
+		assert_eq!(items, EXPECTED_ITEMS);
+	}
+}
@@ -4,15 +4,42 @@ use log::debug;
 use prettyplease::unparse;
 use proc_macro2::TokenStream;
 use quote::{format_ident, quote, ToTokens};
-use std::{collections::HashSet, iter};
+use std::{
+	collections::{BTreeMap, BTreeSet, HashSet},
+	iter, mem,
+	path::PathBuf,
+};
 use syn::parse2;
 
 use crate::{
 	collect::Collection,
-	convert::{EnumProperty, Kind, ObjectProperty},
+	convert::{EnumProperty, EnumValue, Kind, ObjectProperty, SerdeAttribute, ValueEnumVariant},
 	diagnostic::Diagnostic,
 };
 
+/// The name of the file that generated bindings are written to when they are meant to be `include!`d into a hand-owned module
+pub const INCLUDE_FILE_NAME: &str = "generated.rs";
+
+/// The file that shared types are generated into when bindings are split. See `RustGen::with_split_files`
+const SHARED_TYPES_MODULE_NAME: &str = "types";
+
+/// The initial contents of a hand-owned module that `include!`s the generated bindings. Generated items are declared directly in the
+/// including module, so impls for them can be written alongside the `include!`.
+pub fn get_include_scaffold() -> String {
+	format!(
+		"\
+// This module is owned by you, and will never be overwritten. The generated bindings are included below, so their types can be
+// extended here with trait impls and inherent methods, for example:
+//
+// impl MyComponent {{
+//     pub fn my_helper(&self) {{}}
+// }}
+
+include!(\"{INCLUDE_FILE_NAME}\");
+"
+	)
+}
+
 struct GetConstructorInfoParams<'a> {
 	struct_name: &'a str,
 	argument_prefix: Option<&'a str>,
@@ -44,6 +71,17 @@ pub struct RustGen<'a> {
 	names_generated: HashSet<String>,
 	index_name: &'a str,
 	tokens: TokenStream,
+	/// The namespace that items are currently being generated into. See `Collection::enable_namespaces`
+	current_module: Option<String>,
+	/// The items of each namespace, keyed by the qualified name of the namespace
+	module_tokens: BTreeMap<String, TokenStream>,
+	/// See `RustGen::with_split_files`
+	split_files: bool,
+	/// The component module that items are currently being generated into, if bindings are being split
+	current_file: Option<String>,
+	/// The items of each component module, keyed by the name of the module
+	file_tokens: BTreeMap<String, TokenStream>,
+	postprocess: Option<Box<dyn Fn(String) -> String>>,
 }
 
 impl RustGen<'_> {
@@ -57,15 +95,47 @@ impl RustGen<'_> {
 			index_name,
 			names_generated: HashSet::new(),
 			tokens: TokenStream::new(),
+			current_module: None,
+			module_tokens: BTreeMap::new(),
+			split_files: false,
+			current_file: None,
+			file_tokens: BTreeMap::new(),
+			postprocess: None,
 		})
 	}
 
+	/// Generate each component, along with the types that are declared inline in it, into its own module, and every other type into
+	/// a shared `types` module. Use `RustGen::get_split_output` to get the files. Namespaced items stay in the shared module.
+	pub fn with_split_files(mut self) -> Self {
+		self.split_files = true;
+
+		self
+	}
+
+	/// Transform the formatted output before it is returned from `RustGen::get_output`. Useful for adding license banners,
+	/// `#![allow(...)]` headers, or other house style to the generated code.
+	pub fn with_postprocess(mut self, postprocess: Box<dyn Fn(String) -> String>) -> Self {
+		self.postprocess = Some(postprocess);
+
+		self
+	}
+
 	pub fn gen(&mut self) -> Result<()> {
 		self.gen_index();
 
+		let component_names = self.collection.get_component_info().into_iter().map(|(name, _)| name).collect::<HashSet<_>>();
+
 		for def in self.collection.get_kinds() {
 			debug!("Generating {}", def.name);
-			let comment = def.comment.unwrap_or("");
+			let comment = get_doc_tokens(def.comment);
+			let (namespace, local_name) = split_qualified_name(def.name);
+
+			self.current_module = namespace.map(|namespace| namespace.to_string());
+			self.current_file = if self.split_files && component_names.contains(def.name) {
+				Some(get_component_module_name(local_name))
+			} else {
+				None
+			};
 
 			match def.kind {
 				Kind::Dynamic
@@ -77,44 +147,77 @@ impl RustGen<'_> {
 				| Kind::EventKey { .. }
 				| Kind::Ref { .. }
 				| Kind::List { .. }
+				| Kind::Map { .. }
 				| Kind::Tuple { .. } => {
-					let name = format_ident!("{}", &def.name);
+					let name = format_ident!("{}", local_name);
 
-					if !self.has_item(&def.name) {
-						let anon_item = self.gen_kind(def.name, None, def.kind, KindContext::Type)?;
+					if !self.has_item(local_name) {
+						let anon_item = self.gen_kind(local_name, None, def.kind, KindContext::Type)?;
 						let item = quote! {
-							#[doc = #comment]
+							#comment
 							pub type #name = #anon_item;
 						};
 
-						self.add_item(&def.name, item);
+						self.add_item(local_name, item);
 					}
 				}
-				Kind::StringEnum { .. } | Kind::KeyedEnum { .. } | Kind::Object { .. } => {
-					self.gen_kind(def.name, def.comment, def.kind, KindContext::Type)?;
+				Kind::StringEnum { .. } | Kind::ValueEnum { .. } | Kind::KeyedEnum { .. } | Kind::Object { .. } => {
+					self.gen_kind(local_name, def.comment, def.kind, KindContext::Type)?;
 				}
 			};
 		}
 
+		self.current_module = None;
+		self.current_file = None;
+		self.gen_modules();
+
 		Ok(())
 	}
 
 	pub fn get_output(self) -> String {
-		let text = self.tokens.to_string();
-		let file = match parse2(self.tokens) {
-			Ok(file) => file,
-			Err(_) => {
-				Diagnostic::start("Invalid rust code was generated. This is a bug.")
-					.shift()
-					.text("Continuing on with invalid code so that it can be debugged")
-					.build()
-					.print_error();
-
-				return text;
-			}
+		let output = format_tokens(self.tokens);
+
+		match self.postprocess {
+			Some(postprocess) => postprocess(output),
+			None => output,
+		}
+	}
+
+	/// Get the files of bindings that were generated with `RustGen::with_split_files`, keyed by their paths relative to the bindings
+	/// directory. The `mod.rs` re-exports every module, so items can be imported from the root as if the bindings were one file.
+	pub fn get_split_output(self) -> BTreeMap<PathBuf, String> {
+		let postprocess = |output: String| match &self.postprocess {
+			Some(postprocess) => postprocess(output),
+			None => output,
+		};
+		let prelude = quote! {
+			#[allow(unused_imports)]
+			use super::*;
+		};
+
+		let module_names = iter::once(SHARED_TYPES_MODULE_NAME).chain(self.file_tokens.keys().map(|name| name.as_str()));
+		let module_idents = module_names.map(|name| format_ident!("{name}")).collect::<Vec<_>>();
+		let root_tokens = quote! {
+			#(
+				mod #module_idents;
+				pub use #module_idents::*;
+			)*
 		};
 
-		unparse(&file)
+		let shared_tokens = &self.tokens;
+
+		let mut files = BTreeMap::new();
+		files.insert(PathBuf::from("mod.rs"), postprocess(format_tokens(root_tokens)));
+		files.insert(
+			PathBuf::from(format!("{SHARED_TYPES_MODULE_NAME}.rs")),
+			postprocess(format_tokens(quote! { #prelude #shared_tokens })),
+		);
+
+		for (name, tokens) in &self.file_tokens {
+			files.insert(PathBuf::from(format!("{name}.rs")), postprocess(format_tokens(quote! { #prelude #tokens })));
+		}
+
+		files
 	}
 
 	fn gen_index(&mut self) {
@@ -122,20 +225,23 @@ impl RustGen<'_> {
 		let mut inner_tokens = TokenStream::new();
 
 		for (name, _) in self.collection.get_component_info() {
-			let comment = self.collection.get_comment(name).map(|comment| quote! { #[doc = #comment] });
-			let name_ident = format_ident!("{name}");
+			let comment = get_doc_tokens(self.collection.get_comment(name));
+			let variant_ident = format_ident!("{}", name.replace('.', ""));
+			let type_path = get_path_tokens(name);
+			let rename = name.contains('.').then(|| quote! { #[serde(rename = #name)] });
 
 			inner_tokens.extend(iter::once(quote! {
 				#comment
-				#name_ident(Box<#name_ident>),
+				#rename
+				#variant_ident(Box<#type_path>),
 			}));
 
 			self.tokens.extend(iter::once(quote! {
-				impl objection::IntoComponentIndex for #name_ident {
+				impl objection::IntoComponentIndex for #type_path {
 					type Index = #index_ident;
 
 					fn into_index(self) -> #index_ident {
-						#index_ident::#name_ident(Box::new(self))
+						#index_ident::#variant_ident(Box::new(self))
 					}
 				}
 			}));
@@ -200,7 +306,7 @@ impl RustGen<'_> {
 				}
 			}
 			Kind::Ref { name } => {
-				let inner = format_ident!("{}", name);
+				let inner = get_path_tokens(name);
 
 				match context {
 					KindContext::Type | KindContext::ConstructorKey {} => inner.into_token_stream(),
@@ -231,6 +337,16 @@ impl RustGen<'_> {
 					KindContext::Value { existing_value_expression } => existing_value_expression,
 				}
 			}
+			Kind::Map { key, value } => {
+				let key_tokens = self.gen_kind(&format!("{context_name}Key"), None, &key, KindContext::Type)?;
+				let value_tokens = self.gen_kind(&format!("{context_name}Value"), None, &value, KindContext::Type)?;
+
+				match context {
+					KindContext::Type | KindContext::CallSignature => quote! { std::collections::HashMap<#key_tokens, #value_tokens> },
+					KindContext::ConstructorKey => bail!("A map cannot be constructed via a key"),
+					KindContext::Value { existing_value_expression } => existing_value_expression,
+				}
+			}
 			Kind::Tuple { items } => {
 				let inner = items
 					.iter()
@@ -248,13 +364,22 @@ impl RustGen<'_> {
 				let name_ident = format_ident!("{context_name}");
 
 				if !self.has_item(context_name) {
-					let variant_idents = variants.iter().map(|item| format_ident!("{item}"));
-					let comment_str = comment.unwrap_or_default();
+					let variant_idents = variants
+						.iter()
+						.map(|variant| match get_string_variant_name(variant) {
+							Some(name) => Ok(format_ident!("{name}")),
+							None => Err(anyhow!("String literal {variant:?} cannot be named as an enum variant")),
+						})
+						.collect::<Result<Vec<_>>>()?;
+					let comment_tokens = get_doc_tokens(comment);
 					let item = quote! {
-						#[doc = #comment_str]
-						#[derive(Debug, serde::Serialize, serde::Deserialize)]
+						#comment_tokens
+						#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 						pub enum #name_ident {
-							#( #variant_idents, )*
+							#(
+								#[serde(rename = #variants)]
+								#variant_idents,
+							)*
 						}
 					};
 
@@ -267,6 +392,19 @@ impl RustGen<'_> {
 					KindContext::Value { existing_value_expression } => existing_value_expression,
 				}
 			}
+			Kind::ValueEnum { variants } => {
+				let name_ident = format_ident!("{context_name}");
+
+				if !self.has_item(context_name) {
+					self.gen_value_enum(context_name, comment, &variants);
+				}
+
+				match context {
+					KindContext::Type | KindContext::CallSignature => quote! { #name_ident },
+					KindContext::ConstructorKey => bail!("An enum cannot be constructed via a key"),
+					KindContext::Value { existing_value_expression } => existing_value_expression,
+				}
+			}
 			Kind::KeyedEnum { variants } => {
 				let name_ident = format_ident!("{context_name}");
 
@@ -297,11 +435,31 @@ impl RustGen<'_> {
 	}
 
 	fn has_item(&self, name: &str) -> bool {
-		self.names_generated.contains(name)
+		self.names_generated.contains(&self.get_qualified_name(name))
 	}
 
 	fn add_item(&mut self, name: &str, tokens: TokenStream) {
-		self.names_generated.insert(name.to_string());
+		self.names_generated.insert(self.get_qualified_name(name));
+
+		match (&self.current_module, &self.current_file) {
+			(Some(module), _) => self.module_tokens.entry(module.clone()).or_default().extend(iter::once(tokens)),
+			(None, Some(file)) => self.file_tokens.entry(file.clone()).or_default().extend(iter::once(tokens)),
+			(None, None) => self.tokens.extend(iter::once(tokens)),
+		}
+	}
+
+	fn get_qualified_name(&self, name: &str) -> String {
+		match &self.current_module {
+			Some(module) => format!("{module}.{name}"),
+			None => name.to_string(),
+		}
+	}
+
+	/// Wrap the items of each namespace in nested modules
+	fn gen_modules(&mut self) {
+		let module_tokens = mem::take(&mut self.module_tokens);
+		let tokens = get_module_tree_tokens(None, &module_tokens);
+
 		self.tokens.extend(iter::once(tokens));
 	}
 
@@ -321,12 +479,17 @@ impl RustGen<'_> {
 				KindContext::Type,
 			)?;
 
-			variant_def_tokens.push(quote! { #name_ident(#kind_tokens) });
+			let comment_tokens = get_doc_tokens(variant.comment.as_deref());
+
+			variant_def_tokens.push(quote! {
+				#comment_tokens
+				#name_ident(#kind_tokens)
+			});
 		}
 
-		let comment_str = comment.unwrap_or_default();
+		let comment_tokens = get_doc_tokens(comment);
 		let item = quote! {
-			#[doc = #comment_str]
+			#comment_tokens
 			#[derive(Debug, serde::Serialize, serde::Deserialize)]
 			#[serde(tag = "type", content = "def")]
 			pub enum #name_ident {
@@ -344,6 +507,73 @@ impl RustGen<'_> {
 		Ok(())
 	}
 
+	/// Variants are named after the enum's members, but are sent as the members' values. String values are handled with serde
+	/// renames, but serde can't derive numeric representations, so those get handwritten impls.
+	fn gen_value_enum(&mut self, context_name: &str, comment: Option<&str>, variants: &[ValueEnumVariant]) {
+		let name_ident = format_ident!("{context_name}");
+		let comment_tokens = get_doc_tokens(comment);
+		let variant_idents = variants.iter().map(|variant| format_ident!("{}", variant.name)).collect::<Vec<_>>();
+		let variant_comments = variants.iter().map(|variant| get_doc_tokens(variant.comment.as_deref())).collect::<Vec<_>>();
+
+		let numbers = variants
+			.iter()
+			.filter_map(|variant| match variant.value {
+				EnumValue::Number(number) => Some(number),
+				EnumValue::String(_) => None,
+			})
+			.collect::<Vec<_>>();
+
+		let item = if numbers.is_empty() {
+			let values = variants.iter().map(|variant| match &variant.value {
+				EnumValue::String(value) => value.clone(),
+				EnumValue::Number(_) => unreachable!(),
+			});
+
+			quote! {
+				#comment_tokens
+				#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+				pub enum #name_ident {
+					#(
+						#variant_comments
+						#[serde(rename = #values)]
+						#variant_idents,
+					)*
+				}
+			}
+		} else {
+			quote! {
+				#comment_tokens
+				#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+				pub enum #name_ident {
+					#(
+						#variant_comments
+						#variant_idents,
+					)*
+				}
+
+				impl serde::Serialize for #name_ident {
+					fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+						match self {
+							#( Self::#variant_idents => serializer.serialize_f64(#numbers), )*
+						}
+					}
+				}
+
+				impl<'de> serde::Deserialize<'de> for #name_ident {
+					fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+						let value = f64::deserialize(deserializer)?;
+
+						#( if value == #numbers { return Ok(Self::#variant_idents); } )*
+
+						Err(serde::de::Error::custom(format!("{value} is not a value of {}", stringify!(#name_ident))))
+					}
+				}
+			}
+		};
+
+		self.add_item(context_name, item);
+	}
+
 	fn gen_struct(&mut self, context_name: &str, comment: Option<&str>, properties: &[ObjectProperty]) -> Result<()> {
 		let name_ident = format_ident!("{context_name}");
 		let mut property_def_tokens = TokenStream::new();
@@ -369,8 +599,10 @@ impl RustGen<'_> {
 						full_comment.push_str(&format!("\n\n{comment}"));
 					}
 
+					let comment_tokens = get_doc_tokens(Some(&full_comment));
+
 					quote! {
-						#[doc = #full_comment]
+						#comment_tokens
 						pub fn new(#argument_tokens) -> #name_ident {
 							#name_ident { #construction_body_tokens }
 						}
@@ -382,7 +614,7 @@ impl RustGen<'_> {
 		for property in properties {
 			let snake_property_name = property.name.to_snake_case();
 			let snake_property_ident = format_ident!("{}", &snake_property_name);
-			let comment_tokens = property.comment.as_deref().map(|text| quote! { #[doc = #text] });
+			let comment_tokens = get_doc_tokens(property.comment.as_deref());
 			let (resolved_kind, resolved_name) = self.collection.resolve_kind(&property.kind);
 			let property_context_name = get_struct_property_context_name(context_name, &property.name);
 
@@ -461,7 +693,7 @@ impl RustGen<'_> {
 						let full_name_ident = format_ident!("{snake_property_ident}_full");
 						let wrapped_construction_tokens =
 							optional_value_if(property.is_optional, quote! { #kind_constructor_key_tokens { #construction_body_tokens } });
-						let comment_tokens = comment.map(|comment| quote! { #[doc = #comment] });
+						let comment_tokens = get_doc_tokens(comment.as_deref());
 
 						Ok(quote! {
 							#comment_tokens
@@ -514,7 +746,7 @@ impl RustGen<'_> {
 							 }|
 							 -> Result<_> {
 								let key = self.gen_kind(&property_context_name, None, of, KindContext::ConstructorKey)?;
-								let comment_tokens = comment.map(|comment| quote! { #[doc = #comment] });
+								let comment_tokens = get_doc_tokens(comment.as_deref());
 
 								let setter = if property.is_optional {
 									quote! {
@@ -585,15 +817,37 @@ impl RustGen<'_> {
 				default_method
 			}));
 
+			let flatten_tokens = property.is_flattened.then(|| quote! { #[serde(flatten)] });
+			let serde_tokens = get_serde_attribute_tokens(&property.serde_attributes);
+			let optional_tokens = if property.is_optional {
+				get_optional_serde_tokens(&property.serde_attributes)
+			} else {
+				None
+			};
 			let def_tokens = quote! {
 				#comment_tokens
+				#flatten_tokens
+				#serde_tokens
+				#optional_tokens
 				pub #snake_property_ident: #kind_type_tokens,
 			};
 
 			property_def_tokens.extend(iter::once(def_tokens));
 		}
 
-		let comment_tokens = comment.map(|text| quote! { #[doc = #text] });
+		let comment_tokens = get_doc_tokens(comment);
+
+		// without required properties, the constructor takes no arguments, so a component can be mounted with only its defaults,
+		// such as with `Button::default().into_index()`
+		let default_tokens = properties.iter().all(|property| property.is_optional || property.is_flattened).then(|| {
+			quote! {
+				impl Default for #name_ident {
+					fn default() -> #name_ident {
+						#name_ident::new()
+					}
+				}
+			}
+		});
 
 		let item = quote! {
 			#comment_tokens
@@ -607,6 +861,8 @@ impl RustGen<'_> {
 
 				#methods
 			}
+
+			#default_tokens
 		};
 
 		self.add_item(context_name, item);
@@ -637,6 +893,12 @@ impl RustGen<'_> {
 				continue;
 			}
 
+			if property.is_flattened {
+				construction_body_tokens.extend(iter::once(quote! { #property_name_ident: Default::default(), }));
+
+				continue;
+			}
+
 			if arguments_so_far == limit {
 				return Ok(None);
 			}
@@ -678,16 +940,160 @@ impl RustGen<'_> {
 	}
 }
 
+/// Format generated tokens as a file. If they aren't valid rust, they are returned unformatted, so that they can be debugged
+fn format_tokens(tokens: TokenStream) -> String {
+	let text = tokens.to_string();
+
+	match parse2(tokens) {
+		Ok(file) => unparse(&file),
+		Err(_) => {
+			Diagnostic::start("Invalid rust code was generated. This is a bug.")
+				.shift()
+				.text("Continuing on with invalid code so that it can be debugged")
+				.build()
+				.print_error();
+
+			text
+		}
+	}
+}
+
+/// The module that a component is generated into when bindings are split. A component can't take the name of the shared module
+fn get_component_module_name(component_name: &str) -> String {
+	let name = component_name.to_snake_case();
+
+	if name == SHARED_TYPES_MODULE_NAME {
+		format!("{name}_component")
+	} else {
+		name
+	}
+}
+
+/// Split a namespace-qualified name, such as `UI.Button`, into its namespace and its local name
+fn split_qualified_name(name: &str) -> (Option<&str>, &str) {
+	match name.rsplit_once('.') {
+		Some((namespace, local_name)) => (Some(namespace), local_name),
+		None => (None, name),
+	}
+}
+
+/// The path to a possibly namespace-qualified type. `UI.Button` becomes `ui::Button`
+fn get_path_tokens(name: &str) -> TokenStream {
+	let (namespace, local_name) = split_qualified_name(name);
+	let module_idents = namespace
+		.into_iter()
+		.flat_map(|namespace| namespace.split('.'))
+		.map(|segment| format_ident!("{}", segment.to_snake_case()));
+	let local_ident = format_ident!("{}", local_name);
+
+	quote! { #( #module_idents:: )* #local_ident }
+}
+
+fn get_module_tree_tokens(parent: Option<&str>, module_tokens: &BTreeMap<String, TokenStream>) -> TokenStream {
+	let children = module_tokens
+		.keys()
+		.filter_map(|name| match parent {
+			Some(parent) => name.strip_prefix(parent).and_then(|rest| rest.strip_prefix('.')),
+			None => Some(name.as_str()),
+		})
+		.filter_map(|rest| rest.split('.').next())
+		.collect::<BTreeSet<_>>();
+
+	children
+		.into_iter()
+		.map(|child| {
+			let qualified_name = match parent {
+				Some(parent) => format!("{parent}.{child}"),
+				None => child.to_string(),
+			};
+			let module_ident = format_ident!("{}", child.to_snake_case());
+			let items = module_tokens.get(&qualified_name);
+			let nested_modules = get_module_tree_tokens(Some(&qualified_name), module_tokens);
+
+			quote! {
+				pub mod #module_ident {
+					#[allow(unused_imports)]
+					use super::*;
+
+					#items
+					#nested_modules
+				}
+			}
+		})
+		.collect()
+}
+
 fn get_struct_property_context_name(struct_context_name: &str, property_name: &str) -> String {
 	// all property names are camel case, but all property names must be pascal case
 	format!("{struct_context_name}{}", property_name.to_pascal_case())
 }
 
+/// The name of the enum variant that a string literal is generated as, which is the literal in pascal case. The literal itself is
+/// kept as the variant's serialized name. `None` if the literal has no letters to start a name with.
+pub fn get_string_variant_name(variant: &str) -> Option<String> {
+	let name = variant.to_pascal_case();
+
+	match name.chars().next() {
+		Some(first) if first.is_alphabetic() && name.chars().all(char::is_alphanumeric) => Some(name),
+		_ => None,
+	}
+}
+
 fn get_keyed_enum_variant_context_name(enum_context_name: &str, variant_name: &str) -> String {
 	// all variant names must be pascal case, so nothing to do here
 	format!("{enum_context_name}{variant_name}")
 }
 
+fn get_serde_attribute_tokens(attributes: &[SerdeAttribute]) -> Option<TokenStream> {
+	if attributes.is_empty() {
+		return None;
+	}
+
+	let attribute_tokens = attributes.iter().map(|attribute| match attribute {
+		SerdeAttribute::Flatten => quote! { flatten },
+		SerdeAttribute::Skip => quote! { skip },
+		SerdeAttribute::Default => quote! { default },
+		SerdeAttribute::With(path) => quote! { with = #path },
+	});
+
+	Some(quote! { #[serde(#(#attribute_tokens),*)] })
+}
+
+/// Absent optional fields are left out of the serialized struct, which is how typescript reads an optional property. Attributes
+/// that were already requested with `@serde` tags are not repeated, because serde rejects duplicates.
+fn get_optional_serde_tokens(attributes: &[SerdeAttribute]) -> Option<TokenStream> {
+	let mut attribute_tokens = Vec::new();
+
+	if !attributes.contains(&SerdeAttribute::Default) {
+		attribute_tokens.push(quote! { default });
+	}
+
+	if !attributes.contains(&SerdeAttribute::Skip) {
+		attribute_tokens.push(quote! { skip_serializing_if = "Option::is_none" });
+	}
+
+	if attribute_tokens.is_empty() {
+		return None;
+	}
+
+	Some(quote! { #[serde(#(#attribute_tokens),*)] })
+}
+
+/// Doc attributes for a jsdoc comment, one per line, so that multi-line comments are written as consecutive `///` lines. Line comments
+/// end at the end of the line, so the comment can contain anything, including `*/`.
+fn get_doc_tokens(comment: Option<&str>) -> TokenStream {
+	let lines = comment.into_iter().flat_map(|comment| comment.trim_end().lines()).map(|line| {
+		// `///` is followed by a space by convention, which is not part of the line
+		if line.is_empty() {
+			String::new()
+		} else {
+			format!(" {line}")
+		}
+	});
+
+	quote! { #( #[doc = #lines] )* }
+}
+
 fn optional_type_if(condition: bool, inner: TokenStream) -> TokenStream {
 	if condition {
 		quote! { Option<#inner> }
@@ -703,3 +1109,192 @@ fn optional_value_if(condition: bool, inner: TokenStream) -> TokenStream {
 		inner
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		collect::{collect_source, collect_virtual_modules_into, Collection},
+		module_loader::VirtualModule,
+	};
+	use url::Url;
+
+	const COMPONENT_INDEX: &str = "/**\n * @feature_component_index\n */\nexport interface Component {\n\tid: number\n}\n";
+
+	/// Generate the bindings of `source`, with all whitespace collapsed to single spaces
+	async fn gen_source(source: &str) -> String {
+		let collection = collect_source(&format!("{COMPONENT_INDEX}{source}")).await;
+		let mut gen = RustGen::new(&collection).unwrap();
+		gen.gen().unwrap();
+
+		gen.get_output().split_whitespace().collect::<Vec<_>>().join(" ")
+	}
+
+	#[tokio::test]
+	async fn the_output_can_be_included_into_another_module() {
+		let collection = collect_source(&format!(
+			"{COMPONENT_INDEX}/**\n * @component\n */\nexport interface Button {{\n\tlabel: string\n}}\n"
+		))
+		.await;
+		let mut gen = RustGen::new(&collection).unwrap();
+		gen.gen().unwrap();
+
+		let file = syn::parse_file(&gen.get_output()).unwrap();
+
+		// `include!`d files can't have inner attributes, and the including module is the only wrapper
+		assert!(file.attrs.is_empty());
+		assert!(!file.items.is_empty());
+		assert!(file.items.iter().all(|item| !matches!(item, syn::Item::Mod(_))));
+	}
+
+	#[tokio::test]
+	async fn index_signatures_become_a_flattened_map_that_defaults_to_empty() {
+		let output =
+			gen_source("export interface Scores {\n\t[name: string]: number\n\ttotal: number\n}\nexport interface Tags {\n\t[name: string]: string\n}\n").await;
+
+		assert!(output.contains("#[serde(flatten)] pub extra: std::collections::HashMap<String, f64>,"));
+		assert!(output.contains("#[serde(flatten)] pub extra: std::collections::HashMap<String, String>,"));
+		assert!(output.contains("pub fn new(total: impl Into<f64>) -> Scores {"));
+		assert!(output.contains("extra: Default::default()"));
+		assert!(output.contains("impl Default for Tags { fn default() -> Tags { Tags::new() } }"));
+		assert!(!output.contains("impl Default for Scores"));
+	}
+
+	/// What the bindings declare for `type Variant = 'primary' | 'secondary-action' | 'danger_zone'`
+	#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+	enum Variant {
+		#[serde(rename = "primary")]
+		Primary,
+		#[serde(rename = "secondary-action")]
+		SecondaryAction,
+		#[serde(rename = "danger_zone")]
+		DangerZone,
+	}
+
+	#[tokio::test]
+	async fn string_literal_unions_round_trip_through_serde_as_the_original_strings() {
+		let output = gen_source("export type Variant = 'primary' | 'secondary-action' | 'danger_zone'\n").await;
+
+		assert!(output.contains(
+			r#"pub enum Variant { #[serde(rename = "primary")] Primary, #[serde(rename = "secondary-action")] SecondaryAction, #[serde(rename = "danger_zone")] DangerZone, }"#
+		));
+
+		for (variant, literal) in [
+			(Variant::Primary, "primary"),
+			(Variant::SecondaryAction, "secondary-action"),
+			(Variant::DangerZone, "danger_zone"),
+		] {
+			assert_eq!(serde_json::to_value(&variant).unwrap(), literal);
+			assert_eq!(serde_json::from_value::<Variant>(literal.into()).unwrap(), variant);
+		}
+	}
+
+	#[tokio::test]
+	async fn enum_variants_are_named_by_their_members_but_serialized_by_their_values() {
+		let output = gen_source(
+			"export enum Color {\n\tRed = 'red',\n\tDarkBlue = 'dark-blue',\n}\nexport enum Level {\n\tLow = 1,\n\tHigh = 10,\n}\nexport type Size = 'small' | 'extra-large'\n",
+		)
+		.await;
+
+		assert!(output.contains(r#"pub enum Color { #[serde(rename = "red")] Red, #[serde(rename = "dark-blue")] DarkBlue, }"#));
+		assert!(output.contains("Self::Low => serializer.serialize_f64(1f64), Self::High => serializer.serialize_f64(10f64),"));
+		assert!(output.contains(r#"pub enum Size { #[serde(rename = "small")] Small, #[serde(rename = "extra-large")] ExtraLarge, }"#));
+	}
+
+	#[tokio::test]
+	async fn records_become_maps_keyed_by_strings_or_string_literals() {
+		let output = gen_source(
+			"export interface Foo {\n\tname: string\n}\nexport type Size = 'small' | 'large'\nexport interface Layout {\n\tnested: Record<string, \
+			 Record<string, Foo>>\n\tbySize: Record<Size, number>\n\tbyEdge: Record<'top' | 'bottom', string>\n}\n",
+		)
+		.await;
+
+		assert!(output.contains("pub nested: std::collections::HashMap<String, std::collections::HashMap<String, Foo>>,"));
+		assert!(output.contains("pub by_size: std::collections::HashMap<Size, f64>,"));
+		assert!(output.contains("pub by_edge: std::collections::HashMap<LayoutByEdgeKey, String>,"));
+		assert!(output.contains(r#"pub enum LayoutByEdgeKey { #[serde(rename = "top")] Top, #[serde(rename = "bottom")] Bottom, }"#));
+	}
+
+	#[tokio::test]
+	async fn components_without_required_properties_can_be_mounted_with_their_defaults() {
+		let output = gen_source(
+			"/**\n * @component\n */\nexport interface Button {\n\tlabel?: string\n\tdisabled?: boolean\n}\n/**\n * @component\n */\nexport interface Link \
+			 {\n\thref: string\n}\n",
+		)
+		.await;
+
+		assert!(output.contains("pub fn new() -> Button { Button { label: None, disabled: None } }"));
+		assert!(output.contains("impl Default for Button { fn default() -> Button { Button::new() } }"));
+		assert!(output.contains("impl objection::IntoComponentIndex for Button"));
+		assert!(!output.contains("impl Default for Link"));
+
+		// absent fields are left out, so `Button::default().into_index()` is sent as `{"type":"Button","def":{}}`
+		assert!(output.contains(r#"#[serde(default, skip_serializing_if = "Option::is_none")] pub label: Option<String>,"#));
+		assert!(output.contains(r#"#[serde(default, skip_serializing_if = "Option::is_none")] pub disabled: Option<bool>,"#));
+	}
+
+	#[tokio::test]
+	async fn tuples_keep_the_order_of_their_items() {
+		let output =
+			gen_source("export interface Foo {\n\tname: string\n}\nexport interface Marker {\n\tentry: [string, number, Foo]\n\tpoint?: [number, number]\n}\n")
+				.await;
+
+		assert!(output.contains("pub entry: (String, f64, Foo),"));
+		assert!(output.contains("pub point: Option<(f64, f64)>,"));
+		assert!(output.contains("pub fn new(entry: (String, f64, Foo)) -> Marker {"));
+	}
+
+	#[tokio::test]
+	async fn optional_unions_are_wrapped_in_a_single_option() {
+		let output = gen_source(
+			"export interface Props {\n\ttitle?: string | null\n\tsize: 'small' | 'large' | undefined\n\t/**\n\t * @serde default\n\t */\n\tcount?: number\n}\n",
+		)
+		.await;
+
+		assert!(output.contains(r#"#[serde(default, skip_serializing_if = "Option::is_none")] pub title: Option<String>,"#));
+		assert!(output.contains(r#"#[serde(default, skip_serializing_if = "Option::is_none")] pub size: Option<PropsSize>,"#));
+		assert!(output.contains(r#"#[serde(default)] #[serde(skip_serializing_if = "Option::is_none")] pub count: Option<f64>,"#));
+		assert!(!output.contains("Option<Option<"));
+	}
+
+	#[tokio::test]
+	async fn serde_tags_reach_the_generated_fields_next_to_the_camel_case_rename() {
+		let output = gen_source(
+			"export interface Extra {\n\tid: number\n}\nexport interface Props {\n\t/**\n\t * @serde flatten\n\t */\n\textraFields: Extra\n\t/**\n\t * @serde skip default with=my::module\n\t */\n\tcreatedAt: string\n}\n",
+		)
+		.await;
+
+		assert!(output.contains(r#"#[serde(rename_all = "camelCase")] pub struct Props {"#));
+		assert!(output.contains("#[serde(flatten)] pub extra_fields: Extra,"));
+		assert!(output.contains(r#"#[serde(skip, default, with = "my::module")] pub created_at: String,"#));
+	}
+
+	#[tokio::test]
+	async fn namespaced_components_are_generated_into_a_submodule() {
+		let mut collection = Collection::default();
+		collection.enable_namespaces();
+
+		let source =
+			format!("{COMPONENT_INDEX}export namespace UI {{\n\t/**\n\t * @component\n\t */\n\texport interface Button {{\n\t\tlabel: string\n\t}}\n}}\n");
+		let collection = collect_virtual_modules_into(
+			collection,
+			&[VirtualModule {
+				specifier: Url::parse("file:///runtime.ts").unwrap(),
+				source,
+				dependencies: Vec::new(),
+			}],
+		)
+		.await;
+
+		assert!(collection.get_component_info().into_iter().any(|(name, _)| name == "UI.Button"));
+
+		let mut gen = RustGen::new(&collection).unwrap();
+		gen.gen().unwrap();
+		let output = gen.get_output().split_whitespace().collect::<Vec<_>>().join(" ");
+
+		assert!(output.contains(r#"#[serde(rename = "UI.Button")] UIButton(Box<ui::Button>),"#));
+		assert!(output.contains("impl objection::IntoComponentIndex for ui::Button {"));
+		assert!(output.contains("pub mod ui {"));
+		assert!(output.contains("pub struct Button { pub label: String, }"));
+	}
+}
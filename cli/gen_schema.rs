@@ -0,0 +1,358 @@
+use serde_json::{json, Map, Value};
+
+use crate::{
+	collect::Collection,
+	convert::{EnumValue, Kind},
+};
+
+/// The JSON Schema dialect that the schema is written in
+const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Generate a single JSON Schema document that describes the whole protocol between the client and the engine. Every kind of the
+/// collection is defined under `$defs`, along with the envelopes of the protocol, which are prefixed with `objection:` so that they
+/// can't collide with the name of a kind. The document itself validates a request, and `#/$defs/objection:Response` validates a
+/// response.
+///
+/// Event and action paths are dynamic, so the data of an event or action is not tied to its key.
+pub fn get_protocol_schema(collection: &Collection) -> Value {
+	let mut defs = Map::new();
+
+	for (name, schema) in get_envelope_schemas() {
+		defs.insert(name.to_string(), schema);
+	}
+
+	for def in collection.get_kinds() {
+		let mut schema = gen_kind_schema(def.kind);
+
+		if let (Some(comment), Value::Object(schema)) = (def.comment, &mut schema) {
+			schema.insert("description".to_string(), json!(comment));
+		}
+
+		defs.insert(def.name.to_string(), schema);
+	}
+
+	json!({
+		"$schema": SCHEMA_DIALECT,
+		"$ref": "#/$defs/objection:Request",
+		"$defs": defs,
+	})
+}
+
+/// The schemas of the request and response envelopes, as they are read and written by `objection::handle_request`
+fn get_envelope_schemas() -> [(&'static str, Value); 6] {
+	[
+		(
+			"objection:EventKey",
+			json!({
+				"type": "object",
+				"properties": {
+					"eventPath": { "type": "array", "items": { "type": "string" } },
+					"debugSymbol": { "type": ["string", "null"] },
+					"version": { "type": "integer", "minimum": 0 },
+					"prefix": { "type": "string" },
+				},
+				"required": ["eventPath"],
+			}),
+		),
+		(
+			"objection:ActionKey",
+			json!({
+				"type": "object",
+				"properties": {
+					"actionPath": { "type": "array", "items": { "type": "string" } },
+					"debugSymbol": { "type": ["string", "null"] },
+				},
+				"required": ["actionPath"],
+			}),
+		),
+		(
+			"objection:Event",
+			json!({
+				"type": "object",
+				"properties": {
+					"key": { "$ref": "#/$defs/objection:EventKey" },
+					"data": {},
+				},
+				"required": ["key", "data"],
+			}),
+		),
+		(
+			"objection:Action",
+			json!({
+				"type": "object",
+				"properties": {
+					"key": { "$ref": "#/$defs/objection:ActionKey" },
+					"data": {},
+					"priority": { "type": "integer", "minimum": 0, "maximum": 255 },
+				},
+				"required": ["key", "data"],
+			}),
+		),
+		(
+			"objection:Request",
+			json!({
+				"type": "object",
+				"properties": {
+					"sessionId": { "type": "string" },
+					"events": { "type": "array", "items": { "$ref": "#/$defs/objection:Event" } },
+					"internPathPrefixes": { "type": "boolean" },
+				},
+				"required": ["sessionId", "events"],
+			}),
+		),
+		(
+			"objection:Response",
+			json!({ "type": "array", "items": { "$ref": "#/$defs/objection:Action" } }),
+		),
+	]
+}
+
+fn gen_kind_schema(kind: &Kind) -> Value {
+	match kind {
+		Kind::Dynamic => json!({}),
+		Kind::String => json!({ "type": "string" }),
+		Kind::Number => json!({ "type": "number" }),
+		Kind::Bool => json!({ "type": "boolean" }),
+		Kind::Null => json!({ "type": "null" }),
+		Kind::ActionKey { .. } => json!({ "$ref": "#/$defs/objection:ActionKey" }),
+		Kind::EventKey { .. } => json!({ "$ref": "#/$defs/objection:EventKey" }),
+		Kind::Ref { name } => json!({ "$ref": format!("#/$defs/{name}") }),
+		Kind::List { of } => json!({ "type": "array", "items": gen_kind_schema(of) }),
+		// keys are always strings on the wire, so only the values are described
+		Kind::Map { value, .. } => json!({ "type": "object", "additionalProperties": gen_kind_schema(value) }),
+		Kind::Tuple { items } => json!({
+			"type": "array",
+			"prefixItems": items.iter().map(gen_kind_schema).collect::<Vec<_>>(),
+			"items": false,
+			"minItems": items.len(),
+		}),
+		Kind::StringEnum { variants } => json!({ "enum": variants }),
+		Kind::ValueEnum { variants } => json!({
+			"enum": variants
+				.iter()
+				.map(|variant| match &variant.value {
+					EnumValue::String(value) => json!(value),
+					EnumValue::Number(value) => json!(value),
+				})
+				.collect::<Vec<_>>(),
+		}),
+		Kind::KeyedEnum { variants } => json!({
+			"oneOf": variants
+				.iter()
+				.map(|variant| {
+					let mut schema = json!({
+						"type": "object",
+						"properties": {
+							"type": { "const": variant.name },
+							"def": gen_kind_schema(&variant.kind),
+						},
+						"required": ["type", "def"],
+					});
+
+					if let Some(comment) = &variant.comment {
+						schema["description"] = json!(comment);
+					}
+
+					schema
+				})
+				.collect::<Vec<_>>(),
+		}),
+		Kind::Object { properties } => {
+			let mut schema_properties = Map::new();
+			let mut required = Vec::new();
+			let mut additional_properties = None;
+
+			for property in properties {
+				if let (Kind::Map { value, .. }, true) = (&property.kind, property.is_flattened) {
+					additional_properties = Some(gen_kind_schema(value));
+					continue;
+				}
+
+				let mut schema = gen_kind_schema(&property.kind);
+
+				if let (Some(comment), Value::Object(schema)) = (&property.comment, &mut schema) {
+					schema.insert("description".to_string(), json!(comment));
+				}
+
+				if !property.is_optional {
+					required.push(property.name.clone());
+				}
+
+				schema_properties.insert(property.name.clone(), schema);
+			}
+
+			let mut schema = json!({ "type": "object", "properties": schema_properties, "required": required });
+
+			if let Some(additional_properties) = additional_properties {
+				schema["additionalProperties"] = additional_properties;
+			}
+
+			schema
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::collect::collect_source;
+
+	/// Check `value` against the subset of JSON Schema that `get_protocol_schema` emits
+	fn conforms(root: &Value, schema: &Value, value: &Value) -> bool {
+		let schema = match schema {
+			Value::Bool(allowed) => return *allowed,
+			Value::Object(schema) => schema,
+			_ => panic!("{schema} is not a schema"),
+		};
+
+		if let Some(reference) = schema.get("$ref") {
+			let name = reference.as_str().unwrap().strip_prefix("#/$defs/").unwrap();
+
+			if !conforms(root, &root["$defs"][name], value) {
+				return false;
+			}
+		}
+
+		if let Some(types) = schema.get("type") {
+			let types = match types {
+				Value::Array(types) => types.iter().map(|kind| kind.as_str().unwrap()).collect::<Vec<_>>(),
+				kind => vec![kind.as_str().unwrap()],
+			};
+			let is_type = |kind: &str| match kind {
+				"null" => value.is_null(),
+				"boolean" => value.is_boolean(),
+				"integer" => value.is_u64() || value.is_i64(),
+				"number" => value.is_number(),
+				"string" => value.is_string(),
+				"array" => value.is_array(),
+				"object" => value.is_object(),
+				_ => panic!("{kind} is not a type"),
+			};
+
+			if !types.into_iter().any(is_type) {
+				return false;
+			}
+		}
+
+		if let Some(Value::Array(variants)) = schema.get("enum") {
+			if !variants.contains(value) {
+				return false;
+			}
+		}
+
+		if let Some(constant) = schema.get("const") {
+			if constant != value {
+				return false;
+			}
+		}
+
+		if let Some(Value::Array(variants)) = schema.get("oneOf") {
+			if variants.iter().filter(|variant| conforms(root, variant, value)).count() != 1 {
+				return false;
+			}
+		}
+
+		if let (Some(minimum), Some(number)) = (schema.get("minimum"), value.as_f64()) {
+			if number < minimum.as_f64().unwrap() {
+				return false;
+			}
+		}
+
+		if let (Some(maximum), Some(number)) = (schema.get("maximum"), value.as_f64()) {
+			if number > maximum.as_f64().unwrap() {
+				return false;
+			}
+		}
+
+		if let Value::Object(object) = value {
+			let properties = schema.get("properties").and_then(Value::as_object);
+
+			if let Some(Value::Array(required)) = schema.get("required") {
+				if required.iter().any(|name| !object.contains_key(name.as_str().unwrap())) {
+					return false;
+				}
+			}
+
+			for (name, property) in object {
+				let property_schema = match properties.and_then(|properties| properties.get(name)) {
+					Some(property_schema) => property_schema,
+					None => match schema.get("additionalProperties") {
+						Some(additional_properties) => additional_properties,
+						None => continue,
+					},
+				};
+
+				if !conforms(root, property_schema, property) {
+					return false;
+				}
+			}
+		}
+
+		if let Value::Array(items) = value {
+			let prefix_items = schema.get("prefixItems").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+
+			if let Some(min_items) = schema.get("minItems") {
+				if (items.len() as u64) < min_items.as_u64().unwrap() {
+					return false;
+				}
+			}
+
+			for (index, item) in items.iter().enumerate() {
+				let item_schema = match prefix_items.get(index) {
+					Some(item_schema) => item_schema,
+					None => match schema.get("items") {
+						Some(item_schema) => item_schema,
+						None => continue,
+					},
+				};
+
+				if !conforms(root, item_schema, item) {
+					return false;
+				}
+			}
+		}
+
+		true
+	}
+
+	#[tokio::test]
+	async fn requests_and_components_are_checked_against_the_emitted_schema() {
+		let collection = collect_source(
+			"/**\n * @feature_component_index\n */\nexport interface Component {\n\tid: number\n}\n\n/**\n * @component\n */\nexport interface Button \
+			 {\n\tlabel: string\n\tsize?: 'small' | 'large'\n\tposition: [number, number]\n}\n",
+		)
+		.await;
+		let schema = get_protocol_schema(&collection);
+		let button = json!({ "$ref": "#/$defs/Button" });
+		let response = json!({ "$ref": "#/$defs/objection:Response" });
+
+		let request = json!({
+			"sessionId": "abc",
+			"events": [{ "key": { "eventPath": ["0", "onClick"], "debugSymbol": null }, "data": { "x": 1 } }],
+		});
+		assert!(conforms(&schema, &schema, &request));
+		assert!(!conforms(&schema, &schema, &json!({ "events": [] })));
+		assert!(!conforms(
+			&schema,
+			&schema,
+			&json!({ "sessionId": "abc", "events": [{ "key": { "eventPath": "0" }, "data": null }] })
+		));
+
+		assert!(conforms(
+			&schema,
+			&response,
+			&json!([{ "key": { "actionPath": ["0"] }, "data": null, "priority": 128 }])
+		));
+		assert!(!conforms(
+			&schema,
+			&response,
+			&json!([{ "key": { "actionPath": ["0"] }, "data": null, "priority": 256 }])
+		));
+
+		assert!(conforms(&schema, &button, &json!({ "label": "Save", "position": [1, 2] })));
+		assert!(conforms(&schema, &button, &json!({ "label": "Save", "size": "large", "position": [1, 2] })));
+		assert!(!conforms(&schema, &button, &json!({ "label": "Save", "size": "huge", "position": [1, 2] })));
+		assert!(!conforms(&schema, &button, &json!({ "label": "Save", "position": [1, 2, 3] })));
+		assert!(!conforms(&schema, &button, &json!({ "label": "Save", "position": [1] })));
+	}
+}
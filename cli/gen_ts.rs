@@ -0,0 +1,235 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use std::fmt::Write;
+
+use crate::{
+	collect::Collection,
+	convert::{EnumValue, Kind, ObjectProperty},
+};
+
+const KEY_DECLARATIONS: &str = "\
+/** An event that could be triggered, where `T` is the data that the event will contain */
+export type EventKey<T> = { eventPath: string[]; debugSymbol?: string | null; version?: number; prefix?: string; __data?: T }
+
+/** An action that could be triggered, where `T` is the data that the action will contain */
+export type ActionKey<T> = { actionPath: string[]; debugSymbol?: string | null; __data?: T }
+";
+
+pub struct TsGen<'a> {
+	collection: &'a Collection,
+	index_name: &'a str,
+	output: String,
+}
+
+impl TsGen<'_> {
+	pub fn new<'a>(collection: &'a Collection) -> Result<TsGen<'a>> {
+		let index_name = collection.get_component_index_name().ok_or(anyhow!(
+			"No component index was found during typescript code gen. This indicates a failure in the checking step"
+		))?;
+
+		Ok(TsGen {
+			collection,
+			index_name,
+			output: String::new(),
+		})
+	}
+
+	pub fn gen(&mut self) -> Result<()> {
+		self.output.push_str(KEY_DECLARATIONS);
+		self.gen_index();
+
+		for def in self.collection.get_kinds() {
+			if def.name == self.index_name {
+				continue;
+			}
+
+			debug!("Generating {}", def.name);
+			self.output.push('\n');
+
+			// namespaced kinds are declared in a namespace of the same name, so that their qualified references resolve
+			let (namespace, local_name) = match def.name.rsplit_once('.') {
+				Some((namespace, local_name)) => (Some(namespace), local_name),
+				None => (None, def.name),
+			};
+
+			if let Some(namespace) = namespace {
+				self.output.push_str(&format!("export namespace {namespace} {{\n"));
+			}
+
+			push_comment(&mut self.output, def.comment, "");
+
+			match def.kind {
+				Kind::Object { properties } => {
+					self.output.push_str(&format!("export interface {local_name} {{\n"));
+					self.gen_properties(properties);
+					self.output.push_str("}\n");
+				}
+				kind => {
+					let type_tokens = gen_kind(kind);
+
+					self.output.push_str(&format!("export type {local_name} = {type_tokens}\n"));
+				}
+			}
+
+			if namespace.is_some() {
+				self.output.push_str("}\n");
+			}
+		}
+
+		Ok(())
+	}
+
+	pub fn get_output(self) -> String {
+		self.output
+	}
+
+	fn gen_index(&mut self) {
+		let mut variants = self
+			.collection
+			.get_component_info()
+			.iter()
+			.map(|(name, _)| format!("{{ type: \"{name}\"; def: {name} }}"))
+			.collect::<Vec<_>>();
+		variants.sort();
+
+		let union = if variants.is_empty() { "never".to_string() } else { variants.join(" | ") };
+
+		self.output.push('\n');
+		push_comment(&mut self.output, self.collection.get_comment(self.index_name), "");
+		self.output.push_str(&format!("export type {} = {union}\n", self.index_name));
+	}
+
+	fn gen_properties(&mut self, properties: &[ObjectProperty]) {
+		for property in properties {
+			push_comment(&mut self.output, property.comment.as_deref(), "\t");
+
+			let line = match (&property.kind, property.is_flattened) {
+				(Kind::Map { value, .. }, true) => format!("\t[key: string]: {}\n", gen_kind(value)),
+				(kind, _) => format!("\t{}{}: {}\n", property.name, if property.is_optional { "?" } else { "" }, gen_kind(kind)),
+			};
+
+			self.output.push_str(&line);
+		}
+	}
+}
+
+/// Get the typescript type of `kind`
+pub fn gen_kind(kind: &Kind) -> String {
+	match kind {
+		Kind::Dynamic => "unknown".to_string(),
+		Kind::String => "string".to_string(),
+		Kind::Number => "number".to_string(),
+		Kind::Bool => "boolean".to_string(),
+		Kind::Null => "null".to_string(),
+		Kind::ActionKey { data_type } => format!("ActionKey<{}>", gen_kind(data_type)),
+		Kind::EventKey { data_type } => format!("EventKey<{}>", gen_kind(data_type)),
+		Kind::Ref { name } => name.clone(),
+		Kind::List { of } => format!("Array<{}>", gen_kind(of)),
+		Kind::Map { key, value } => format!("Record<{}, {}>", gen_kind(key), gen_kind(value)),
+		Kind::Tuple { items } => format!("[{}]", items.iter().map(|item| gen_kind(item)).collect::<Vec<_>>().join(", ")),
+		Kind::StringEnum { variants } => variants.iter().map(|variant| format!("{variant:?}")).collect::<Vec<_>>().join(" | "),
+		Kind::ValueEnum { variants } => variants
+			.iter()
+			.map(|variant| match &variant.value {
+				EnumValue::String(value) => format!("{value:?}"),
+				EnumValue::Number(value) => value.to_string(),
+			})
+			.collect::<Vec<_>>()
+			.join(" | "),
+		Kind::KeyedEnum { variants } => variants
+			.iter()
+			.map(|variant| format!("{{ type: {:?}; def: {} }}", variant.name, gen_kind(&variant.kind)))
+			.collect::<Vec<_>>()
+			.join(" | "),
+		Kind::Object { properties } => {
+			let mut inline = String::new();
+
+			for property in properties {
+				match (&property.kind, property.is_flattened) {
+					(Kind::Map { value, .. }, true) => write!(inline, " [key: string]: {};", gen_kind(value)).unwrap(),
+					(kind, _) => write!(inline, " {}{}: {};", property.name, if property.is_optional { "?" } else { "" }, gen_kind(kind)).unwrap(),
+				}
+			}
+
+			format!("{{{inline} }}")
+		}
+	}
+}
+
+/// Generate a declaration file for the bundle, typing the `window.OBJECTION` namespace that the bundle installs, along with every
+/// type that the runtime shares with the engine
+pub fn get_bundle_declarations(collection: &Collection) -> Result<String> {
+	let mut gen = TsGen::new(collection)?;
+	gen.gen()?;
+
+	let index_name = gen.index_name;
+	let mut output = gen.get_output();
+
+	write!(
+		output,
+		"
+/** The namespace that the bundle installs on `window.OBJECTION` */
+export interface ObjectionNamespace {{
+	engineUrl: URL
+	sessionId?: string
+	existingState?: {index_name}
+	mount(): Promise<void>
+	startRuntime(mountData: {index_name}): void
+	signalApplicationReady(): Promise<void>
+	sendEvent<T>(key: EventKey<T>, data: T): Promise<void>
+	registerActionListener<T>(key: ActionKey<T>, listener: (data: T) => void): () => void
+	getActionId<T>(key: ActionKey<T>): string
+	getEventId<T>(key: EventKey<T>): string
+}}
+
+declare global {{
+	interface Window {{
+		OBJECTION: ObjectionNamespace
+	}}
+}}
+"
+	)
+	.unwrap();
+
+	Ok(output)
+}
+
+fn push_comment(output: &mut String, comment: Option<&str>, indent: &str) {
+	let comment = match comment {
+		Some(comment) if !comment.trim().is_empty() => comment,
+		_ => return,
+	};
+
+	output.push_str(indent);
+	output.push_str("/**\n");
+
+	for line in comment.lines() {
+		output.push_str(indent);
+		output.push_str(" * ");
+		output.push_str(line);
+		output.push('\n');
+	}
+
+	output.push_str(indent);
+	output.push_str(" */\n");
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::collect::collect_source;
+
+	#[tokio::test]
+	async fn bundle_declarations_type_the_props_of_every_component() {
+		let collection = collect_source(
+			"/**\n * @feature_component_index\n */\nexport interface Component {\n\tid: number\n}\n\n/**\n * @component\n */\nexport interface Button {\n\tlabel: string\n\tcount?: number\n\ttags: string[]\n}\n",
+		)
+		.await;
+		let declarations = get_bundle_declarations(&collection).unwrap();
+
+		assert!(declarations.contains("export type Component = { type: \"Button\"; def: Button }\n"));
+		assert!(declarations.contains("export interface Button {\n\tlabel: string\n\tcount?: number\n\ttags: Array<string>\n}\n"));
+		assert!(declarations.contains("\texistingState?: Component\n"));
+		assert!(declarations.contains("\t\tOBJECTION: ObjectionNamespace\n"));
+	}
+}
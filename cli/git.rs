@@ -0,0 +1,39 @@
+use anyhow::{anyhow, Context, Result};
+use std::{path::PathBuf, process::Stdio};
+use tokio::process::Command;
+use url::Url;
+
+/// Get the urls of all files that have changed since `reference`, including uncommitted and untracked files
+pub async fn get_changed_files(reference: &str) -> Result<Vec<Url>> {
+	let root = PathBuf::from(run_git(&["rev-parse", "--show-toplevel"]).await?.trim());
+	let changed = run_git(&["diff", "--name-only", reference]).await?;
+	let untracked = run_git(&["ls-files", "--others", "--exclude-standard", "--full-name"]).await?;
+
+	changed
+		.lines()
+		.chain(untracked.lines())
+		.filter(|line| !line.is_empty())
+		.map(|line| {
+			let path = root.join(line);
+
+			Url::from_file_path(&path).map_err(|_| anyhow!("failed to convert {path:?} into a url"))
+		})
+		.collect()
+}
+
+async fn run_git(args: &[&str]) -> Result<String> {
+	let output = Command::new("git")
+		.args(args)
+		.stdout(Stdio::piped())
+		.stderr(Stdio::inherit())
+		.stdin(Stdio::null())
+		.output()
+		.await
+		.context("failed to run git")?;
+
+	if !output.status.success() {
+		return Err(anyhow!("`git {}` exited with a non-zero exit code", args.join(" ")));
+	}
+
+	String::from_utf8(output.stdout).context("git produced output that was not utf-8")
+}
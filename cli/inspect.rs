@@ -5,6 +5,7 @@ use crate::{
 	collect::Collection,
 	convert::Kind,
 	diagnostic::{Diagnostic, DiagnosticList},
+	gen_rust::get_string_variant_name,
 };
 
 const RUST_RESERVED_WORDS: &[&str] = &[
@@ -58,7 +59,11 @@ impl Inspector<'_> {
 
 	pub fn inspect(self, diagnostic_list: &mut DiagnosticList) {
 		for def in self.collection.get_kinds() {
-			self.inspect_name(def.name, NameContext::Type, diagnostic_list);
+			// namespaced names are checked segment by segment
+			for segment in def.name.split('.') {
+				self.inspect_name(segment, NameContext::Type, diagnostic_list);
+			}
+
 			self.inspect_kind(def.kind, diagnostic_list);
 		}
 	}
@@ -68,14 +73,56 @@ impl Inspector<'_> {
 			Kind::Dynamic | Kind::String | Kind::Number | Kind::Bool | Kind::Null | Kind::Ref { .. } => (),
 			Kind::ActionKey { data_type } | Kind::EventKey { data_type } => self.inspect_kind(data_type, diagnostic_list),
 			Kind::List { of } => self.inspect_kind(of, diagnostic_list),
+			Kind::Map { key, value } => {
+				self.inspect_kind(key, diagnostic_list);
+				self.inspect_kind(value, diagnostic_list);
+			}
 			Kind::Tuple { items } => {
 				for item in items {
 					self.inspect_kind(item, diagnostic_list);
 				}
 			}
 			Kind::StringEnum { variants } => {
-				for name in variants {
-					self.inspect_name(name.as_str(), NameContext::Variant, diagnostic_list)
+				let mut names = HashMap::new();
+
+				// the literals are sent as they are written, so only the names that they are generated as need to be valid
+				for variant in variants {
+					let name = match get_string_variant_name(variant) {
+						Some(name) => name,
+						None => {
+							diagnostic_list.add(
+								Diagnostic::start("String literal ")
+									.inline_code(format!("{variant:?}"))
+									.text(" cannot be named as an enum variant")
+									.shift()
+									.text("The literal must start with a letter")
+									.build(),
+							);
+
+							continue;
+						}
+					};
+
+					if let Some(existing) = names.insert(name.clone(), variant) {
+						diagnostic_list.add(
+							Diagnostic::start("String literals ")
+								.inline_code(format!("{existing:?}"))
+								.text(" and ")
+								.inline_code(format!("{variant:?}"))
+								.text(" would both be named ")
+								.inline_code(&name)
+								.shift()
+								.text("Enum variants are named by converting the literal to pascal case")
+								.build(),
+						);
+					}
+
+					self.inspect_name(&name, NameContext::Variant, diagnostic_list)
+				}
+			}
+			Kind::ValueEnum { variants } => {
+				for variant in variants {
+					self.inspect_name(&variant.name, NameContext::Variant, diagnostic_list);
 				}
 			}
 			Kind::KeyedEnum { variants } => {
@@ -3,69 +3,273 @@ mod build;
 mod bundle;
 mod collect;
 mod convert;
+mod daemon;
 mod diagnostic;
+mod diff;
 mod engine;
+mod gen_go;
 mod gen_rust;
+mod gen_schema;
+mod gen_ts;
+mod git;
 mod inspect;
 mod module_loader;
 mod platform;
 mod tcp_watcher;
+#[cfg(test)]
+mod test_util;
 mod web;
 mod writer;
 
 use anstyle::{AnsiColor, Color as AnsColor, Style};
-use anyhow::{Context, Result};
-use build::BuildOptions;
-use clap::{builder::Styles, Parser, Subcommand};
+use anyhow::{anyhow, bail, Context, Result};
+use build::{BuildOptions, BuildStage, ListFormat};
+use clap::{builder::Styles, Parser, Subcommand, ValueEnum};
+use collect::GraphFormat;
 use colored::{Color, Colorize};
-use engine::Engine;
+use daemon::{run_daemon, DaemonParams};
+use diagnostic::{print_warning_report, Diagnostic};
+use engine::{BindingsWriter, Engine};
 use env_logger::Env;
-use log::{error, Level};
+use gen_rust::{get_include_scaffold, INCLUDE_FILE_NAME};
+use git::get_changed_files;
+use log::{error, info, Level};
+use module_loader::resolve_runtime;
 use platform::{BuildParams, Platform, RunParams};
 use std::{
 	env::{self, current_dir},
+	fs::{create_dir_all, metadata, read_to_string, write},
 	io::Write,
-	path::PathBuf,
+	num::NonZeroUsize,
+	path::{Path, PathBuf},
 	process::exit,
+	thread::available_parallelism,
+	time::Duration,
 };
-use tokio::runtime::Builder;
+use tokio::{runtime::Builder, time::timeout};
 use url::Url;
-use writer::Writer;
+use writer::{remove_pending_writes, JsonStyle, Writer};
 
 const VERSION: &str = "0.7.0";
 
 #[derive(Parser, Debug, Clone)]
 #[command(styles = get_styles(), version(VERSION))]
 struct Command {
-	/// The runtime to use. Must be a url.
-	#[arg(long, default_value_t = Url::parse(&format!("https://raw.githubusercontent.com/radical-ui/objection/blob/{VERSION}/runtime/mod.tsx")).unwrap())]
-	runtime: Url,
+	/// The runtime to use. Must be a url, `-` to read the runtime source from stdin, or a directory, in which case the entry is read
+	/// from its deno.json or package.json, falling back to a `mod.ts` or `index.ts`. Relative imports in a runtime read from stdin
+	/// are resolved against the current directory.
+	#[arg(long, default_value_t = format!("https://raw.githubusercontent.com/radical-ui/objection/blob/{VERSION}/runtime/mod.tsx"))]
+	runtime: String,
 
 	/// The platform to build for. Defaults to `web`.
 	#[arg(long, default_value_t = Default::default())]
 	platform: Platform,
 
-	/// The engine that the componet trees will be built in.
-	#[arg(long, default_value_t = Default::default())]
-	engine: Engine,
+	/// The engine that the componet trees will be built in. Can be specified multiple times to generate bindings for several engines
+	/// from one build.
+	#[arg(long = "engine", default_values_t = [Engine::default()])]
+	engines: Vec<Engine>,
+
+	/// The path that engine bindings should be written to. When there are multiple engines, specify this once per engine, formatted
+	/// as `ENGINE=PATH`, such as `typescript=engine/bindings.ts`. Required by the run and build operations, and by --daemon.
+	#[arg(long)]
+	bindings_path: Vec<String>,
+
+	/// Treat the bindings path as a directory. Bindings are written to a `generated.rs` within it, which is meant to be `include!`d by
+	/// a `mod.rs` that you own. If there is no `mod.rs`, one is created that includes the bindings, but it is never overwritten, so
+	/// impls for the generated types can be added to it.
+	#[arg(long)]
+	bindings_include: bool,
+
+	/// Treat the bindings path as a directory, and split the rust bindings across it: each component, along with the types declared
+	/// inline in it, is generated into its own module, and every other type into `types.rs`. The generated `mod.rs` re-exports every
+	/// module, so items are imported the same way as from a single file.
+	#[arg(long, conflicts_with = "bindings_include")]
+	bindings_split: bool,
+
+	/// Don't format the rust bindings with rustfmt. By default, they are formatted with the rustfmt on PATH, using the config of the
+	/// current directory, and are written as generated if rustfmt isn't installed.
+	#[arg(long)]
+	no_format: bool,
+
+	/// A file whose contents will be placed at the top of the generated bindings. Useful for license banners or lint attributes.
+	#[arg(long)]
+	bindings_header: Option<PathBuf>,
+
+	/// Fail if the generated bindings are not valid code for the engine
+	#[arg(long)]
+	verify_bindings: bool,
 
-	/// The path that engine bindings should be written to.
+	/// Instead of running the operation, collect the runtime and print a report of every type and function that could not be
+	/// resolved. Exits successfully, even if there are unresolved items.
 	#[arg(long)]
-	bindings_path: PathBuf,
+	report_unresolved: bool,
 
-	/// The url that the engine will be running at. Can be a websocket or http url.
+	/// Instead of running the operation, collect the runtime and print the name of every function that it must export, one per
+	/// line. Useful for scaffolding render functions.
 	#[arg(long)]
-	engine_url: Url,
+	list_functions: bool,
+
+	/// The url that the engine will be running at. Can be a websocket or http url. Events are sent to this exact url, including its
+	/// path. Required by every operation except clean.
+	#[arg(long)]
+	engine_url: Option<Url>,
+
+	/// A path to append to the engine url, for engines that are mounted behind a prefix, such as `/api/ui`. Leading and trailing
+	/// slashes are ignored.
+	#[arg(long)]
+	engine_path: Option<String>,
 
 	/// The type of operation to run
 	#[command(subcommand)]
 	operation: Operation,
 
+	/// Replace a token with a value wherever it appears as a whole identifier in the runtime's own modules, but not in dependencies
+	/// from other origins. Formatted as `TOKEN=VALUE`. Can be specified multiple times.
+	#[arg(long = "define", value_parser = parse_definition)]
+	defines: Vec<(String, String)>,
+
+	/// Send a header with every remote asset download. Formatted as `NAME=VALUE`. Can be specified multiple times. Headers declared on
+	/// an asset in its index take precedence.
+	#[arg(long = "asset-header", value_parser = parse_definition)]
+	asset_headers: Vec<(String, String)>,
+
+	/// Resolve the relative local paths in asset indexes against this directory, rather than against the directory of the index. Can
+	/// be a path or a url. Absolute paths and urls in indexes are unaffected.
+	#[arg(long, value_parser = parse_asset_root)]
+	asset_root: Option<Url>,
+
+	/// Instead of running the operation, keep running and serve `check` and `bindings` commands over a unix socket at this path.
+	/// Requests and responses are newline-delimited JSON, such as `{"id":1,"command":"check"}`. The runtime is only collected again
+	/// once one of its local files changes. Useful for editor integrations.
+	#[arg(long)]
+	daemon: Option<PathBuf>,
+
+	/// After the operation finishes, print a table of the files that produced warnings, sorted by the number of warnings. Useful
+	/// for prioritizing cleanup when adopting objection in an existing codebase.
+	#[arg(long)]
+	warning_report: bool,
+
+	/// Print the type dependency graph after the runtime has been collected. Useful for debugging why a type was pruned or
+	/// reported as missing.
+	#[arg(long, num_args = 0..=1, default_missing_value = "dot")]
+	print_graph: Option<GraphFormat>,
+
+	/// Alongside the bundle, write a `bundle.d.ts` that types the namespace the bundle installs and every type shared with the engine.
+	/// Only applies to the build operation.
+	#[arg(long)]
+	emit_declarations: bool,
+
+	/// Collect the types and components declared inside of exported namespaces. Namespaced types are generated into nested modules
+	/// in the engine bindings.
+	#[arg(long)]
+	namespaces: bool,
+
+	/// Don't collect the exports of files whose path matches this glob, such as `**/*.stories.ts`. Excluded files are still bundled if
+	/// the runtime imports them. Can be specified multiple times.
+	#[arg(long)]
+	exclude: Vec<String>,
+
+	/// Only load modules that use these schemes, such as `https`. `file:` modules and modules from the runtime's origin are always
+	/// allowed. Can be comma separated or specified multiple times.
+	#[arg(long, value_delimiter = ',')]
+	allowed_module_schemes: Vec<String>,
+
+	/// Only load remote modules from these hosts. Modules from the runtime's origin are always allowed. Can be comma separated or
+	/// specified multiple times.
+	#[arg(long, value_delimiter = ',')]
+	allowed_hosts: Vec<String>,
+
+	/// The style of every JSON artifact, such as the protocol and the build's `entrypoints.json`. By default, artifacts that are meant
+	/// to be read are pretty, and artifacts that are loaded by other programs are compact. Object keys are always sorted.
+	#[arg(long)]
+	json_style: Option<JsonStyle>,
+
+	/// Write a JSON description of every event and action key that the runtime can produce to this path. Useful for reviewing the
+	/// protocol between the engine and the runtime.
+	#[arg(long)]
+	emit_protocol: Option<PathBuf>,
+
+	/// Write a JSON Schema of the whole protocol to this path, with a definition for every type that the runtime shares with the
+	/// engine. External clients can validate their requests and responses against it.
+	#[arg(long)]
+	emit_schema: Option<PathBuf>,
+
+	/// Fail to convert types that are nested more deeply than this, rather than recursing further. The default is generous, so this
+	/// only needs to be set for unusually deep types, or to fail faster on pathological ones.
+	#[arg(long)]
+	max_depth: Option<usize>,
+
+	/// Don't warn about components that share a name with an element that the frontend treats specially, such as `slot` or
+	/// `template`.
+	#[arg(long)]
+	no_reserved_name_check: bool,
+
+	/// Warn about components with this name, in addition to the built-in reserved names. Names are compared without regard to case.
+	/// Can be comma separated or specified multiple times.
+	#[arg(long = "reserved-name", value_delimiter = ',')]
+	reserved_names: Vec<String>,
+
+	/// Fail the build when differently shaped types would be generated under the same name, such as when two components declare an
+	/// inline `options` prop and a type named after it. Otherwise, these conflicts are only warned about.
+	#[arg(long)]
+	deny_type_name_conflicts: bool,
+
+	/// Fail if the whole invocation takes longer than this, such as `90s`, `5m`, or `500ms`. A bare number is a number of seconds.
+	/// Files that were being written when the time ran out are removed. Useful for bounding builds in CI.
+	#[arg(long, value_parser = parse_duration)]
+	timeout: Option<Duration>,
+
+	/// Instead of running the operation, compare the components of the runtime with those of this older version of it, printing the
+	/// added, removed, and changed props, events, and actions as JSON. Fails if any of the changes are breaking. Accepts the same
+	/// values as `--runtime`.
+	#[arg(long)]
+	diff_against: Option<String>,
+
+	/// Write the generated entry module to this path before it is bundled. The entry imports the runtime by its absolute url, so the
+	/// file can be imported as-is. Useful for debugging how components are wired up to the transport.
+	#[arg(long)]
+	emit_entry: Option<PathBuf>,
+
+	/// Only fail on errors from types and components declared in files that have changed since this git reference. Errors elsewhere
+	/// are reported as warnings. The whole runtime is still collected, so that dependencies are resolved correctly.
+	#[arg(long)]
+	changed_since: Option<String>,
+
+	/// The maximum number of build tasks, such as bundling and loading asset indexes, that can run at once. Defaults to the number
+	/// of available cpus.
+	#[arg(long, default_value_t = available_parallelism().unwrap_or(NonZeroUsize::MIN))]
+	jobs: NonZeroUsize,
+
 	/// The deno script to use for bundling the runtime. Primarily useful if one wants to test a modified version of the default bundler.
 	#[arg(long, default_value_t = Url::parse(&format!("https://raw.githubusercontent.com/radical-ui/objection/blob/{VERSION}/bundle/mod.ts")).unwrap())]
 	bundler: Url,
 }
 
+impl Command {
+	/// Whether one of the flags that run something instead of the operation was given
+	fn replaces_operation(&self) -> bool {
+		self.report_unresolved || self.list_functions || self.diff_against.is_some() || self.daemon.is_some()
+	}
+
+	/// Whether this invocation can write bindings, which only the daemon, and the run and build operations do
+	fn writes_bindings(&self) -> bool {
+		if self.daemon.is_some() {
+			return true;
+		}
+
+		if self.replaces_operation() {
+			return false;
+		}
+
+		match &self.operation {
+			Operation::Run { .. } => true,
+			Operation::Build { only, .. } => only.is_empty() || only.contains(&BuildStage::Bindings),
+			Operation::ListComponents { .. } | Operation::Clean { .. } | Operation::Check => false,
+		}
+	}
+}
+
 #[derive(Subcommand, Debug, Clone)]
 enum Operation {
 	/// Run the application using the configured runtime (see --runtime) and platform (see --platform). Engine is expected to be
@@ -87,7 +291,43 @@ enum Operation {
 		/// The directory to where the generated client code will be written
 		#[arg(long, default_value_t = String::from("target/objection_build"))]
 		out_dir: String,
+
+		/// Include a content hash in the bundle's filename, for long-term caching. An `entrypoints.json` is written alongside, mapping
+		/// `bundle.js` to the hashed filename.
+		#[arg(long)]
+		hashed_filenames: bool,
+
+		/// Rewrite every output. By default, outputs that haven't changed since the last build into the output dir are left
+		/// untouched, according to a build cache that is kept in the output dir.
+		#[arg(long, alias = "no-build-cache")]
+		force: bool,
+
+		/// Run only these stages of the build, leaving the outputs of the other stages as they are. Can be comma separated or specified
+		/// multiple times. By default, every stage is run.
+		#[arg(long, value_enum, value_delimiter = ',')]
+		only: Vec<BuildStage>,
+	},
+	/// Collect the configured runtime (see --runtime) and list every component that it defines, along with its render function and
+	/// the number of events and actions that it has. Nothing is built, and errors elsewhere in the runtime are ignored.
+	ListComponents {
+		#[arg(long, value_enum, default_value_t = ListFormat::Text)]
+		format: ListFormat,
 	},
+	/// Remove the output directory of builds, along with the cache of downloaded assets. Only directories inside of the current
+	/// directory, or the cache directory itself, are ever removed. The runtime isn't collected, so --engine-url and --bindings-path
+	/// aren't needed.
+	Clean {
+		/// The output directory of builds, as it was passed to the build command
+		#[arg(long, default_value_t = String::from("target/objection_build"))]
+		out_dir: String,
+
+		/// List what would be removed, and how many bytes would be reclaimed, without removing anything
+		#[arg(long)]
+		dry_run: bool,
+	},
+	/// Collect and validate the configured runtime (see --runtime), reporting every error, without bundling it or generating
+	/// bindings. Exits with a non-zero code if the runtime is invalid, which makes it suitable for a pre-commit hook.
+	Check,
 }
 
 fn main() {
@@ -123,39 +363,354 @@ fn main() {
 
 async fn main_async() -> Result<()> {
 	let args = Command::parse();
+	let duration = match args.timeout {
+		Some(duration) => duration,
+		None => return run(args).await,
+	};
+
+	match timeout(duration, run(args)).await {
+		Ok(result) => result,
+		Err(_) => {
+			remove_pending_writes();
+			bail!("build exceeded the timeout of {duration:?}")
+		}
+	}
+}
+
+async fn run(args: Command) -> Result<()> {
+	let cache_writer = get_cache_writer()?;
+
+	// clean doesn't collect the runtime, so it needs neither bindings nor an engine url
+	if let (Operation::Clean { out_dir, dry_run }, false) = (&args.operation, args.replaces_operation()) {
+		return clean(out_dir, *dry_run, &cache_writer);
+	}
+
+	let bindings_header = match &args.bindings_header {
+		Some(path) => Some(read_to_string(path).with_context(|| format!("failed to read the bindings header at {path:?}"))?),
+		None => None,
+	};
+	let runtime = resolve_runtime(&args.runtime).await?;
+	let changed_files = match &args.changed_since {
+		Some(reference) => Some(
+			get_changed_files(reference)
+				.await
+				.with_context(|| format!("failed to get the files changed since {reference}"))?,
+		),
+		None => None,
+	};
+	let engine_url = args
+		.engine_url
+		.as_ref()
+		.ok_or_else(|| anyhow!("--engine-url is required, unless the operation is clean"))?;
+	let engine_url = match &args.engine_path {
+		Some(path) => join_engine_path(engine_url, path),
+		None => engine_url.clone(),
+	};
 	let build_options = BuildOptions {
 		bundler: &args.bundler,
-		runtime: &args.runtime,
-		engine_url: &args.engine_url,
-		engine: args.engine,
+		runtime: &runtime.url,
+		runtime_source: runtime.stdin_source.as_deref(),
+		engine_url: &engine_url,
+		engines: &args.engines,
+		bindings_header: bindings_header.as_deref(),
+		bindings_split: args.bindings_split,
+		format_bindings: !args.no_format,
+		defines: &args.defines,
+		print_graph: args.print_graph,
+		verify_bindings: args.verify_bindings,
+		changed_files: changed_files.as_deref(),
+		protocol_path: args.emit_protocol.as_deref(),
+		schema_path: args.emit_schema.as_deref(),
+		entry_path: args.emit_entry.as_deref(),
+		jobs: args.jobs,
+		emit_declarations: args.emit_declarations,
+		asset_headers: &args.asset_headers,
+		asset_root: args.asset_root.as_ref(),
+		namespaces: args.namespaces,
+		allowed_module_schemes: &args.allowed_module_schemes,
+		allowed_hosts: &args.allowed_hosts,
+		json_style: args.json_style,
+		exclude: &args.exclude,
+		max_depth: args.max_depth,
+		check_reserved_names: !args.no_reserved_name_check,
+		reserved_names: &args.reserved_names,
+		deny_type_name_conflicts: args.deny_type_name_conflicts,
+		stages: &[],
 	};
-	let bindings_writer = Writer::new(current_dir().context("failed to get the current working directory")?).into_file_writer(args.bindings_path);
-	let home = PathBuf::from(env::var("HOME").context("Failed to find the HOME env variable")?).join(".cache/objection");
-	let cache_writer = Writer::new(home);
+	let mut bindings_writers = Vec::with_capacity(args.engines.len());
+
+	// bindings are only validated, and include modules only scaffolded, when they will be written, so that operations like check
+	// don't create anything in the project
+	let bindings_paths = if args.writes_bindings() {
+		get_bindings_paths(&args.engines, &args.bindings_path)?
+	} else {
+		Vec::new()
+	};
+
+	for (engine, path) in bindings_paths {
+		// only rust has a way to include a file into a module
+		let path = if args.bindings_include && engine == Engine::Rust {
+			prepare_include_bindings(&path, bindings_header.as_deref())?
+		} else {
+			path
+		};
+
+		// split bindings are written into the bindings path, and always include a `mod.rs`
+		if args.bindings_split && engine == Engine::Rust {
+			validate_bindings_path(&path.join("mod.rs"))?;
+		} else {
+			validate_bindings_path(&path)?;
+		}
+
+		bindings_writers.push((
+			engine,
+			Writer::new(current_dir().context("failed to get the current working directory")?).into_file_writer(path),
+		));
+	}
+
+	let bindings_writer = BindingsWriter::new(bindings_writers);
 
-	match args.operation {
+	let result = if args.report_unresolved {
+		build::report_unresolved(build_options).await
+	} else if args.list_functions {
+		build::list_functions(build_options).await
+	} else if let Some(previous_runtime) = &args.diff_against {
+		let previous_runtime = resolve_runtime(previous_runtime).await?;
+
+		build::report_diff(build_options, &previous_runtime).await
+	} else if let Some(socket_path) = &args.daemon {
+		run_daemon(DaemonParams {
+			build_options,
+			bindings_writer: &bindings_writer,
+			socket_path,
+		})
+		.await
+	} else {
+		run_operation(args.operation, args.platform, build_options, &bindings_writer, &cache_writer).await
+	};
+
+	// the report is informational, so it doesn't change the outcome of the operation
+	if args.warning_report {
+		print_warning_report();
+	}
+
+	result
+}
+
+async fn run_operation(
+	operation: Operation,
+	platform: Platform,
+	build_options: BuildOptions<'_>,
+	bindings_writer: &BindingsWriter,
+	cache_writer: &Writer,
+) -> Result<()> {
+	match operation {
 		Operation::Run { web_port, no_reload } => {
-			args.platform
+			platform
 				.run(RunParams {
 					build_options,
 					web_port,
 					reload: !no_reload,
-					bindings_writer: &bindings_writer,
-					cache_writer: &cache_writer,
+					bindings_writer,
+					cache_writer,
 				})
 				.await
 		}
-		Operation::Build { out_dir } => {
-			args.platform
+		Operation::Build {
+			out_dir,
+			hashed_filenames,
+			force,
+			only,
+		} => {
+			platform
 				.build(BuildParams {
-					build_options,
-					bindings_writer: &bindings_writer,
+					build_options: BuildOptions {
+						stages: &only,
+						..build_options
+					},
+					bindings_writer,
 					output_writer: &Writer::new(out_dir),
-					cache_writer: &cache_writer,
+					cache_writer,
+					hashed_filenames,
+					force,
 				})
 				.await
 		}
+		Operation::ListComponents { format } => build::list_components(build_options, format).await,
+		Operation::Check => build::check(build_options).await,
+		Operation::Clean { out_dir, dry_run } => clean(&out_dir, dry_run, cache_writer),
+	}
+}
+
+fn get_cache_writer() -> Result<Writer> {
+	let home = PathBuf::from(env::var("HOME").context("Failed to find the HOME env variable")?).join(".cache/objection");
+
+	Ok(Writer::new(home))
+}
+
+/// Remove the build outputs in `out_dir` and the cache of downloaded assets
+fn clean(out_dir: &str, dry_run: bool, cache_writer: &Writer) -> Result<()> {
+	let project_dir = current_dir().context("failed to get the current working directory")?;
+	let cache_root = cache_writer.get_directory().parent().context("the cache directory has no parent")?;
+	let reclaimed = Writer::new(out_dir).clean(&project_dir, dry_run)? + cache_writer.clean(cache_root, dry_run)?;
+
+	info!("{} {reclaimed} bytes", if dry_run { "Would reclaim" } else { "Reclaimed" });
+
+	Ok(())
+}
+
+/// Pair each engine with the path that its bindings are written to. A single unprefixed path is allowed when there is only one engine.
+fn get_bindings_paths(engines: &[Engine], bindings_paths: &[String]) -> Result<Vec<(Engine, PathBuf)>> {
+	if let ([engine], [path]) = (engines, bindings_paths) {
+		if !path.contains('=') {
+			return Ok(Vec::from([(*engine, PathBuf::from(path))]));
+		}
+	}
+
+	let mut paths = Vec::with_capacity(engines.len());
+
+	for definition in bindings_paths {
+		let (engine_name, path) = parse_definition(definition)?;
+		let engine = Engine::from_str(&engine_name, true).map_err(|_| anyhow!("`{engine_name}` in `--bindings-path {definition}` is not an engine"))?;
+
+		if !engines.contains(&engine) {
+			bail!("A bindings path was given for the {engine} engine, but that engine was not selected with --engine");
+		}
+
+		paths.push((engine, PathBuf::from(path)));
+	}
+
+	for engine in engines {
+		if !paths.iter().any(|(path_engine, _)| path_engine == engine) {
+			bail!("No bindings path was given for the {engine} engine. Specify one with `--bindings-path {engine}=PATH`");
+		}
+	}
+
+	Ok(paths)
+}
+
+/// Fail before any expensive work if bindings can't be written to `path`. Nothing is created, as missing parent directories are created
+/// when the bindings are written.
+fn validate_bindings_path(path: &Path) -> Result<()> {
+	if path.is_dir() {
+		return Diagnostic::start("The bindings path ")
+			.inline_code(path.display())
+			.text(" is a directory")
+			.shift()
+			.text("Pass the path of a file, such as ")
+			.inline_code(path.join("bindings.rs").display())
+			.text(", or use ")
+			.inline_code("--bindings-include")
+			.text(" to write bindings into a directory")
+			.build()
+			.err();
 	}
+
+	// the file itself if it exists, and otherwise the directory that its missing parents would be created in
+	let existing = path
+		.ancestors()
+		.find(|ancestor| !ancestor.as_os_str().is_empty() && ancestor.exists())
+		.unwrap_or(Path::new("."));
+	let existing_metadata = metadata(existing).with_context(|| format!("failed to read the metadata of {existing:?}"))?;
+
+	if existing != path && !existing_metadata.is_dir() {
+		return Diagnostic::start("The bindings path ")
+			.inline_code(path.display())
+			.text(" can't be created, because ")
+			.inline_code(existing.display())
+			.text(" is a file")
+			.build()
+			.err();
+	}
+
+	if existing_metadata.permissions().readonly() {
+		return Diagnostic::start("The bindings path ")
+			.inline_code(path.display())
+			.text(" can't be written to, because ")
+			.inline_code(existing.display())
+			.text(" is read only")
+			.build()
+			.err();
+	}
+
+	Ok(())
+}
+
+/// Create the hand-owned module that includes the bindings if it doesn't exist yet, returning the path that bindings should be written to
+fn prepare_include_bindings(directory: &Path, bindings_header: Option<&str>) -> Result<PathBuf> {
+	// inner attributes and inner doc comments are not allowed in `include!`d files
+	if bindings_header
+		.map(|header| header.contains("#![") || header.contains("//!"))
+		.unwrap_or_default()
+	{
+		return Diagnostic::start("The bindings header contains inner attributes or inner doc comments, which can't be used with ")
+			.inline_code("--bindings-include")
+			.shift()
+			.text("Place them in the module that includes the bindings instead")
+			.build()
+			.err();
+	}
+
+	create_dir_all(directory).with_context(|| format!("failed to create the bindings directory at {directory:?}"))?;
+
+	let module_path = directory.join("mod.rs");
+
+	if !module_path.exists() {
+		write(&module_path, get_include_scaffold()).with_context(|| format!("failed to write the bindings module at {module_path:?}"))?;
+		info!("Created {module_path:?}, which includes the generated bindings");
+	}
+
+	Ok(directory.join(INCLUDE_FILE_NAME))
+}
+
+fn join_engine_path(engine_url: &Url, path: &str) -> Url {
+	let segments = path.split('/').filter(|segment| !segment.is_empty()).collect::<Vec<_>>();
+	let mut url = engine_url.clone();
+
+	if let Ok(mut path_segments) = url.path_segments_mut() {
+		path_segments.pop_if_empty().extend(segments);
+	}
+
+	url
+}
+
+fn parse_definition(text: &str) -> Result<(String, String)> {
+	let (token, value) = text
+		.split_once('=')
+		.ok_or_else(|| anyhow!("expected a definition to be formatted as `TOKEN=VALUE`, but found `{text}`"))?;
+
+	Ok((token.to_string(), value.to_string()))
+}
+
+fn parse_duration(text: &str) -> Result<Duration> {
+	let split_at = text.find(|char: char| !char.is_ascii_digit() && char != '.').unwrap_or(text.len());
+	let (amount, unit) = text.split_at(split_at);
+	let invalid_duration = || anyhow!("expected a duration such as `90s`, `5m`, or `500ms`, but found `{text}`");
+	let amount = amount.parse::<f64>().map_err(|_| invalid_duration())?;
+
+	let seconds = match unit {
+		"ms" => amount / 1000.0,
+		"" | "s" => amount,
+		"m" => amount * 60.0,
+		"h" => amount * 60.0 * 60.0,
+		_ => bail!("unknown duration unit `{unit}` in `{text}`. Expected one of ms, s, m, or h"),
+	};
+
+	// durations that are too long to represent would otherwise panic
+	Duration::try_from_secs_f64(seconds).map_err(|_| invalid_duration())
+}
+
+fn parse_asset_root(text: &str) -> Result<Url> {
+	// single letter schemes are windows drive letters
+	if let Ok(url) = Url::parse(text) {
+		if url.scheme().len() > 1 {
+			return Ok(url);
+		}
+	}
+
+	let path = current_dir().context("failed to get the current working directory")?.join(text);
+
+	Url::from_directory_path(&path).map_err(|_| anyhow!("failed to convert {path:?} into a url"))
 }
 
 fn get_styles() -> Styles {
@@ -168,3 +723,83 @@ fn get_styles() -> Styles {
 		.valid(Style::new().bold().underline().fg_color(Some(AnsColor::Ansi(AnsiColor::Green))))
 		.placeholder(Style::new().fg_color(Some(AnsColor::Ansi(AnsiColor::White))))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use test_util::TempDir;
+
+	#[test]
+	fn engine_paths_are_appended_to_the_engine_url_regardless_of_slashes() {
+		let root = Url::parse("http://localhost:8000").unwrap();
+		let mounted = Url::parse("http://localhost:8000/engine/").unwrap();
+
+		for path in ["api/ui", "/api/ui", "api/ui/", "//api//ui//"] {
+			assert_eq!(join_engine_path(&root, path).as_str(), "http://localhost:8000/api/ui");
+			assert_eq!(join_engine_path(&mounted, path).as_str(), "http://localhost:8000/engine/api/ui");
+		}
+
+		assert_eq!(join_engine_path(&root, "/").as_str(), "http://localhost:8000/");
+	}
+
+	#[test]
+	fn directories_are_rejected_as_bindings_paths() {
+		let directory = TempDir::new("bindings_directory");
+		let error = validate_bindings_path(directory.path()).unwrap_err().to_string();
+
+		assert!(error.contains("is a directory"));
+		assert!(error.contains("bindings.rs"));
+	}
+
+	#[test]
+	fn bindings_paths_are_validated_without_creating_anything() {
+		let directory = TempDir::new("bindings_path");
+		let nested_path = directory.path().join("src/generated/bindings.rs");
+		let file = directory.write("file.rs", "");
+
+		validate_bindings_path(&nested_path).unwrap();
+		validate_bindings_path(&file).unwrap();
+
+		assert!(!directory.path().join("src").exists());
+		assert!(validate_bindings_path(&file.join("bindings.rs")).unwrap_err().to_string().contains("is a file"));
+	}
+
+	#[test]
+	fn included_bindings_never_overwrite_the_hand_owned_module() {
+		let directory = TempDir::new("include_bindings");
+		let bindings_directory = directory.path().join("bindings");
+		let module_path = bindings_directory.join("mod.rs");
+
+		let bindings_path = prepare_include_bindings(&bindings_directory, Some("// Generated code")).unwrap();
+
+		assert_eq!(bindings_path, bindings_directory.join(INCLUDE_FILE_NAME));
+		assert_eq!(read_to_string(&module_path).unwrap(), get_include_scaffold());
+
+		write(&module_path, "include!(\"generated.rs\");\n\nimpl Button {}\n").unwrap();
+		prepare_include_bindings(&bindings_directory, None).unwrap();
+
+		assert_eq!(read_to_string(&module_path).unwrap(), "include!(\"generated.rs\");\n\nimpl Button {}\n");
+		assert!(prepare_include_bindings(&bindings_directory, Some("#![allow(dead_code)]")).is_err());
+		assert!(prepare_include_bindings(&bindings_directory, Some("//! Bindings")).is_err());
+	}
+
+	#[test]
+	fn durations_are_parsed_in_every_unit() {
+		assert_eq!(parse_duration("500ms").unwrap(), Duration::from_millis(500));
+		assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+		assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+		assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+		assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+	}
+
+	#[test]
+	fn durations_with_unknown_units_are_rejected() {
+		assert!(parse_duration("5d").unwrap_err().to_string().contains("unknown duration unit `d`"));
+		assert!(parse_duration("ms").unwrap_err().to_string().contains("expected a duration"));
+	}
+
+	#[test]
+	fn durations_that_overflow_are_rejected_without_panicking() {
+		assert!(parse_duration("99999999999999999999h").unwrap_err().to_string().contains("expected a duration"));
+	}
+}
@@ -1,21 +1,401 @@
-use anyhow::{anyhow, Context, Result};
-use deno_graph::source::{MemoryLoader, Source};
+use anyhow::{anyhow, bail, Context, Result};
+use deno_graph::{
+	source::{LoadFuture, LoadOptions, LoadResponse, Loader, MemoryLoader, Source},
+	GraphKind, Module, ModuleGraph,
+};
+use log::debug;
+use reqwest::{header::LOCATION, redirect::Policy, Client};
 use serde::{Deserialize, Serialize};
-use serde_json::from_slice;
-use std::{env, path::PathBuf, process::Stdio};
-use tokio::{fs::read_to_string, process::Command};
+use serde_json::{from_slice, from_str, Value};
+use std::{
+	collections::HashSet,
+	env::{self, current_dir},
+	fmt::{self, Display},
+	path::{Path, PathBuf},
+	process::Stdio,
+	sync::Mutex,
+};
+use tokio::{
+	fs::{read, read_to_string},
+	io::{stdin, AsyncReadExt},
+	process::Command,
+};
 use url::Url;
 
-use crate::bundle::Bundler;
+use crate::{bundle::Bundler, diagnostic::Diagnostic};
 
-pub async fn load_modules(entry_url: &Url, memory_loader: &mut MemoryLoader, bundler: &mut Bundler) -> Result<()> {
+/// The url that a runtime read from stdin is registered under. It has no file on disk, so it is only ever loaded from memory. See
+/// `load_stdin_modules`
+pub const STDIN_RUNTIME_URL: &str = "stdin:///runtime.tsx";
+
+/// The manifests that a runtime directory's entry is read from, in the order that they are checked
+const RUNTIME_MANIFESTS: &[&str] = &["deno.json", "package.json"];
+
+/// The entries that are looked for when a runtime directory has no manifest that specifies one
+const CONVENTIONAL_RUNTIME_ENTRIES: &[&str] = &["mod.ts", "mod.tsx", "index.ts", "index.tsx"];
+
+/// A runtime that has been resolved from the `--runtime` argument
+#[derive(Debug)]
+pub struct ResolvedRuntime {
+	pub url: Url,
+	/// The source of the runtime, if it was read from stdin
+	pub stdin_source: Option<String>,
+}
+
+/// Resolve the `--runtime` argument into a url. A value of `-` causes the runtime source to be read from stdin. If the argument is a
+/// directory, the entry is resolved from the directory's manifest. See `resolve_runtime_dir`
+pub async fn resolve_runtime(specifier: &str) -> Result<ResolvedRuntime> {
+	if specifier != "-" {
+		let dir = match Url::parse(specifier) {
+			Ok(url) if url.scheme() == "file" => url.to_file_path().ok().filter(|path| path.is_dir()),
+			Ok(url) => return Ok(ResolvedRuntime { url, stdin_source: None }),
+			Err(_) => Some(PathBuf::from(specifier)).filter(|path| path.is_dir()),
+		};
+
+		let url = match dir {
+			Some(dir) => {
+				let entry = resolve_runtime_dir(&dir).await?;
+				debug!("resolved runtime directory {dir:?} to {entry:?}");
+
+				Url::from_file_path(&entry).map_err(|_| anyhow!("failed to convert {entry:?} into a url"))?
+			}
+			None => Url::parse(specifier).with_context(|| format!("expected the runtime to be a url, a directory, or `-`, but got '{specifier}'"))?,
+		};
+
+		return Ok(ResolvedRuntime { url, stdin_source: None });
+	}
+
+	let mut source = String::new();
+	stdin().read_to_string(&mut source).await.context("failed to read the runtime from stdin")?;
+
+	Ok(ResolvedRuntime {
+		url: Url::parse(STDIN_RUNTIME_URL)?,
+		stdin_source: Some(source),
+	})
+}
+
+/// Find the entry of a runtime directory. The entry is read from the `exports` field of `deno.json`, or from the `exports` or `main`
+/// field of `package.json`. If neither manifest specifies an entry, a conventional entry such as `mod.ts` is used. It is an error for
+/// the manifests to disagree, or for there to be more than one conventional entry.
+async fn resolve_runtime_dir(dir: &Path) -> Result<PathBuf> {
+	let dir = dir.canonicalize().with_context(|| format!("failed to resolve the runtime directory {dir:?}"))?;
+	let mut manifest_entries = Vec::new();
+
+	for manifest in RUNTIME_MANIFESTS {
+		let path = dir.join(manifest);
+
+		if !path.is_file() {
+			continue;
+		}
+
+		let source = read_to_string(&path).await.with_context(|| format!("failed to read {path:?}"))?;
+		let value = from_str::<Value>(&source).with_context(|| format!("failed to parse {path:?}"))?;
+
+		if let Some(entry) = get_manifest_entry(&value).with_context(|| format!("failed to read the entry from {path:?}"))? {
+			manifest_entries.push((*manifest, dir.join(entry)));
+		}
+	}
+
+	match manifest_entries.as_slice() {
+		[] => (),
+		[(manifest, entry), rest @ ..] => {
+			if let Some((other_manifest, other_entry)) = rest.iter().find(|(_, other_entry)| other_entry != entry) {
+				bail!("the runtime entry is ambiguous, because {manifest} specifies {entry:?}, but {other_manifest} specifies {other_entry:?}");
+			}
+
+			if !entry.is_file() {
+				bail!("{manifest} specifies {entry:?} as the runtime entry, but it does not exist");
+			}
+
+			return Ok(entry.clone());
+		}
+	}
+
+	let conventional_entries = CONVENTIONAL_RUNTIME_ENTRIES
+		.iter()
+		.map(|name| dir.join(name))
+		.filter(|path| path.is_file())
+		.collect::<Vec<_>>();
+
+	match conventional_entries.as_slice() {
+		[entry] => Ok(entry.clone()),
+		[] => bail!(
+			"no runtime entry was found in {dir:?}. Specify one with the `exports` field of a deno.json or package.json, or add one of {}",
+			CONVENTIONAL_RUNTIME_ENTRIES.join(", ")
+		),
+		entries => bail!(
+			"the runtime entry in {dir:?} is ambiguous, because it contains {}. Specify one with the `exports` field of a deno.json or package.json",
+			entries.iter().map(|entry| format!("{entry:?}")).collect::<Vec<_>>().join(" and ")
+		),
+	}
+}
+
+/// Read the entry from a manifest's `exports` field, falling back to its `main` field. Only the root export (`.`) is considered, and
+/// conditional exports resolve to their `import` or `default` condition.
+fn get_manifest_entry(manifest: &Value) -> Result<Option<&str>> {
+	let mut export = match manifest.get("exports").or_else(|| manifest.get("main")) {
+		Some(export) => export,
+		None => return Ok(None),
+	};
+
+	if let Some(root) = export.get(".") {
+		export = root;
+	}
+
+	for condition in ["import", "default"] {
+		if let Some(conditional) = export.get(condition) {
+			export = conditional;
+			break;
+		}
+	}
+
+	match export {
+		Value::String(entry) => Ok(Some(entry)),
+		_ => bail!("expected the root export to be a string, but got {export}"),
+	}
+}
+
+/// Substrings of deno's error messages that indicate a failure that could succeed if retried
+const TRANSIENT_ERROR_PATTERNS: &[&str] = &[
+	"error sending request",
+	"connection reset",
+	"connection refused",
+	"connection closed",
+	"timed out",
+	"dns error",
+	"temporarily unavailable",
+	"502 bad gateway",
+	"503 service unavailable",
+	"504 gateway timeout",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModuleLoadErrorKind {
+	/// The failure was caused by the network, and could succeed if retried
+	Transient,
+	/// The failure will happen again if retried, such as a syntax error or a missing module
+	Permanent,
+}
+
+/// A failure to load a module. `load_modules` returns this inside of its error when the failure could be classified, which can be
+/// accessed with `anyhow::Error::downcast_ref`.
+#[derive(Debug)]
+pub struct ModuleLoadError {
+	pub kind: ModuleLoadErrorKind,
+	/// The module that failed to load, if it is known
+	pub specifier: Option<Url>,
+	pub message: String,
+}
+
+impl ModuleLoadError {
+	/// Classify a failure by its message, which is transient if it looks like it was caused by the network
+	pub fn classify(specifier: Option<Url>, message: impl Into<String>) -> ModuleLoadError {
+		let message = message.into();
+		let lowercase_message = message.to_lowercase();
+		let kind = if TRANSIENT_ERROR_PATTERNS.iter().any(|pattern| lowercase_message.contains(pattern)) {
+			ModuleLoadErrorKind::Transient
+		} else {
+			ModuleLoadErrorKind::Permanent
+		};
+
+		ModuleLoadError { kind, specifier, message }
+	}
+
+	/// Get the kind of module load failure that caused `error`, if it was caused by one
+	pub fn kind_of(error: &anyhow::Error) -> Option<ModuleLoadErrorKind> {
+		error.chain().find_map(|cause| cause.downcast_ref::<ModuleLoadError>()).map(|error| error.kind)
+	}
+}
+
+impl Display for ModuleLoadError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let kind = match self.kind {
+			ModuleLoadErrorKind::Transient => "transient",
+			ModuleLoadErrorKind::Permanent => "permanent",
+		};
+
+		match &self.specifier {
+			Some(specifier) => write!(f, "Failed to load {specifier} ({kind} failure): {}", self.message),
+			None => write!(f, "Failed to load the module graph ({kind} failure): {}", self.message),
+		}
+	}
+}
+
+impl std::error::Error for ModuleLoadError {}
+
+/// The schemes and hosts that modules may be loaded from. An empty list of schemes or hosts means that any scheme or host is allowed.
+/// `file:` modules and modules from the same origin as the runtime are always allowed.
+#[derive(Debug)]
+pub struct ModuleAllowlist {
+	schemes: Vec<String>,
+	hosts: Vec<String>,
+	runtime_origin: (String, Option<String>),
+}
+
+impl ModuleAllowlist {
+	pub fn new(schemes: &[String], hosts: &[String], runtime_url: &Url) -> ModuleAllowlist {
+		ModuleAllowlist {
+			schemes: schemes.iter().map(|scheme| scheme.trim_end_matches(':').to_lowercase()).collect(),
+			hosts: hosts.iter().map(|host| host.to_lowercase()).collect(),
+			runtime_origin: (runtime_url.scheme().to_string(), runtime_url.host_str().map(|host| host.to_string())),
+		}
+	}
+
+	pub fn check(&self, specifier: &Url) -> Result<()> {
+		if specifier.scheme() == "file" {
+			return Ok(());
+		}
+
+		let origin = (specifier.scheme().to_string(), specifier.host_str().map(|host| host.to_string()));
+
+		if self.runtime_origin == origin {
+			return Ok(());
+		}
+
+		if !self.schemes.is_empty() && !self.schemes.iter().any(|scheme| scheme == specifier.scheme()) {
+			return Diagnostic::start("Module ")
+				.inline_code(specifier)
+				.text(" uses the ")
+				.inline_code(format!("{}:", specifier.scheme()))
+				.text(" scheme, which is not allowed")
+				.shift()
+				.text("Allowed schemes are ")
+				.join_map(self.schemes.iter(), |builder, scheme| builder.inline_code(format!("{scheme}:")))
+				.build()
+				.err();
+		}
+
+		let host = specifier.host_str().unwrap_or_default();
+
+		if !self.hosts.is_empty() && !self.hosts.iter().any(|allowed| allowed == host) {
+			return Diagnostic::start("Module ")
+				.inline_code(specifier)
+				.text(" is hosted on ")
+				.inline_code(host)
+				.text(", which is not allowed")
+				.shift()
+				.text("Allowed hosts are ")
+				.join_map(self.hosts.iter(), |builder, host| builder.inline_code(host))
+				.build()
+				.err();
+		}
+
+		Ok(())
+	}
+
+	/// Walk the module graph of `entry_url`, failing if any module in it isn't allowed. This happens before deno caches the graph, and
+	/// a module that isn't allowed is never fetched, so neither is anything that it imports.
+	pub async fn check_graph(&self, entry_url: &Url) -> Result<()> {
+		if self.schemes.is_empty() && self.hosts.is_empty() {
+			return Ok(());
+		}
+
+		let loader = AllowlistLoader {
+			allowlist: self,
+			client: Client::builder()
+				.redirect(Policy::none())
+				.build()
+				.context("failed to create a client for checking the module graph")?,
+			rejected: Mutex::new(Vec::new()),
+		};
+		let mut graph = ModuleGraph::new(GraphKind::All);
+
+		graph.build(Vec::from([entry_url.clone()]), &loader, Default::default()).await;
+
+		let mut rejected = loader.rejected.into_inner().unwrap();
+		rejected.sort();
+
+		for specifier in &rejected {
+			self.check(specifier)?;
+		}
+
+		// the imports of a module that failed to load couldn't be checked
+		graph
+			.valid()
+			.map_err(|error| anyhow!(ModuleLoadError::classify(None, error.to_string())))
+			.context("failed to check the module graph against the allowed schemes and hosts")
+	}
+}
+
+/// Loads the module graph for `ModuleAllowlist::check_graph`. Modules that aren't allowed are recorded instead of being fetched.
+/// Redirects are handed back to the graph rather than followed, so that their targets are checked as well.
+struct AllowlistLoader<'a> {
+	allowlist: &'a ModuleAllowlist,
+	client: Client,
+	rejected: Mutex<Vec<Url>>,
+}
+
+impl Loader for AllowlistLoader<'_> {
+	fn load(&self, specifier: &Url, _options: LoadOptions) -> LoadFuture {
+		if self.allowlist.check(specifier).is_err() {
+			self.rejected.lock().unwrap().push(specifier.clone());
+
+			return Box::pin(async { Ok(None) });
+		}
+
+		let specifier = specifier.clone();
+
+		match specifier.scheme() {
+			"file" => Box::pin(async move {
+				let path = specifier.to_file_path().map_err(|_| anyhow!("{specifier} is not a valid file url"))?;
+				let content = read(&path).await.with_context(|| format!("failed to read {path:?}"))?;
+
+				Ok(Some(LoadResponse::Module {
+					content: content.into(),
+					specifier,
+					maybe_headers: None,
+				}))
+			}),
+			"http" | "https" => {
+				let request = self.client.get(specifier.clone());
+
+				Box::pin(async move {
+					let response = request.send().await.with_context(|| format!("Failed to fetch {specifier}"))?;
+
+					if response.status().is_redirection() {
+						let location = response
+							.headers()
+							.get(LOCATION)
+							.and_then(|location| location.to_str().ok())
+							.ok_or_else(|| anyhow!("{specifier} redirected without a location"))?;
+
+						return Ok(Some(LoadResponse::Redirect {
+							specifier: specifier.join(location).with_context(|| format!("{specifier} redirected to an invalid url"))?,
+						}));
+					}
+
+					let response = response.error_for_status().with_context(|| format!("Failed to fetch {specifier}"))?;
+					let maybe_headers = response
+						.headers()
+						.iter()
+						.filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+						.collect();
+					let content = response.bytes().await.with_context(|| format!("Download of {specifier} was interrupted"))?;
+
+					Ok(Some(LoadResponse::Module {
+						content: content.to_vec().into(),
+						specifier,
+						maybe_headers: Some(maybe_headers),
+					}))
+				})
+			}
+			// such as `npm:` modules, which deno resolves on its own
+			_ => Box::pin(async move { Ok(Some(LoadResponse::External { specifier })) }),
+		}
+	}
+}
+
+pub async fn load_modules(entry_url: &Url, memory_loader: &mut MemoryLoader, bundler: &mut Bundler, allowlist: &ModuleAllowlist) -> Result<()> {
+	allowlist.check(entry_url)?;
+	// deno fetches every module in the graph while caching it, so modules that aren't allowed have to be found first
+	allowlist.check_graph(entry_url).await?;
 	cache_graph(entry_url).await?;
 
 	let info_graph = InfoGraph::load(entry_url).await?;
 
 	for module in info_graph.modules {
 		if let Some(error) = module.error {
-			return Err(anyhow!(error).context(format!("Failed to load {}", module.specifier)));
+			return Err(ModuleLoadError::classify(Some(module.specifier), error).into());
 		}
 
 		for dependency in module.dependencies {
@@ -45,6 +425,123 @@ pub async fn load_modules(entry_url: &Url, memory_loader: &mut MemoryLoader, bun
 	Ok(())
 }
 
+/// Register a runtime that was read from stdin, then load every module that it imports with `load_modules`. Deno can't read the
+/// runtime, so its imports are found by parsing it. Relative imports resolve against the current directory: the `stdin:` modules that
+/// they would otherwise point to are redirected to the `file:` modules of the same path.
+pub async fn load_stdin_modules(
+	entry_url: &Url,
+	source: &str,
+	memory_loader: &mut MemoryLoader,
+	bundler: &mut Bundler,
+	allowlist: &ModuleAllowlist,
+) -> Result<()> {
+	let current_dir_url = Url::from_directory_path(current_dir().context("failed to get the current working directory")?)
+		.map_err(|_| anyhow!("failed to convert the current working directory into a url"))?;
+	let mut loaded = HashSet::new();
+
+	memory_loader.add_source(
+		entry_url.clone(),
+		Source::Module {
+			specifier: entry_url.to_string(),
+			maybe_headers: None,
+			content: source.to_string(),
+		},
+	);
+	bundler.register_source(entry_url.clone(), source);
+
+	for (dependency, specifier) in get_stdin_dependencies(entry_url, source).await? {
+		let resolved = resolve_stdin_dependency(&specifier, &current_dir_url)?;
+
+		if resolved != specifier {
+			memory_loader.add_source(specifier, Source::Redirect(resolved.to_string()));
+		}
+
+		bundler.register_dependency(entry_url, dependency, resolved.clone());
+
+		if loaded.insert(resolved.clone()) {
+			load_modules(&resolved, memory_loader, bundler, allowlist).await?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Every import of the stdin runtime at `entry_url`, as it was written in `source`, along with the specifier that it resolves to
+async fn get_stdin_dependencies(entry_url: &Url, source: &str) -> Result<Vec<(String, Url)>> {
+	let mut loader = MemoryLoader::default();
+	let mut graph = ModuleGraph::new(GraphKind::All);
+
+	loader.add_source(
+		entry_url.clone(),
+		Source::Module {
+			specifier: entry_url.to_string(),
+			maybe_headers: None,
+			content: source.to_string(),
+		},
+	);
+
+	// only the runtime is in the loader, so its dependencies fail to load, but they are still resolved
+	graph.build(Vec::from([entry_url.clone()]), &loader, Default::default()).await;
+
+	let module = match graph.try_get(entry_url) {
+		Ok(Some(Module::Js(module))) => module,
+		Ok(_) => return Ok(Vec::new()),
+		Err(error) => return Err(ModuleLoadError::classify(Some(entry_url.clone()), error.to_string()).into()),
+	};
+
+	Ok(module
+		.dependencies
+		.iter()
+		.filter_map(|(dependency, resolution)| {
+			let specifier = resolution.maybe_code.maybe_specifier().or_else(|| resolution.maybe_type.maybe_specifier())?;
+
+			Some((dependency.clone(), specifier.clone()))
+		})
+		.collect())
+}
+
+/// Point an import of the stdin runtime that resolved to another `stdin:` module at the file of the same path in `current_dir_url`
+fn resolve_stdin_dependency(specifier: &Url, current_dir_url: &Url) -> Result<Url> {
+	if specifier.scheme() != "stdin" {
+		return Ok(specifier.clone());
+	}
+
+	current_dir_url
+		.join(specifier.path().trim_start_matches('/'))
+		.with_context(|| format!("failed to resolve {specifier} against the current directory"))
+}
+
+/// A module that only exists in memory. See `register_virtual_modules`
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone)]
+pub struct VirtualModule {
+	pub specifier: Url,
+	pub source: String,
+	/// Every import of this module, as it was written in the source, along with the specifier it resolves to
+	pub dependencies: Vec<(String, Url)>,
+}
+
+/// Register modules directly, as if they had been loaded by `load_modules`, so that the pipeline can be run without touching the
+/// filesystem or invoking deno
+#[cfg(any(test, feature = "test-util"))]
+pub fn register_virtual_modules(modules: &[VirtualModule], memory_loader: &mut MemoryLoader, bundler: &mut Bundler) {
+	for module in modules {
+		for (dependency, resolved) in &module.dependencies {
+			bundler.register_dependency(&module.specifier, dependency.clone(), resolved.clone());
+		}
+
+		bundler.register_source(module.specifier.clone(), module.source.clone());
+		memory_loader.add_source(
+			module.specifier.clone(),
+			Source::Module {
+				specifier: module.specifier.to_string(),
+				maybe_headers: None,
+				content: module.source.clone(),
+			},
+		);
+	}
+}
+
 /// A resolved module dependency
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InfoGraphModuleDependencyResolution {
@@ -129,15 +626,109 @@ async fn cache_graph(entry_url: &Url) -> Result<()> {
 		.env_clear()
 		.env("PATH", env::var("PATH").unwrap())
 		.stdout(Stdio::inherit())
-		.stdout(Stdio::inherit())
+		.stderr(Stdio::piped())
 		.stdin(Stdio::null());
 
-	let mut process = command.spawn()?;
-	let status = process.wait().await?;
+	let process = command.spawn()?;
+	let output = process.wait_with_output().await?;
 
-	if !status.success() {
-		return Err(anyhow!("failed to cache module graph due to `deno cache` exiting with a non-zero exit code"));
+	// stderr is captured so that the failure can be classified, but it is still shown to the user
+	let stderr = String::from_utf8_lossy(&output.stderr);
+	eprint!("{stderr}");
+
+	if !output.status.success() {
+		return Err(anyhow!(ModuleLoadError::classify(None, stderr.trim())))
+			.context("failed to cache module graph due to `deno cache` exiting with a non-zero exit code");
 	}
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::TempDir;
+
+	#[tokio::test]
+	async fn runtime_directories_resolve_to_their_manifest_entry() {
+		let directory = TempDir::new("runtime_manifest");
+		let entry = directory.write("src/main.ts", "export interface Button {}\n");
+		directory.write("mod.ts", "export interface Card {}\n");
+		directory.write(
+			"package.json",
+			r#"{ "exports": { ".": { "import": "./src/main.ts", "require": "./dist/main.cjs" } } }"#,
+		);
+
+		assert_eq!(resolve_runtime_dir(directory.path()).await.unwrap(), entry.canonicalize().unwrap());
+
+		directory.write("deno.json", r#"{ "exports": "./mod.ts" }"#);
+		let error = resolve_runtime_dir(directory.path()).await.unwrap_err().to_string();
+
+		assert!(error.contains("the runtime entry is ambiguous, because deno.json specifies"));
+	}
+
+	#[tokio::test]
+	async fn runtime_directories_without_a_manifest_entry_use_the_conventional_entry() {
+		let directory = TempDir::new("runtime_conventional");
+		directory.write("package.json", r#"{ "name": "runtime" }"#);
+
+		let missing = resolve_runtime_dir(directory.path()).await.unwrap_err().to_string();
+
+		assert!(missing.contains("no runtime entry was found"));
+
+		let entry = directory.write("index.ts", "export interface Button {}\n");
+
+		assert_eq!(resolve_runtime_dir(directory.path()).await.unwrap(), entry.canonicalize().unwrap());
+
+		directory.write("mod.ts", "export interface Card {}\n");
+		let ambiguous = resolve_runtime_dir(directory.path()).await.unwrap_err().to_string();
+
+		assert!(ambiguous.contains("is ambiguous, because it contains"));
+	}
+
+	#[tokio::test]
+	async fn stdin_runtime_imports_resolve_against_the_current_directory() {
+		let entry_url = Url::parse(STDIN_RUNTIME_URL).unwrap();
+		let current_dir_url = Url::parse("file:///project/").unwrap();
+		let source = "import { a } from './lib/a.ts'\nimport { b } from 'https://example.com/b.ts'\nexport const c = a + b\n";
+
+		let mut dependencies = get_stdin_dependencies(&entry_url, source)
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|(dependency, specifier)| (dependency, resolve_stdin_dependency(&specifier, &current_dir_url).unwrap().to_string()))
+			.collect::<Vec<_>>();
+		dependencies.sort();
+
+		assert_eq!(
+			dependencies,
+			[
+				("./lib/a.ts".to_string(), "file:///project/lib/a.ts".to_string()),
+				("https://example.com/b.ts".to_string(), "https://example.com/b.ts".to_string()),
+			]
+		);
+	}
+
+	#[tokio::test]
+	async fn stdin_runtime_with_a_syntax_error_fails_to_load() {
+		let entry_url = Url::parse(STDIN_RUNTIME_URL).unwrap();
+
+		assert!(get_stdin_dependencies(&entry_url, "export const = ").await.is_err());
+	}
+
+	#[tokio::test]
+	async fn http_imports_are_rejected_before_fetching_when_only_https_is_allowed() {
+		let directory = TempDir::new("module_allowlist");
+		let entry_url = Url::from_file_path(directory.write("mod.ts", "import { a } from './a.ts'\nexport const b = a\n")).unwrap();
+		let local_url = Url::from_file_path(directory.write("local.ts", "export const c = 1\n")).unwrap();
+		directory.write("a.ts", "export { a } from 'http://example.invalid/a.ts'\n");
+
+		let allowlist = ModuleAllowlist::new(&["https".to_string()], &[], &entry_url);
+		let error = allowlist.check_graph(&entry_url).await.unwrap_err();
+
+		// the module can't be fetched, so the failure can only come from the allowlist
+		assert!(format!("{error:#}").contains("http://example.invalid/a.ts"));
+		assert!(format!("{error:#}").contains("scheme, which is not allowed"));
+		assert!(allowlist.check_graph(&local_url).await.is_ok());
+	}
+}
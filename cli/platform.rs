@@ -3,8 +3,9 @@ use clap::ValueEnum;
 
 use crate::{
 	build::BuildOptions,
+	engine::BindingsWriter,
 	web::{build_web_static, run_web_static, BuildWebStaticParams, RunWebStaticParams},
-	writer::{FileWriter, Writer},
+	writer::Writer,
 };
 
 #[derive(Debug, Clone, Copy)]
@@ -12,16 +13,18 @@ pub struct RunParams<'a> {
 	pub build_options: BuildOptions<'a>,
 	pub web_port: u16,
 	pub reload: bool,
-	pub bindings_writer: &'a FileWriter,
+	pub bindings_writer: &'a BindingsWriter,
 	pub cache_writer: &'a Writer,
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct BuildParams<'a> {
 	pub build_options: BuildOptions<'a>,
-	pub bindings_writer: &'a FileWriter,
+	pub bindings_writer: &'a BindingsWriter,
 	pub output_writer: &'a Writer,
 	pub cache_writer: &'a Writer,
+	pub hashed_filenames: bool,
+	pub force: bool,
 }
 
 #[derive(Debug, ValueEnum, Clone, Default)]
@@ -67,6 +70,8 @@ impl Platform {
 					build_options: params.build_options,
 					bindings_writer: params.bindings_writer,
 					output_writer: params.output_writer,
+					hashed_filenames: params.hashed_filenames,
+					force: params.force,
 				})
 				.await
 			}
@@ -14,7 +14,26 @@ const safeJoin = (path) =>
 	path.map((item) => item.replaceAll(":", "\\:")).join("::");
 
 namespace.getActionId = (actionKey) => safeJoin(actionKey.actionPath);
-namespace.getEventId = (eventKey) => safeJoin(eventKey.eventPath);
+namespace.getEventId = (eventKey) =>
+	safeJoin(expandEventKey(eventKey).eventPath);
+
+// the prefixes that the engine has interned, by id. Ids are derived from the prefixes themselves, so they never change meaning
+namespace.pathPrefixes = new Map();
+
+// the engine keeps no prefixes, so keys must be expanded before they are sent back
+const expandEventKey = (eventKey) => {
+	if (eventKey.prefix === undefined) return eventKey;
+
+	const prefix = namespace.pathPrefixes.get(eventKey.prefix);
+	if (!prefix) {
+		throw new Error(
+			`Event key has the path prefix ${eventKey.prefix}, which the engine never sent`,
+		);
+	}
+
+	const { prefix: _, ...key } = eventKey;
+	return { ...key, eventPath: [...prefix, ...eventKey.eventPath] };
+};
 
 namespace.actionListeners = new Map();
 
@@ -38,9 +57,11 @@ namespace.sendEvent = async (key, data) => {
 
 	const response = await fetch(namespace.engineUrl, {
 		method: "POST",
+		// interning must be requested ahead of the events, so that engines that stream requests see it in time
 		body: JSON.stringify({
 			sessionId: namespace.sessionId,
-			events: [{ key, data }],
+			internPathPrefixes: true,
+			events: [{ key: expandEventKey(key), data }],
 		}),
 		headers: { "content-type": "application/json" },
 	})
@@ -56,9 +77,14 @@ namespace.sendEvent = async (key, data) => {
 	const actions = await response.json();
 
 	for (const action of actions) {
-		const listener = namespace.actionListeners.get(
-			namespace.getActionId(action.key),
-		);
+		const actionId = namespace.getActionId(action.key);
+		const listener = namespace.actionListeners.get(actionId);
+
+		// the engine may send root actions that this client predates, which shouldn't break it
+		if (!listener && actionId.startsWith("root_")) {
+			console.warn(`Ignoring unknown root action: ${actionId}`, action.data);
+			continue;
+		}
 		if (!listener) {
 			throw new Error(
 				`No action listener was specified for action: ${
@@ -81,6 +107,42 @@ namespace.registerActionListener = (key, listener) => {
 	};
 };
 
+namespace.registerActionListener(
+	{ actionPath: ["root_path_prefixes"] },
+	(prefixes) => {
+		for (const [id, path] of Object.entries(prefixes)) {
+			namespace.pathPrefixes.set(id, path);
+		}
+	},
+);
+
+// defaults for the errors that the engine reports. A runtime can replace any of these by registering its own listener
+namespace.registerActionListener(
+	{ actionPath: ["root_error"] },
+	(message) => console.error(`Engine error: ${message}`),
+);
+
+namespace.registerActionListener(
+	{ actionPath: ["root_request_error"] },
+	(message) => console.error(`The engine rejected a request: ${message}`),
+);
+
+namespace.registerActionListener(
+	{ actionPath: ["root_event_error"] },
+	({ index, message }) =>
+		console.error(`The engine rejected event ${index}: ${message}`),
+);
+
+namespace.registerActionListener(
+	{ actionPath: ["root_session_error"] },
+	(message) => {
+		console.error(`The engine rejected the session: ${message}`);
+
+		// a new session is started on the next event
+		namespace.sessionId = null;
+	},
+);
+
 namespace.mount = async () => {
 	let mountData = null;
 
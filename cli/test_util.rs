@@ -0,0 +1,44 @@
+use std::{
+	env,
+	fs::{create_dir_all, remove_dir_all, write},
+	path::{Path, PathBuf},
+	process,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The number of temporary directories that this process has created, so that every directory gets a unique name
+static TEMP_DIR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A directory that belongs to a single test. It is removed when dropped, even if the test panics
+pub struct TempDir {
+	path: PathBuf,
+}
+
+impl TempDir {
+	pub fn new(name: &str) -> TempDir {
+		let count = TEMP_DIR_COUNT.fetch_add(1, Ordering::SeqCst);
+		let path = env::temp_dir().join(format!("objection_{name}_{}_{count}", process::id()));
+		create_dir_all(&path).unwrap();
+
+		TempDir { path }
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Write `contents` to `name` within the directory, creating its parent directories as needed
+	pub fn write(&self, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+		let path = self.path.join(name);
+		create_dir_all(path.parent().unwrap()).unwrap();
+		write(&path, contents).unwrap();
+
+		path
+	}
+}
+
+impl Drop for TempDir {
+	fn drop(&mut self) {
+		let _ = remove_dir_all(&self.path);
+	}
+}
@@ -3,7 +3,7 @@ use anyhow::{Context, Result};
 use axum::{
 	extract::{ws::Message, Request, WebSocketUpgrade},
 	http::HeaderMap,
-	response::{Html, IntoResponse},
+	response::{Html, IntoResponse, Response},
 	routing::get,
 	serve, Router,
 };
@@ -11,6 +11,8 @@ use axum_extra::TypedHeader;
 use log::{debug, info, warn};
 use rand::random;
 use reqwest::StatusCode;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
 use std::{collections::HashMap, sync::Arc};
 use tokio::{net::TcpListener, select, sync::mpsc};
 use tower::ServiceExt;
@@ -18,10 +20,12 @@ use tower_http::services::ServeFile;
 use url::Url;
 
 use crate::{
-	build::{build, Build, BuildOptions},
+	asset_loader::{AccessibleAssets, AssetsLoaderWriteOptions},
+	build::{build, Build, BuildOptions, BuildStage},
 	diagnostic::DiagnosticList,
+	engine::BindingsWriter,
 	tcp_watcher::{TcpState, TcpWatcher},
-	writer::{FileWriter, Writer},
+	writer::{BuildCache, JsonStyle, Writer},
 };
 
 #[derive(Debug)]
@@ -44,7 +48,7 @@ pub struct RunWebStaticParams<'a> {
 	pub build_options: BuildOptions<'a>,
 	pub web_port: u16,
 	pub reload: bool,
-	pub bindings_writer: &'a FileWriter,
+	pub bindings_writer: &'a BindingsWriter,
 	pub cache_writer: &'a Writer,
 }
 
@@ -54,12 +58,18 @@ pub async fn run_web_static(params: RunWebStaticParams<'_>) -> Result<()> {
 		client_bundle,
 		bindings,
 		assets_loader,
+		..
 	} = build(&mut diagnostic_list, params.build_options).await?;
 
-	let index = get_index_html(params.build_options.engine_url, true);
+	// the dev server always runs every stage
+	let client_bundle = client_bundle.context("the runtime was not bundled")?;
+	let index = get_index_html(params.build_options.engine_url, "/bundle.js", true);
 	let (dev_connection_sender, mut dev_connection_receiver) = mpsc::channel(10);
 
-	let accessible_assets = Arc::new(assets_loader.download(params.cache_writer, &mut diagnostic_list).await?);
+	let accessible_assets = assets_loader
+		.download(params.cache_writer, &mut diagnostic_list, params.build_options.asset_headers)
+		.await?;
+	let accessible_assets = Arc::new(accessible_assets);
 	diagnostic_list.flush("download assets")?;
 
 	params.bindings_writer.write(bindings).await?;
@@ -125,14 +135,7 @@ pub async fn run_web_static(params: RunWebStaticParams<'_>) -> Result<()> {
 				})
 			}),
 		)
-		.fallback(|request: Request| async move {
-			let local_path = accessible_assets.get_local_path(request.uri().path());
-
-			match local_path {
-				Some(local_path) => ServeFile::new(local_path).oneshot(request).await.into_response(),
-				None => StatusCode::NOT_FOUND.into_response(),
-			}
-		});
+		.fallback(|request: Request| async move { serve_asset(&accessible_assets, request).await });
 
 	let listener = TcpListener::bind(("localhost", params.web_port))
 		.await
@@ -203,41 +206,243 @@ pub async fn run_web_static(params: RunWebStaticParams<'_>) -> Result<()> {
 	Ok(())
 }
 
+/// Serve the cached file of the asset at the web path of `request`. Browsers request plenty of paths that were never meant to be
+/// assets, such as `/favicon.ico`, so unknown paths are only logged at the debug level.
+async fn serve_asset(accessible_assets: &AccessibleAssets, request: Request) -> Response {
+	let local_path = accessible_assets.get_local_path(request.uri().path());
+
+	match local_path {
+		Some(local_path) => ServeFile::new(local_path).oneshot(request).await.into_response(),
+		None => {
+			debug!("no asset is registered at the web path {}", request.uri().path());
+
+			StatusCode::NOT_FOUND.into_response()
+		}
+	}
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct BuildWebStaticParams<'a> {
 	pub build_options: BuildOptions<'a>,
-	pub bindings_writer: &'a FileWriter,
+	pub bindings_writer: &'a BindingsWriter,
 	pub output_writer: &'a Writer,
+	/// Write the bundle as `bundle.<hash>.js`, along with an `entrypoints.json` that maps `bundle.js` to the hashed file
+	pub hashed_filenames: bool,
+	/// Rewrite every output, even if the build cache shows that it hasn't changed. See `BuildCache`
+	pub force: bool,
 }
 
 pub async fn build_web_static(params: BuildWebStaticParams<'_>) -> Result<()> {
 	let mut diagnostic_list = DiagnosticList::new();
+	let output = build(&mut diagnostic_list, params.build_options).await?;
+
+	write_web_static(&mut diagnostic_list, params, output).await
+}
+
+/// Write the outputs of a build. Outputs that the build cache shows to be unchanged are left untouched
+async fn write_web_static(diagnostic_list: &mut DiagnosticList, params: BuildWebStaticParams<'_>, output: Build) -> Result<()> {
 	let Build {
 		client_bundle,
 		bindings,
+		bundle_declarations,
 		assets_loader,
-	} = build(&mut diagnostic_list, params.build_options).await?;
+	} = output;
+
+	let mut cache = if params.force {
+		BuildCache::empty(params.output_writer)
+	} else {
+		BuildCache::load(params.output_writer).await
+	};
+
+	params.bindings_writer.write_cached(bindings, &mut cache).await?;
+
+	if let Some(client_bundle) = client_bundle {
+		let bundle_file = get_bundle_file(&client_bundle, params.hashed_filenames);
+
+		params
+			.output_writer
+			.write_file_cached(
+				"index.html",
+				get_index_html(params.build_options.engine_url, &format!("/{bundle_file}"), false),
+				&mut cache,
+			)
+			.await?;
+		params.output_writer.write_file_cached(&bundle_file, client_bundle, &mut cache).await?;
+
+		if params.hashed_filenames {
+			let entrypoints = get_entrypoints(&bundle_file);
+			let json_style = params.build_options.json_style.unwrap_or(JsonStyle::Compact);
+
+			params
+				.output_writer
+				.write_file_cached("entrypoints.json", json_style.serialize(&entrypoints)?, &mut cache)
+				.await?;
+		}
+	}
 
-	params.bindings_writer.write(bindings).await?;
-	params
-		.output_writer
-		.write_file("index.html", get_index_html(params.build_options.engine_url, false))
-		.await?;
-	params.output_writer.write_file("bundle.js", client_bundle).await?;
+	if let Some(bundle_declarations) = bundle_declarations {
+		params.output_writer.write_file_cached("bundle.d.ts", bundle_declarations, &mut cache).await?;
+	}
 
-	assets_loader.write(params.output_writer, &mut diagnostic_list, Default::default()).await?;
-	info!("Wrote assets");
+	if params.build_options.runs_stage(BuildStage::Assets) {
+		assets_loader
+			.write(
+				params.output_writer,
+				diagnostic_list,
+				AssetsLoaderWriteOptions {
+					headers: params.build_options.asset_headers,
+					cache: Some(&mut cache),
+					..Default::default()
+				},
+			)
+			.await?;
+		info!("Wrote assets");
+
+		diagnostic_list.flush("write assets")?;
+	}
 
-	diagnostic_list.flush("write assets")?;
+	cache.save().await?;
 
 	Ok(())
 }
 
 const STATIC_HTML: &str = include_str!("web_index.html");
+/// The number of hex characters of the bundle's sha256 that are placed in its filename
+const BUNDLE_HASH_LENGTH: usize = 16;
 const DEV_JS: &str = include_str!("dev.js");
 
-fn get_index_html(engine_url: &Url, is_dev: bool) -> String {
-	AhoCorasick::new(&["ENGINE_URL", "\"DEV_SCRIPT\""])
+/// The name that `client_bundle` is written under. Hashed names only change when the contents of the bundle do
+fn get_bundle_file(client_bundle: &str, hashed_filenames: bool) -> String {
+	if !hashed_filenames {
+		return "bundle.js".to_string();
+	}
+
+	let mut hasher = Sha256::new();
+	hasher.update(client_bundle.as_bytes());
+	let hash = hex::encode(hasher.finalize());
+
+	format!("bundle.{}.js", &hash[..BUNDLE_HASH_LENGTH])
+}
+
+/// Maps the logical name of each output to the hashed file that it was written to
+fn get_entrypoints(bundle_file: &str) -> Value {
+	json!({ "bundle.js": bundle_file })
+}
+
+fn get_index_html(engine_url: &Url, bundle_path: &str, is_dev: bool) -> String {
+	AhoCorasick::new(&["ENGINE_URL", "BUNDLE_PATH", "\"DEV_SCRIPT\""])
 		.unwrap()
-		.replace_all(STATIC_HTML, &[engine_url.as_str(), if is_dev { DEV_JS } else { "" }])
+		.replace_all(STATIC_HTML, &[engine_url.as_str(), bundle_path, if is_dev { DEV_JS } else { "" }])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		asset_loader::AssetsLoader,
+		build::get_options,
+		engine::{Bindings, Engine, EngineBindings},
+		test_util::TempDir,
+	};
+	use axum::body::{to_bytes, Body};
+	use std::fs::{metadata, read_to_string};
+	use tokio::sync::Semaphore;
+
+	async fn request_asset(accessible_assets: &AccessibleAssets, web_path: &str) -> Response {
+		serve_asset(accessible_assets, Request::builder().uri(web_path).body(Body::empty()).unwrap()).await
+	}
+
+	#[test]
+	fn hashed_bundle_files_are_named_after_their_contents() {
+		let bundle_file = get_bundle_file("console.log('hello')", true);
+		let hash = hex::encode(Sha256::digest(b"console.log('hello')"));
+
+		assert_eq!(bundle_file, format!("bundle.{}.js", &hash[..BUNDLE_HASH_LENGTH]));
+		assert_eq!(get_bundle_file("console.log('hello')", true), bundle_file);
+		assert_ne!(get_bundle_file("console.log('goodbye')", true), bundle_file);
+		assert_eq!(get_bundle_file("console.log('hello')", false), "bundle.js");
+		assert_eq!(get_entrypoints(&bundle_file), json!({ "bundle.js": bundle_file }));
+	}
+
+	#[test]
+	fn the_entry_is_pointed_at_the_full_engine_url() {
+		let engine_url = Url::parse("http://localhost:8000/api/ui").unwrap();
+		let index = get_index_html(&engine_url, "/bundle.js", false);
+
+		assert!(index.contains("engineUrl: new URL(\"http://localhost:8000/api/ui\")"));
+		assert!(index.contains("/bundle.js"));
+	}
+
+	#[tokio::test]
+	async fn the_dev_server_serves_known_web_paths_from_their_cached_files() {
+		let dir = TempDir::new("dev_server_assets");
+		let cached_path = dir.write("0f1e2d.svg", "<svg></svg>");
+		let accessible_assets = AccessibleAssets::new(HashMap::from([("/images/logo.svg".to_string(), cached_path.to_str().unwrap().to_string())]));
+
+		let known = request_asset(&accessible_assets, "/images/logo.svg").await;
+
+		assert_eq!(known.status(), StatusCode::OK);
+		assert_eq!(known.headers()["content-type"], "image/svg+xml");
+		assert_eq!(to_bytes(known.into_body(), usize::MAX).await.unwrap(), "<svg></svg>");
+
+		let unknown = request_asset(&accessible_assets, "/images/missing.svg").await;
+
+		assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+	}
+
+	/// Write the outputs of a build whose only asset has `asset_contents` into `directory`'s `out` folder, as `build_web_static` would
+	async fn write_build(directory: &TempDir, asset_contents: &str) {
+		directory.write("source/logo.svg", asset_contents);
+		let index_path = directory.write(
+			"source/assets.json",
+			json!([{ "sha256": hex::encode(Sha256::digest(asset_contents)), "localPath": "logo.svg", "webPath": "/logo.svg" }]).to_string(),
+		);
+		let mut diagnostic_list = DiagnosticList::new();
+		let mut assets_loader = AssetsLoader::default();
+		assets_loader.register_index_url(Url::from_file_path(index_path).unwrap());
+		assets_loader.load(&mut diagnostic_list, &Arc::new(Semaphore::new(1))).await.unwrap();
+
+		let runtime = Url::parse("stdin:///runtime.tsx").unwrap();
+		let bundler = Url::parse("file:///missing/bundler.ts").unwrap();
+		let output_writer = Writer::new(directory.path().join("out"));
+		let bindings_writer = BindingsWriter::new(Vec::from([(Engine::Rust, output_writer.clone().into_file_writer("bindings.rs"))]));
+		let params = BuildWebStaticParams {
+			build_options: get_options(&runtime, &bundler, "", &[BuildStage::Assets]),
+			bindings_writer: &bindings_writer,
+			output_writer: &output_writer,
+			hashed_filenames: false,
+			force: false,
+		};
+		let output = Build {
+			client_bundle: Some("console.log('hello')".to_string()),
+			bindings: EngineBindings(Vec::from([(Engine::Rust, Bindings::File("pub struct Button;\n".to_string()))])),
+			bundle_declarations: None,
+			assets_loader,
+		};
+
+		write_web_static(&mut diagnostic_list, params, output).await.unwrap();
+		diagnostic_list.flush("write assets").unwrap();
+	}
+
+	#[tokio::test]
+	async fn changing_only_an_asset_leaves_the_bundle_and_bindings_untouched() {
+		let directory = TempDir::new("build_cache");
+		let out = directory.path().join("out");
+		let asset_url = Url::from_file_path(directory.path().join("source/logo.svg")).unwrap();
+		let asset_output = Writer::new(&out).get_full_path(asset_url.to_string());
+		let modified = |name: &str| metadata(out.join(name)).unwrap().modified().unwrap();
+
+		write_build(&directory, "<svg></svg>").await;
+
+		let bundle_modified = modified("bundle.js");
+		let bindings_modified = modified("bindings.rs");
+
+		assert_eq!(read_to_string(&asset_output).unwrap(), "<svg></svg>");
+
+		write_build(&directory, "<svg><circle /></svg>").await;
+
+		assert_eq!(modified("bundle.js"), bundle_modified);
+		assert_eq!(modified("bindings.rs"), bindings_modified);
+		assert_eq!(read_to_string(&asset_output).unwrap(), "<svg><circle /></svg>");
+	}
 }
@@ -1,15 +1,132 @@
-use anyhow::{anyhow, Context, Result};
-use log::info;
-use reqwest::get;
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use log::{debug, info};
+use reqwest::{header::RANGE, Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_str, to_string, to_string_pretty, to_value};
 use sha2::{Digest, Sha256};
-use std::path::{Path, PathBuf};
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap},
+	fs::{read_dir, remove_dir_all, remove_file, symlink_metadata},
+	io,
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
 use tokio::{
-	fs::{create_dir_all, File},
-	io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+	fs::{create_dir_all, read_to_string, rename, File, OpenOptions},
+	io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
 };
 use url::Url;
 
+/// Files that are in the middle of being written. See `remove_pending_writes`
+static PENDING_WRITES: Mutex<BTreeSet<PathBuf>> = Mutex::new(BTreeSet::new());
+
+/// Whether a failed download could succeed if it were retried, which is only the case for timeouts and connection errors. Other
+/// failures, such as a 404, will happen again.
+pub fn is_transient_download_error(error: &anyhow::Error) -> bool {
+	error
+		.chain()
+		.find_map(|cause| cause.downcast_ref::<reqwest::Error>())
+		.map(|error| error.is_timeout() || error.is_connect())
+		.unwrap_or_default()
+}
+
+/// Remove every file that was being written when its write was cancelled, such as by `--timeout`. Writes go to a partial file that
+/// is only moved into place once it is complete, so an interrupted write never leaves a truncated output behind.
+pub fn remove_pending_writes() {
+	let mut pending_writes = PENDING_WRITES.lock().unwrap();
+
+	for path in pending_writes.iter() {
+		if let Err(error) = remove_file(path) {
+			debug!("failed to remove the partially written {path:?}: {error}");
+		}
+	}
+
+	pending_writes.clear();
+}
+
+/// The way that JSON artifacts are formatted
+#[derive(Debug, ValueEnum, Clone, Copy, PartialEq, Eq)]
+pub enum JsonStyle {
+	/// No whitespace. Smallest, so used by default for artifacts that are loaded at runtime
+	Compact,
+	/// Indented. Used by default for artifacts that are meant to be read or diffed
+	Pretty,
+}
+
+impl JsonStyle {
+	/// Serialize `value` in this style. Object keys are always sorted, so that output is stable between builds
+	pub fn serialize(self, value: &impl Serialize) -> Result<String> {
+		// without serde_json's `preserve_order` feature, objects are backed by a sorted map
+		let value = to_value(value)?;
+
+		Ok(match self {
+			JsonStyle::Compact => to_string(&value)?,
+			JsonStyle::Pretty => to_string_pretty(&value)?,
+		})
+	}
+}
+
+/// The file in an output directory that records what was written. See `BuildCache`
+const BUILD_CACHE_FILE_NAME: &str = ".objection_build_cache.json";
+
+/// Records the sha256 of every output that a build wrote, so that the next build can leave the outputs that didn't change untouched,
+/// preserving their modification times, and skip re-hashing assets that are already in place. Outputs are keyed by their full path.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BuildCache {
+	outputs: BTreeMap<PathBuf, String>,
+	#[serde(skip)]
+	path: PathBuf,
+}
+
+impl BuildCache {
+	/// Load the cache of `writer`'s directory. A missing or invalid cache is treated as empty, so every output will be written
+	pub async fn load(writer: &Writer) -> BuildCache {
+		let path = writer.get_full_path(BUILD_CACHE_FILE_NAME);
+		let cache = match read_to_string(&path).await {
+			Ok(text) => from_str::<BuildCache>(&text).unwrap_or_default(),
+			Err(_) => BuildCache::default(),
+		};
+
+		BuildCache { path, ..cache }
+	}
+
+	/// A cache for `writer`'s directory that ignores what was previously written, so that every output is written
+	pub fn empty(writer: &Writer) -> BuildCache {
+		BuildCache {
+			outputs: BTreeMap::new(),
+			path: writer.get_full_path(BUILD_CACHE_FILE_NAME),
+		}
+	}
+
+	/// Whether the file at `full_path` was last written with content of this hash, and still exists
+	pub fn is_fresh(&self, full_path: &Path, sha256: &[u8]) -> bool {
+		self.outputs.get(full_path) == Some(&hex::encode(sha256)) && full_path.exists()
+	}
+
+	pub fn record(&mut self, full_path: PathBuf, sha256: &[u8]) {
+		self.outputs.insert(full_path, hex::encode(sha256));
+	}
+
+	pub async fn save(&self) -> Result<()> {
+		let json = to_string_pretty(&self)?;
+
+		tokio::fs::write(&self.path, json)
+			.await
+			.with_context(|| format!("failed to write the build cache to {:?}", self.path))
+	}
+}
+
+/// The result of `Writer::download_file`
 #[derive(Debug)]
+pub struct Download {
+	pub sha256: Vec<u8>,
+	/// The number of bytes that were read from the source. When a partial download is resumed, this excludes the bytes that were
+	/// already on disk.
+	pub bytes_transferred: u64,
+}
+
+#[derive(Debug, Clone)]
 pub struct Writer {
 	directory: PathBuf,
 }
@@ -20,19 +137,46 @@ impl Writer {
 	}
 
 	pub async fn write_file(&self, path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> Result<()> {
-		let (file, joined_path) = self.create(path).await?;
+		let partial_path = get_partial_path(path.as_ref());
+		let joined_path = self.directory.join(path.as_ref());
+		let (file, joined_partial_path) = self.create(&partial_path).await?;
 		let mut writer = BufWriter::new(file);
 
+		PENDING_WRITES.lock().unwrap().insert(joined_partial_path.clone());
+
 		writer
 			.write_all(data.as_ref())
 			.await
-			.with_context(|| format!("failed to write {joined_path:?}"))?;
+			.with_context(|| format!("failed to write {joined_partial_path:?}"))?;
+		writer.flush().await.with_context(|| format!("failed to write {joined_partial_path:?}"))?;
+
+		rename(&joined_partial_path, &joined_path)
+			.await
+			.with_context(|| format!("failed to move {joined_partial_path:?} to {joined_path:?}"))?;
+		PENDING_WRITES.lock().unwrap().remove(&joined_partial_path);
 
 		info!("Wrote {joined_path:?}");
 
 		Ok(())
 	}
 
+	/// Write `data` to `path`, unless `cache` shows that the file already has that content
+	pub async fn write_file_cached(&self, path: impl AsRef<Path>, data: impl AsRef<[u8]>, cache: &mut BuildCache) -> Result<()> {
+		let full_path = self.get_full_path(path.as_ref());
+		let sha256 = Sha256::digest(data.as_ref()).to_vec();
+
+		if cache.is_fresh(&full_path, &sha256) {
+			debug!("Skipped writing {full_path:?}, because it hasn't changed");
+
+			return Ok(());
+		}
+
+		self.write_file(path, data).await?;
+		cache.record(full_path, &sha256);
+
+		Ok(())
+	}
+
 	pub async fn get_sha256(&self, path: impl AsRef<Path>) -> Result<Vec<u8>> {
 		let mut hasher = Sha256::new();
 		let joined_path = self.directory.join(path.as_ref());
@@ -54,54 +198,156 @@ impl Writer {
 		Ok(hasher.finalize().to_vec())
 	}
 
-	/// Download `url` to `path`, returning the sha256 of the downloaded file
-	pub async fn download_file(&self, path: impl AsRef<Path>, url: &Url) -> Result<Vec<u8>> {
+	/// Download `url` to `path`, returning the sha256 of the downloaded file and the number of bytes that were transferred. Remote
+	/// downloads are first written to a `.partial` file next to `path`. If a download is interrupted, the partial file is kept, and the
+	/// next download of the same path will request only the remaining bytes, falling back to a full download if the server does not
+	/// support ranges. `headers` are only sent for remote downloads.
+	pub async fn download_file(&self, path: impl AsRef<Path>, url: &Url, headers: &HashMap<String, String>) -> Result<Download> {
+		if url.scheme() != "file" {
+			return self.download_remote_file(path.as_ref(), url, headers).await;
+		}
+
 		let mut hasher = Sha256::new();
 		let (file, joined_path) = self.create(path).await?;
 		let mut writer = BufWriter::new(file);
+		let mut reader = BufReader::new(File::open(url.path()).await.with_context(|| format!("failed to open {}", url.path()))?);
+		let mut buffer = [0; 1024];
+		let mut bytes_transferred = 0;
 
-		if url.scheme() == "file" {
-			let mut reader = BufReader::new(File::open(url.path()).await.with_context(|| format!("failed to open {}", url.path()))?);
-			let mut buffer = [0; 1024];
+		loop {
+			let bytes_read = reader.read(&mut buffer).await.with_context(|| format!("failed to read {}", url.path()))?;
+			if bytes_read == 0 {
+				break;
+			}
 
-			loop {
-				let bytes_read = reader.read(&mut buffer).await.with_context(|| format!("failed to read {}", url.path()))?;
-				if bytes_read == 0 {
-					break;
-				}
+			hasher.update(&buffer[..bytes_read]);
+			bytes_transferred += bytes_read as u64;
 
-				hasher.update(&buffer[..bytes_read]);
+			writer
+				.write_all(&buffer[..bytes_read])
+				.await
+				.with_context(|| format!("failed to write to {joined_path:?}"))?;
+		}
 
-				writer
-					.write_all(&buffer[..bytes_read])
-					.await
-					.with_context(|| format!("failed to write to {joined_path:?}"))?;
-			}
+		info!("Copied {}", url.path());
+
+		writer.flush().await?;
+		Ok(Download {
+			sha256: hasher.finalize().to_vec(),
+			bytes_transferred,
+		})
+	}
+
+	async fn download_remote_file(&self, path: &Path, url: &Url, headers: &HashMap<String, String>) -> Result<Download> {
+		let partial_path = get_partial_path(path);
+		let joined_path = self.directory.join(path);
+		let joined_partial_path = self.directory.join(&partial_path);
+
+		let mut hasher = Sha256::new();
+		let existing_length = match File::open(&joined_partial_path).await {
+			Ok(file) => hash_reader(BufReader::new(file), &mut hasher)
+				.await
+				.with_context(|| format!("failed to read {joined_partial_path:?}"))?,
+			Err(_) => 0,
+		};
+
+		let client = Client::new();
+		let get = || {
+			headers
+				.iter()
+				.fold(client.get(url.clone()), |request, (name, value)| request.header(name.as_str(), value.as_str()))
+		};
+		let mut request = get();
+
+		if existing_length > 0 {
+			request = request.header(RANGE, format!("bytes={existing_length}-"));
+		}
+
+		let mut response = request.send().await.with_context(|| format!("Failed to fetch {url}"))?;
+
+		if existing_length > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+			debug!("server rejected the range request for {url}; restarting the download");
+
+			response = get().send().await.with_context(|| format!("Failed to fetch {url}"))?;
+		}
+
+		let response_status = response.status();
+		let mut response = response.error_for_status().with_context(|| format!("Failed to fetch {url}"))?;
+
+		let file = if existing_length > 0 && response_status == StatusCode::PARTIAL_CONTENT {
+			info!("Resuming download of {url} at byte {existing_length}");
 
-			info!("Copied {}", url.path());
+			OpenOptions::new()
+				.append(true)
+				.open(&joined_partial_path)
+				.await
+				.with_context(|| format!("failed to open {joined_partial_path:?} for appending"))?
 		} else {
-			let mut response = get(url.clone()).await.with_context(|| format!("Failed to fetch {url}"))?;
+			hasher = Sha256::new();
 
-			while let Some(chunk) = response.chunk().await? {
-				hasher.update(&chunk[..]);
+			self.create(&partial_path).await?.0
+		};
+		let mut writer = BufWriter::new(file);
+		let mut bytes_transferred = 0;
 
-				writer
-					.write_all(&chunk[..])
-					.await
-					.with_context(|| format!("failed to write to {joined_path:?}"))?;
-			}
+		while let Some(chunk) = response.chunk().await.with_context(|| format!("Download of {url} was interrupted"))? {
+			hasher.update(&chunk[..]);
+			bytes_transferred += chunk.len() as u64;
 
-			info!("Downloaded {url}");
+			writer
+				.write_all(&chunk[..])
+				.await
+				.with_context(|| format!("failed to write to {joined_partial_path:?}"))?;
 		}
 
 		writer.flush().await?;
-		Ok(hasher.finalize().to_vec())
+		rename(&joined_partial_path, &joined_path)
+			.await
+			.with_context(|| format!("failed to move {joined_partial_path:?} to {joined_path:?}"))?;
+
+		info!("Downloaded {url}");
+
+		Ok(Download {
+			sha256: hasher.finalize().to_vec(),
+			bytes_transferred,
+		})
 	}
 
 	pub fn get_full_path(&self, path: impl AsRef<Path>) -> PathBuf {
 		self.directory.join(path.as_ref())
 	}
 
+	pub fn get_directory(&self) -> &Path {
+		&self.directory
+	}
+
+	/// Remove the directory of this writer and everything in it, returning the number of bytes that were reclaimed. For safety, the
+	/// directory must be inside of `root`, and can't be `root` itself. With `dry_run`, nothing is removed.
+	pub fn clean(&self, root: &Path, dry_run: bool) -> Result<u64> {
+		let directory = match self.directory.canonicalize() {
+			Ok(directory) => directory,
+			// nothing was ever written
+			Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(0),
+			Err(error) => return Err(error).with_context(|| format!("failed to resolve {:?}", self.directory)),
+		};
+		let root = root.canonicalize().with_context(|| format!("failed to resolve {root:?}"))?;
+
+		if directory == root || !directory.starts_with(&root) {
+			bail!("refusing to remove {directory:?}, which is not inside of {root:?}");
+		}
+
+		let size = get_size(&directory).with_context(|| format!("failed to measure {directory:?}"))?;
+
+		if dry_run {
+			info!("Would remove {directory:?} ({size} bytes)");
+		} else {
+			remove_dir_all(&directory).with_context(|| format!("failed to remove {directory:?}"))?;
+			info!("Removed {directory:?} ({size} bytes)");
+		}
+
+		Ok(size)
+	}
+
 	pub fn into_file_writer(self, path: impl Into<PathBuf>) -> FileWriter {
 		FileWriter {
 			writer: self,
@@ -140,4 +386,185 @@ impl FileWriter {
 	pub async fn write(&self, data: impl AsRef<[u8]>) -> Result<()> {
 		self.writer.write_file(&self.path, data).await
 	}
+
+	pub async fn write_cached(&self, data: impl AsRef<[u8]>, cache: &mut BuildCache) -> Result<()> {
+		self.writer.write_file_cached(&self.path, data, cache).await
+	}
+
+	/// Write to `path` within this writer's path, treating it as a directory
+	pub async fn write_child(&self, path: impl AsRef<Path>, data: impl AsRef<[u8]>) -> Result<()> {
+		self.writer.write_file(self.path.join(path), data).await
+	}
+
+	/// Like `FileWriter::write_child`, but skips the write if the file is unchanged. See `Writer::write_file_cached`
+	pub async fn write_child_cached(&self, path: impl AsRef<Path>, data: impl AsRef<[u8]>, cache: &mut BuildCache) -> Result<()> {
+		self.writer.write_file_cached(self.path.join(path), data, cache).await
+	}
+}
+
+fn get_partial_path(path: &Path) -> PathBuf {
+	let mut partial_path = path.as_os_str().to_owned();
+	partial_path.push(".partial");
+
+	PathBuf::from(partial_path)
+}
+
+/// The total size of the files at `path`. Symlinks are not followed, as only the links themselves would be removed
+fn get_size(path: &Path) -> io::Result<u64> {
+	let metadata = symlink_metadata(path)?;
+
+	if !metadata.is_dir() {
+		return Ok(metadata.len());
+	}
+
+	let mut size = 0;
+
+	for entry in read_dir(path)? {
+		size += get_size(&entry?.path())?;
+	}
+
+	Ok(size)
+}
+
+/// Feed all of `reader` into `hasher`, returning the number of bytes read
+async fn hash_reader(mut reader: impl AsyncRead + Unpin, hasher: &mut Sha256) -> Result<u64> {
+	let mut buffer = [0; 1024];
+	let mut length = 0;
+
+	loop {
+		let bytes_read = reader.read(&mut buffer).await?;
+		if bytes_read == 0 {
+			break;
+		}
+
+		hasher.update(&buffer[..bytes_read]);
+		length += bytes_read as u64;
+	}
+
+	Ok(length)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::test_util::TempDir;
+	use serde_json::Value;
+	use std::{fs, sync::Arc};
+	use tokio::{net::TcpListener, task::JoinHandle};
+
+	const CONTENT: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+	/// Serve `CONTENT` at the returned url, honoring `Range` headers if `supports_ranges` is set. The head of every request is recorded,
+	/// lowercased, in the returned list
+	async fn serve_content(supports_ranges: bool) -> (Url, Arc<Mutex<Vec<String>>>, JoinHandle<()>) {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = Url::parse(&format!("http://{}/fox.txt", listener.local_addr().unwrap())).unwrap();
+		let requests = Arc::new(Mutex::new(Vec::new()));
+		let server_requests = requests.clone();
+
+		let server = tokio::spawn(async move {
+			loop {
+				let (mut stream, _) = listener.accept().await.unwrap();
+				let mut buffer = [0; 1024];
+				let bytes_read = stream.read(&mut buffer).await.unwrap();
+				let head = String::from_utf8_lossy(&buffer[..bytes_read]).to_lowercase();
+				let start = head
+					.lines()
+					.find_map(|line| line.strip_prefix("range: bytes="))
+					.and_then(|range| range.trim_end_matches('-').parse::<usize>().ok())
+					.filter(|_| supports_ranges);
+
+				server_requests.lock().unwrap().push(head);
+
+				let (status, extra_header, body) = match start {
+					Some(start) => (
+						"206 Partial Content",
+						format!("Content-Range: bytes {start}-{}/{}\r\n", CONTENT.len() - 1, CONTENT.len()),
+						&CONTENT[start..],
+					),
+					None => ("200 OK", String::new(), CONTENT),
+				};
+				let head = format!("HTTP/1.1 {status}\r\nContent-Length: {}\r\n{extra_header}Connection: close\r\n\r\n", body.len());
+
+				stream.write_all(head.as_bytes()).await.unwrap();
+				stream.write_all(body).await.unwrap();
+			}
+		});
+
+		(url, requests, server)
+	}
+
+	#[derive(Serialize)]
+	#[serde(rename_all = "camelCase")]
+	struct ManifestEntry {
+		web_path: &'static str,
+		local_path: &'static str,
+		sha256: &'static str,
+	}
+
+	#[test]
+	fn both_json_styles_serialize_the_same_data_with_sorted_keys() {
+		let manifest = [ManifestEntry {
+			web_path: "/logo.svg",
+			local_path: "assets/logo.svg",
+			sha256: "00",
+		}];
+		let compact = JsonStyle::Compact.serialize(&manifest).unwrap();
+		let pretty = JsonStyle::Pretty.serialize(&manifest).unwrap();
+
+		assert_eq!(compact, r#"[{"localPath":"assets/logo.svg","sha256":"00","webPath":"/logo.svg"}]"#);
+		assert!(pretty.contains('\n'));
+		assert!(pretty.find("localPath").unwrap() < pretty.find("webPath").unwrap());
+		assert_eq!(from_str::<Value>(&compact).unwrap(), from_str::<Value>(&pretty).unwrap());
+	}
+
+	#[tokio::test]
+	async fn interrupted_downloads_resume_from_the_partial_file() {
+		let (url, requests, server) = serve_content(true).await;
+		let directory = TempDir::new("resumed_download");
+		directory.write("fox.txt.partial", &CONTENT[..10]);
+
+		let download = Writer::new(directory.path()).download_file("fox.txt", &url, &HashMap::new()).await.unwrap();
+		server.abort();
+
+		assert_eq!(requests.lock().unwrap().len(), 1);
+		assert!(requests.lock().unwrap()[0].contains("range: bytes=10-"));
+		assert_eq!(download.bytes_transferred, CONTENT.len() as u64 - 10);
+		assert_eq!(download.sha256, Sha256::digest(CONTENT).to_vec());
+		assert_eq!(fs::read(directory.path().join("fox.txt")).unwrap(), CONTENT);
+		assert!(!directory.path().join("fox.txt.partial").exists());
+	}
+
+	#[tokio::test]
+	async fn downloads_restart_when_the_server_ignores_the_range() {
+		let (url, _, server) = serve_content(false).await;
+		let directory = TempDir::new("restarted_download");
+		directory.write("fox.txt.partial", b"stale bytes");
+
+		let download = Writer::new(directory.path()).download_file("fox.txt", &url, &HashMap::new()).await.unwrap();
+		server.abort();
+
+		assert_eq!(download.bytes_transferred, CONTENT.len() as u64);
+		assert_eq!(download.sha256, Sha256::digest(CONTENT).to_vec());
+		assert_eq!(fs::read(directory.path().join("fox.txt")).unwrap(), CONTENT);
+	}
+
+	#[tokio::test]
+	async fn configured_headers_are_sent_with_remote_downloads() {
+		let (url, requests, server) = serve_content(false).await;
+		let directory = TempDir::new("download_headers");
+		let headers = HashMap::from([
+			("X-Api-Key".to_string(), "secret".to_string()),
+			("Referer".to_string(), "https://example.com/".to_string()),
+		]);
+
+		Writer::new(directory.path()).download_file("fox.txt", &url, &headers).await.unwrap();
+		server.abort();
+
+		let requests = requests.lock().unwrap();
+
+		assert_eq!(requests.len(), 1);
+		assert!(requests[0].contains("x-api-key: secret\r\n"));
+		assert!(requests[0].contains("referer: https://example.com/\r\n"));
+	}
 }
@@ -1,13 +1,41 @@
 use proc_macro2::TokenStream;
 use quote::quote;
-use syn::{parse2, ItemEnum};
+use syn::{parse2, Ident, ItemEnum};
 
-#[proc_macro_derive(EventSymbol)]
+/// Implements `objection::EventSymbol` for an enum. Add `#[event_symbol(json)]` to use the readable json encoding, or
+/// `#[event_symbol(base64url_bincode)]` to use the compact base64url encoding, instead of the default hex-bincode encoding.
+#[proc_macro_derive(EventSymbol, attributes(event_symbol))]
 pub fn derive_event_symbol(tokens: proc_macro::TokenStream) -> proc_macro::TokenStream {
 	let enum_repr = parse2::<ItemEnum>(TokenStream::from(tokens)).expect("Expected an enum");
 	let name_ident = enum_repr.ident;
+	let mut encoding_tokens = None;
+
+	for attr in &enum_repr.attrs {
+		if !attr.path().is_ident("event_symbol") {
+			continue;
+		}
+
+		let encoding = attr.parse_args::<Ident>().expect("Expected an encoding, such as `#[event_symbol(json)]`");
+
+		encoding_tokens = Some(match encoding.to_string().as_str() {
+			"json" => quote! { objection::EventSymbolEncoding::Json },
+			"hex_bincode" => quote! { objection::EventSymbolEncoding::HexBincode },
+			"base64url_bincode" => quote! { objection::EventSymbolEncoding::Base64UrlBincode },
+			other => panic!("Unknown event symbol encoding `{other}`. Expected `json`, `hex_bincode`, or `base64url_bincode`"),
+		});
+	}
+
+	let encoding_fn = encoding_tokens.map(|encoding| {
+		quote! {
+			fn encoding() -> objection::EventSymbolEncoding {
+				#encoding
+			}
+		}
+	});
 
 	proc_macro::TokenStream::from(quote! {
-		impl objection::EventSymbol for #name_ident {}
+		impl objection::EventSymbol for #name_ident {
+			#encoding_fn
+		}
 	})
 }
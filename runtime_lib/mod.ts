@@ -16,7 +16,7 @@ export function getComponentRenderer<Params, Return>(component: Component): { fu
  *
  * @feature_event_key
  */
-export type EventKey<T> = { eventPath: string[]; debugSymbol?: string }
+export type EventKey<T> = { eventPath: string[]; debugSymbol?: string; version?: number; prefix?: string }
 
 /**
  * An action that could be triggered, where `T` is the data that the action will contain
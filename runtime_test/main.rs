@@ -1,11 +1,17 @@
 use anyhow::{anyhow, Error, Result};
 use async_worker::{Queue, QueueBuilder, Worker};
-use axum::{extract::State, routing::post, Json, Router};
+use axum::{
+	extract::{ConnectInfo, State},
+	http::HeaderMap,
+	routing::post,
+	Json, Router,
+};
 use basic_ui::get_basic_ui;
 use bindings::ThemeManager;
 use log::info;
-use objection::{handle_request, RootUi, UiResponse};
+use objection::{handle_request_with_context, RequestContext, RootUi, SessionId, UiResponse};
 use serde_json::Value;
+use std::net::SocketAddr;
 use theme::get_theme;
 use tokio::net::TcpListener;
 use tower_http::cors::CorsLayer;
@@ -49,9 +55,13 @@ async fn main() {
 	let app = Router::new()
 		.route(
 			"/ui",
-			post(move |State(queue): State<_>, Json(body): Json<Value>| async move {
-				Json(handle_request(body, |session_id, ui| cycle_event_loop(queue, session_id, ui)).await)
-			}),
+			post(
+				move |State(queue): State<_>, ConnectInfo(remote_addr): ConnectInfo<SocketAddr>, headers: HeaderMap, Json(body): Json<Value>| async move {
+					let context = get_request_context(&headers, remote_addr);
+
+					Json(handle_request_with_context(body, context, |session_id, ui| cycle_event_loop(queue, session_id, ui)).await)
+				},
+			),
 		)
 		.layer(CorsLayer::very_permissive())
 		.with_state(queue);
@@ -59,10 +69,22 @@ async fn main() {
 	let listener = TcpListener::bind(("localhost", port)).await.unwrap();
 	info!("listening at http://localhost:{port}");
 
-	axum::serve(listener, app).await.unwrap();
+	axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await.unwrap();
+}
+
+fn get_request_context(headers: &HeaderMap, remote_addr: SocketAddr) -> RequestContext {
+	let mut context = RequestContext::new().with_remote_addr(remote_addr.to_string());
+
+	for (name, value) in headers {
+		if let Ok(value) = value.to_str() {
+			context = context.with_header(name, value);
+		}
+	}
+
+	context
 }
 
-async fn cycle_event_loop(queue: &Queue<Session>, session_id: String, ui: RootUi) -> Result<UiResponse> {
+async fn cycle_event_loop(queue: &Queue<Session>, session_id: SessionId, ui: RootUi) -> Result<UiResponse> {
 	queue.enqueue(&session_id, ui).await.map_err(map_async_worker_error)?;
 	queue.poll(&session_id).await.map_err(map_async_worker_error)?
 }
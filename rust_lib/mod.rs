@@ -1,8 +1,26 @@
+mod stream;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures_util::io::AsyncRead;
 use log::error;
-use rand::random;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use serde_json::{from_value, json, to_value, Value};
-use std::{fmt::Display, future::Future, marker::PhantomData};
+use rand::{random, rngs::StdRng, Rng, SeedableRng};
+use serde::{de::DeserializeOwned, ser::SerializeStruct, Deserialize, Serialize, Serializer};
+use serde_json::{from_str, from_value, json, to_value, Value};
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, HashMap},
+	fmt::{self, Display},
+	future::{poll_fn, Future},
+	marker::PhantomData,
+	ops::Deref,
+	pin::pin,
+	sync::{
+		atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering},
+		Arc, Mutex,
+	},
+	thread::LocalKey,
+};
+use stream::{StreamError, ValueReader};
 use thiserror::Error;
 
 enum EventScope<'a> {
@@ -14,8 +32,11 @@ pub struct Client<'a> {
 	current_event_scope: Vec<EventScope<'a>>,
 
 	event_path: &'a [String],
-	event_data: &'a mut Option<Value>,
-	actions: &'a mut Vec<Value>,
+	/// The protocol version of the key that the incoming event was sent with. See `set_protocol_version`
+	event_version: Option<u32>,
+	event_data: &'a RefCell<Option<Value>>,
+	actions: &'a RefCell<Vec<Value>>,
+	context: &'a RequestContext,
 }
 
 pub struct Ui<'a> {
@@ -25,19 +46,24 @@ pub struct Ui<'a> {
 impl<'a> Ui<'a> {
 	pub fn event_key<T>(&self) -> EventKey<T> {
 		EventKey {
-			event_path: self
-				.current_event_scope
-				.iter()
-				.map(|scope| match scope {
-					EventScope::Owned(symbol) => symbol.to_string(),
-					EventScope::Borrowed(scope) => scope.to_string(),
-				})
-				.collect(),
+			event_path: self.current_path(),
 			debug_symbol: None,
+			version: protocol_version(),
 			_marker: PhantomData,
 		}
 	}
 
+	/// The encoded symbols of every scope that this ui is in, starting from the root. This is the path that `Ui::event_key` will use
+	pub fn current_path(&self) -> Vec<String> {
+		self.current_event_scope
+			.iter()
+			.map(|scope| match scope {
+				EventScope::Owned(symbol) => symbol.to_string(),
+				EventScope::Borrowed(scope) => scope.to_string(),
+			})
+			.collect()
+	}
+
 	pub fn scope(&'a self, symbol: impl EventSymbol) -> Ui<'a> {
 		let mut current_event_scope = borrow_scope(&self.current_event_scope);
 
@@ -55,6 +81,11 @@ impl<'a> Ui<'a> {
 }
 
 impl<'a> Client<'a> {
+	/// Metadata about the http request that carried this event. See `handle_request_with_context`
+	pub fn context(&self) -> &RequestContext {
+		self.context
+	}
+
 	pub fn ui(&'a self) -> Ui<'a> {
 		Ui {
 			current_event_scope: borrow_scope(&self.current_event_scope),
@@ -62,11 +93,11 @@ impl<'a> Client<'a> {
 	}
 
 	fn take_current_event_data(&mut self) -> Option<Value> {
-		self.event_data.take()
+		self.event_data.borrow_mut().take()
 	}
 
 	fn push_action<T: Serialize>(&mut self, action: Action<T>) {
-		self.actions.push(to_value(&action).unwrap());
+		self.actions.borrow_mut().push(to_value(&action).unwrap());
 	}
 }
 
@@ -89,66 +120,219 @@ pub struct UiResponse {
 	actions: Vec<Value>,
 }
 
+impl UiResponse {
+	/// The actions that will be sent to the client, in order. See `Action::from_value` for turning these back into typed actions
+	pub fn actions(&self) -> &[Value] {
+		&self.actions
+	}
+}
+
+/// The root of a single event's handling. Any number of clients can be created from it at once, and the actions that they emit are
+/// all collected into the response. The event data is shared between them, so it can still only be taken once.
 pub struct RootUi {
 	event_path: Vec<String>,
-	event_data: Option<Value>,
-	actions: Vec<Value>,
+	event_version: Option<u32>,
+	event_data: RefCell<Option<Value>>,
+	actions: RefCell<Vec<Value>>,
+	context: Arc<RequestContext>,
 }
 
 impl RootUi {
-	fn from_event(event: RawEvent) -> RootUi {
+	fn from_event(event: RawEvent, context: Arc<RequestContext>) -> RootUi {
 		RootUi {
 			event_path: event.key.event_path,
-			event_data: Some(event.data),
-			actions: Vec::new(),
+			event_version: event.key.version,
+			event_data: RefCell::new(Some(event.data)),
+			actions: RefCell::new(Vec::new()),
+			context,
 		}
 	}
 
-	pub fn get_client(&mut self) -> Client {
+	/// Metadata about the http request that carried this event. See `handle_request_with_context`
+	pub fn context(&self) -> &RequestContext {
+		&self.context
+	}
+
+	/// The path of the incoming event. Useful for routing before a client is built and event data is taken
+	pub fn event_path(&self) -> &[String] {
+		&self.event_path
+	}
+
+	/// The first symbol of the incoming event path, if there is one
+	pub fn event_head(&self) -> Option<&str> {
+		self.event_path.first().map(|symbol| symbol.as_str())
+	}
+
+	pub fn get_client(&self) -> Client {
 		Client {
 			current_event_scope: Vec::from([EventScope::Owned("main".into())]),
 
 			event_path: &self.event_path,
-			event_data: &mut self.event_data,
-			actions: &mut self.actions,
+			event_version: self.event_version,
+			event_data: &self.event_data,
+			actions: &self.actions,
+			context: &self.context,
 		}
 	}
 
 	pub fn take_mount_event(&mut self) -> Result<Option<MountEventData>, TakeMountEventError> {
 		let first_event = self.event_path.first().ok_or(TakeMountEventError::EmptyEventPath)?;
 
-		Ok(if first_event == "root_app_ready" {
-			Some(from_value(self.event_data.take().ok_or(TakeMountEventError::NoEventData)?).map_err(|inner| {
-				TakeMountEventError::FailedToDeserializeMountData {
-					serde_error: inner.to_string(),
-				}
-			})?)
+		Ok(if first_event == APP_READY_EVENT {
+			Some(
+				from_value(self.event_data.get_mut().take().ok_or(TakeMountEventError::NoEventData)?).map_err(|inner| {
+					TakeMountEventError::FailedToDeserializeMountData {
+						serde_error: inner.to_string(),
+					}
+				})?,
+			)
 		} else {
 			None
 		})
 	}
 
-	pub fn set_root_ui(&mut self, ui: impl IntoComponentIndex) {
+	pub fn set_root_ui(&self, ui: impl IntoComponentIndex) {
 		self.actions
-			.push(json!({ "key": { "actionPath": ["root_mount"] }, "data": ui.into_index().to_value() }));
+			.borrow_mut()
+			.push(json!({ "key": { "actionPath": [MOUNT_ACTION] }, "data": ui.into_index().to_value() }));
 	}
 
+	/// Finish handling this event. Actions are stably sorted by their priority (see `ActionKey::emit_with_priority`), so actions of
+	/// the same priority retain the order in which they were emitted
 	pub fn into_response(self) -> UiResponse {
-		UiResponse { actions: self.actions }
+		let mut actions = self.actions.into_inner();
+
+		actions.sort_by_key(|action| {
+			action
+				.get("priority")
+				.and_then(|priority| priority.as_u64())
+				.map(|priority| priority.min(u8::MAX as u64) as u8)
+				.unwrap_or(DEFAULT_ACTION_PRIORITY)
+		});
+
+		UiResponse { actions }
+	}
+}
+
+/// The event path that the runtime sends once it has loaded, so that the root ui can be mounted. See `RootUi::take_mount_event`
+pub const APP_READY_EVENT: &str = "root_app_ready";
+
+/// The action path that the root ui is mounted on. See `RootUi::set_root_ui`
+pub const MOUNT_ACTION: &str = "root_mount";
+
+/// The action path that handler errors are sent to, unless they are routed elsewhere. See `IntoErrorAction`
+pub const ERROR_ACTION: &str = "root_error";
+
+/// The action path that a request is rejected with when its body is malformed, such as when `sessionId` is missing. None of the
+/// request's events are handled, so the client may retry the whole request. The data is the error message.
+pub const REQUEST_ERROR_ACTION: &str = "root_request_error";
+
+/// The action path that a single malformed event is reported with. The other events in the request are still handled, so the client
+/// should drop only this event. The data is an object with the `index` of the event in the request and the error `message`.
+pub const EVENT_ERROR_ACTION: &str = "root_event_error";
+
+/// The action path that the interned path prefixes of a response are sent on, ahead of every other action. The data is an object that
+/// maps the id of each prefix that the response uses to the path segments that it stands for. See `set_path_prefix_interning`
+pub const PATH_PREFIXES_ACTION: &str = "root_path_prefixes";
+
+/// The action path that a request is rejected with when its session id is refused by the validator (see `set_session_id_validator`).
+/// None of the request's events are handled. The data is the error message.
+pub const SESSION_ERROR_ACTION: &str = "root_session_error";
+
+/// Every event path that the runtime itself sends, rather than a component
+pub const ROOT_EVENTS: &[&str] = &[APP_READY_EVENT];
+
+/// Every action path that the engine itself sends, rather than a component
+pub const ROOT_ACTIONS: &[&str] = &[
+	MOUNT_ACTION,
+	ERROR_ACTION,
+	REQUEST_ERROR_ACTION,
+	SESSION_ERROR_ACTION,
+	EVENT_ERROR_ACTION,
+	PATH_PREFIXES_ACTION,
+];
+
+/// The id of the session that sent a request. Derefs to `String`, so it can be used wherever the id was used as a string
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SessionId(String);
+
+impl SessionId {
+	/// Check `id` with the validator set by `set_session_id_validator`. If it is refused, the response that rejects the request is
+	/// returned as the error.
+	fn validate(id: String) -> Result<SessionId, Value> {
+		let validator = SESSION_ID_VALIDATOR.lock().unwrap().clone();
+
+		match validator {
+			Some(validator) if !validator(&id) => {
+				error!("refusing a request with an invalid session id");
+
+				Err(json!([ErrorAction::new([SESSION_ERROR_ACTION], "Invalid session id").into_value()]))
+			}
+			_ => Ok(SessionId(id)),
+		}
+	}
+
+	pub fn into_inner(self) -> String {
+		self.0
+	}
+}
+
+impl Deref for SessionId {
+	type Target = String;
+
+	fn deref(&self) -> &String {
+		&self.0
+	}
+}
+
+impl Display for SessionId {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.0)
 	}
 }
 
+impl From<SessionId> for String {
+	fn from(id: SessionId) -> String {
+		id.0
+	}
+}
+
+type SessionIdValidator = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+static SESSION_ID_VALIDATOR: Mutex<Option<SessionIdValidator>> = Mutex::new(None);
+
+/// Refuse requests whose session id doesn't pass `validator`, such as ids that are malformed or were never issued. Refused requests are
+/// answered with `SESSION_ERROR_ACTION`, and none of their events reach the handler. By default, every session id is accepted.
+pub fn set_session_id_validator(validator: impl Fn(&str) -> bool + Send + Sync + 'static) {
+	*SESSION_ID_VALIDATOR.lock().unwrap() = Some(Arc::new(validator));
+}
+
+/// Accept every session id again. See `set_session_id_validator`
+pub fn clear_session_id_validator() {
+	*SESSION_ID_VALIDATOR.lock().unwrap() = None;
+}
+
 #[derive(Debug, Error)]
 #[error("Invalid request body. {serde_error}")]
 struct RequestError {
 	serde_error: String,
 }
 
+impl RequestError {
+	fn into_response(self) -> Value {
+		json!([ErrorAction::new([REQUEST_ERROR_ACTION], self.to_string()).into_value()])
+	}
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct RawRequest {
 	session_id: String,
-	events: Vec<RawEvent>,
+	/// Events are parsed individually, so that a malformed event doesn't fail the whole request. See `parse_event`
+	events: Vec<Value>,
+	/// Whether the client can expand interned path prefixes. See `set_path_prefix_interning`
+	#[serde(default)]
+	intern_path_prefixes: bool,
 }
 
 #[derive(Deserialize)]
@@ -162,222 +346,1010 @@ struct RawEvent {
 #[serde(rename_all = "camelCase")]
 struct RawEventKey {
 	event_path: Vec<String>,
+	#[serde(default)]
+	version: Option<u32>,
+	/// Set if the client sent a key without expanding its prefix, which is never valid. See `set_path_prefix_interning`
+	#[serde(default)]
+	prefix: Option<String>,
 }
 
 fn parse_request(json: Value) -> Result<RawRequest, RequestError> {
 	from_value::<RawRequest>(json).map_err(|e| RequestError { serde_error: e.to_string() })
 }
 
-pub async fn handle_request<'a, Func, Output, Error>(request_body: Value, mut f: Func) -> Value
-where
-	Error: Display + Sized,
-	Output: Future<Output = std::result::Result<UiResponse, Error>>,
-	Func: FnMut(String, RootUi) -> Output,
-{
-	let RawRequest { session_id, events } = match parse_request(request_body) {
-		Ok(infos) => infos,
-		Err(err) => {
-			return json!([
-				{
-					"key": { "actionPath": ["root_error"] },
-					"data": err.to_string()
-				}
-			])
-		}
-	};
-
-	let mut all_actions = Vec::new();
-
-	for event in events {
-		// really hate that I have to do this clone here, but it needs to be done until rust has better support for async closures
-		// the concept is to ensure that session_id is borowed
-		let actions = match f(session_id.clone(), RootUi::from_event(event)).await {
-			Ok(response) => response.actions,
-			Err(error) => Vec::from([json!({
-				"key": {
-					"actionPath": ["root_error"],
-					"data": error.to_string()
-				}
-			})]),
-		};
+/// Parse the event at `index` in a request. If it is malformed, the action that reports it is returned as the error.
+fn parse_event(index: usize, event: Value) -> Result<RawEvent, Value> {
+	let get_error_action = |message: String| ErrorAction::new([EVENT_ERROR_ACTION], json!({ "index": index, "message": message })).into_value();
+	let event = from_value::<RawEvent>(event).map_err(|error| get_error_action(format!("Invalid event. {error}")))?;
 
-		all_actions.extend(actions)
+	if let Some(id) = &event.key.prefix {
+		return Err(get_error_action(format!(
+			"Event key has the unexpanded path prefix {id}. Clients must expand prefixes with the paths sent on {PATH_PREFIXES_ACTION} before sending keys back"
+		)));
 	}
 
-	json!(all_actions)
+	Ok(event)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct EventKey<T> {
-	event_path: Vec<String>,
-	debug_symbol: Option<String>,
-
-	#[serde(skip)]
-	_marker: PhantomData<T>,
+static PATH_PREFIX_INTERNING: AtomicBool = AtomicBool::new(false);
+
+/// Shrink responses that contain many event keys with the same scope, such as those of a long list. When enabled, the scope of every
+/// event key in a response (every segment of its path but the last) is replaced with a short id, and the paths of the ids that the
+/// response uses are sent ahead of its other actions on `PATH_PREFIXES_ACTION`. The client expands keys before sending them back, so
+/// the engine never has to. Ids are derived from the paths that they stand for, so they are the same in every process, and no state is
+/// kept between requests.
+///
+/// Only `EventKey`s are interned, and only while they are serialized by the handler of a request, so keys that are serialized on
+/// another task, such as by a worker that the event was passed to, are sent in full. Action keys are never interned, because their
+/// paths are a single id. Prefixes are only interned for clients that ask for it in their request, so older clients keep working. When
+/// streaming a request, `internPathPrefixes` must come before the events that it applies to. Disabled by default.
+pub fn set_path_prefix_interning(is_enabled: bool) {
+	PATH_PREFIX_INTERNING.store(is_enabled, Ordering::Relaxed);
 }
 
-impl<T> EventKey<T> {
-	pub fn get_dynamic_symbols(&self) -> Vec<String> {
-		self.event_path.clone()
-	}
+/// The path prefixes that have been interned in a response, keyed by their ids
+type PathPrefixes = BTreeMap<String, Vec<String>>;
+
+thread_local! {
+	/// The prefixes of the request whose handler is currently being polled on this thread, if it is interning. See `with_scoped`
+	static SCOPED_PATH_PREFIXES: RefCell<Option<PathPrefixes>> = const { RefCell::new(None) };
 }
 
-#[derive(Debug, Error)]
-pub enum TakeDataError {
-	#[error(
-		"this event path is different from the incomming event path;
-		application should always validate the event path before taking the event data;
-		this event path: {existing:?}; incomming event path: {incomming:?}"
-	)]
-	DifferingEventPaths { existing: Vec<String>, incomming: Vec<String> },
+/// The id of a path prefix, which is the base62 encoded FNV-1a hash of its segments
+fn get_path_prefix_id(prefix: &[String]) -> String {
+	let mut hash = 0xcbf29ce484222325_u64;
 
-	#[error("tried to take event data, but it was already taken; this is probably caused by calling the EventKey::take_data function more than once in a single event loop cycle")]
-	DataAlreadyTaken,
+	for segment in prefix {
+		// 0xff never appears in utf-8, so it separates segments unambiguously
+		for byte in segment.bytes().chain([0xff]) {
+			hash ^= byte as u64;
+			hash = hash.wrapping_mul(0x100000001b3);
+		}
+	}
 
-	#[error("failed to deserialize event data according the the pre-specified type; {serde_error}")]
-	FailedToDeserialize { serde_error: String },
+	encode_base62(hash)
 }
 
-impl<T: DeserializeOwned> EventKey<T> {
-	pub fn take_data(&self, client: &mut Client) -> Result<T, TakeDataError> {
-		if self.event_path.len() != client.event_path.len() {
-			return Err(TakeDataError::DifferingEventPaths {
-				existing: self.event_path.clone(),
-				incomming: client.event_path.to_vec(),
-			});
-		}
-
-		for (index, symbol) in self.event_path.iter().enumerate() {
-			let expected_symbol = client
-				.event_path
-				.get(index)
-				.expect("this should never happen because of the length check above");
+/// The actions of a request's response, as they are added by each event
+struct ResponseBuilder {
+	actions: Vec<Value>,
+	/// `None` if the prefixes of this response are not being interned
+	prefixes: Option<PathPrefixes>,
+}
 
-			if expected_symbol != symbol {
-				return Err(TakeDataError::DifferingEventPaths {
-					existing: self.event_path.clone(),
-					incomming: client.event_path.to_vec(),
-				});
-			}
+impl ResponseBuilder {
+	fn new() -> ResponseBuilder {
+		ResponseBuilder {
+			actions: Vec::new(),
+			prefixes: None,
 		}
+	}
 
-		let raw_data = client.take_current_event_data().ok_or(TakeDataError::DataAlreadyTaken)?;
+	/// Intern the prefixes of the event keys that are serialized from now on, if the engine has opted in. See `set_path_prefix_interning`
+	fn intern_path_prefixes(&mut self) {
+		if self.prefixes.is_none() && PATH_PREFIX_INTERNING.load(Ordering::Relaxed) {
+			self.prefixes = Some(PathPrefixes::new());
+		}
+	}
 
-		let data = from_value(raw_data).map_err(|inner| TakeDataError::FailedToDeserialize {
-			serde_error: inner.to_string(),
-		})?;
+	fn finish(mut self) -> Value {
+		if let Some(prefixes) = self.prefixes.filter(|prefixes| !prefixes.is_empty()) {
+			self.actions
+				.insert(0, json!({ "key": { "actionPath": [PATH_PREFIXES_ACTION] }, "data": prefixes }));
+		}
 
-		Ok(data)
+		json!(self.actions)
 	}
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-#[serde(rename_all = "camelCase")]
-pub struct ActionKey<T> {
+/// The action that a handler error is sent to the client as. See `IntoErrorAction` for how errors are turned into actions.
+///
+/// Errors are non-fatal by default, meaning that they only replace the response of the event that caused them. See
+/// `ErrorAction::fatal` for errors that should reject the whole request.
+#[derive(Debug, Clone)]
+pub struct ErrorAction {
 	action_path: Vec<String>,
-	debug_symbol: Option<String>,
-
-	#[serde(skip)]
-	_marker: PhantomData<T>,
+	data: Value,
+	is_fatal: bool,
 }
 
-impl<T: Serialize + Clone> ActionKey<T> {
-	pub fn create() -> ActionKey<T> {
-		ActionKey {
-			action_path: Vec::from([random::<u64>().to_string()]),
-			debug_symbol: None,
-			_marker: PhantomData,
+impl ErrorAction {
+	pub fn new<T: Serialize>(action_path: impl IntoIterator<Item = impl Into<String>>, data: T) -> ErrorAction {
+		ErrorAction {
+			action_path: action_path.into_iter().map(Into::into).collect(),
+			data: to_value(data).unwrap(),
+			is_fatal: false,
 		}
 	}
 
-	pub fn with_debug_symbol(mut self, data: impl Into<String>) -> Self {
-		self.debug_symbol = Some(data.into());
+	/// Mark this error as fatal, such as when the session is corrupt. A fatal error aborts the request: the events after it are not
+	/// handled, the actions of the events before it are discarded, and the response contains only this error.
+	pub fn fatal(mut self) -> ErrorAction {
+		self.is_fatal = true;
 
 		self
 	}
 
-	pub fn emit(&self, data: T, client: &mut Client) {
-		client.push_action(Action { key: self.to_owned(), data });
+	pub fn is_fatal(&self) -> bool {
+		self.is_fatal
 	}
-}
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Event<T> {
-	key: EventKey<T>,
-	data: T,
+	/// Route this error to the path of an action key that the runtime is listening on
+	pub fn from_key<T: Serialize>(key: &ActionKey<T>, data: T) -> ErrorAction {
+		ErrorAction::new(key.action_path.clone(), data)
+	}
+
+	fn into_value(self) -> Value {
+		json!({ "key": { "actionPath": self.action_path }, "data": self.data })
+	}
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Action<T> {
-	key: ActionKey<T>,
-	data: T,
+impl Display for ErrorAction {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} on {:?}", self.data, self.action_path)
+	}
 }
 
-#[derive(Debug, Error)]
-pub enum FromStringError {
-	#[error("failed to decode hex; {inner_error}; the following text is what we tried to parse: {hex}")]
-	FailedToDecodeHex { hex: String, inner_error: String },
+/// An error that a handler passed to `handle_routed_request` can fail with. By default, the error's message is sent to `root_error`.
+/// To route an error elsewhere, such as a form boundary or a toast, override `IntoErrorAction::into_error_action`, or return an
+/// `ErrorAction` directly.
+///
+/// ```ignore
+/// impl IntoErrorAction for FormError {
+/// 	fn into_error_action(self) -> ErrorAction {
+/// 		ErrorAction::from_key(&self.boundary, self.to_string())
+/// 	}
+/// }
+/// ```
+pub trait IntoErrorAction: Display + Sized {
+	fn into_error_action(self) -> ErrorAction {
+		ErrorAction::new([ERROR_ACTION], self.to_string())
+	}
+}
 
-	#[error("failed to deserialize from raw bytes; {serde_error}; the following bytes are what we tried to deserialize: {bytes:?}")]
-	FailedToDeserialize { bytes: Vec<u8>, serde_error: String },
+impl IntoErrorAction for ErrorAction {
+	fn into_error_action(self) -> ErrorAction {
+		self
+	}
 }
 
-#[derive(Debug, Error)]
-pub enum ParseError {
-	#[error("failed to parse the symbol from a string; {0}")]
-	FromStringError(FromStringError),
+impl IntoErrorAction for String {}
+impl IntoErrorAction for &str {}
+impl IntoErrorAction for Box<dyn std::error::Error + Send + Sync> {}
+impl IntoErrorAction for serde_json::Error {}
+impl IntoErrorAction for TakeDataError {}
+impl IntoErrorAction for TakeMountEventError {}
+impl IntoErrorAction for ParseError {}
+impl IntoErrorAction for FromStringError {}
+
+#[cfg(feature = "anyhow")]
+impl IntoErrorAction for anyhow::Error {}
+
+/// Render a component tree outside of the request loop, which is useful for snapshot testing a single component. `render` is called
+/// with `props` and a client for an event that has no path or data, and the tree that it returns is mounted as the root ui. The
+/// response contains any actions that `render` emitted, along with the `root_mount` action that holds the rendered tree, ordered as
+/// in `RootUi::into_response`.
+pub fn render_once<Props, Component, Render>(render: Render, props: Props) -> UiResponse
+where
+	Component: IntoComponentIndex,
+	Render: FnOnce(Props, &mut Client) -> Component,
+{
+	let root = RootUi {
+		event_path: Vec::new(),
+		event_version: None,
+		event_data: RefCell::new(None),
+		actions: RefCell::new(Vec::new()),
+		context: Arc::default(),
+	};
 
-	#[error(
-		"tried to parse the next symbol, but there are none left; this could be a user error, but it could also be caused by not calling Ui::scope somewhere"
-	)]
-	NoSymbolsLeft,
+	let component = render(props, &mut root.get_client());
+	root.set_root_ui(component);
+
+	root.into_response()
 }
 
-pub trait EventSymbol: Sized + Serialize + for<'de> Deserialize<'de> {
-	fn to_string(&self) -> String {
-		hex::encode(bincode::serialize(&self).unwrap())
+/// Metadata about the http request that carried a batch of events, such as its headers and the address of the client. Header names
+/// are case insensitive.
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+	headers: HashMap<String, String>,
+	remote_addr: Option<String>,
+}
+
+impl RequestContext {
+	pub fn new() -> RequestContext {
+		RequestContext::default()
 	}
 
-	fn from_string(string: &str) -> Result<Self, FromStringError> {
-		let bytes = hex::decode(string).map_err(|inner| FromStringError::FailedToDecodeHex {
-			hex: string.to_string(),
-			inner_error: inner.to_string(),
-		})?;
+	pub fn with_header(mut self, name: impl AsRef<str>, value: impl Into<String>) -> Self {
+		self.headers.insert(name.as_ref().to_lowercase(), value.into());
 
-		bincode::deserialize(&bytes).map_err(|inner| FromStringError::FailedToDeserialize {
-			bytes: bytes.to_vec(),
-			serde_error: inner.to_string(),
-		})
+		self
+	}
+
+	pub fn with_remote_addr(mut self, remote_addr: impl Into<String>) -> Self {
+		self.remote_addr = Some(remote_addr.into());
+
+		self
+	}
+
+	pub fn header(&self, name: &str) -> Option<&str> {
+		self.headers.get(&name.to_lowercase()).map(|value| value.as_str())
+	}
+
+	/// Every header, keyed by its lowercased name
+	pub fn headers(&self) -> &HashMap<String, String> {
+		&self.headers
+	}
+
+	pub fn remote_addr(&self) -> Option<&str> {
+		self.remote_addr.as_deref()
 	}
 }
 
-pub trait IntoComponentIndex
+/// Handle every event in a request, in order, calling `f` once per event. Each event gets its own `RootUi`, which owns that event's
+/// path and data, and nothing else. A handler can never read the data of another event in the batch: its clients only hold its own
+/// event, so taking data with the key of any other event fails with `TakeDataError::DifferingEventPaths`.
+///
+/// If a handler fails, its error is sent to `root_error` in place of the actions of its event. See `handle_routed_request` for sending
+/// errors elsewhere.
+pub async fn handle_request<'a, Func, Output, Error>(request_body: Value, f: Func) -> Value
 where
-	Self: Sized,
+	Error: Display,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
 {
-	type Index: ComponentIndex;
+	handle_request_with_context(request_body, RequestContext::default(), f).await
+}
 
-	fn into_index(self) -> Self::Index;
+/// Like `handle_request`, but makes `context` available to every event's handler through `RootUi::context` and `Client::context`
+pub async fn handle_request_with_context<'a, Func, Output, Error>(request_body: Value, context: RequestContext, mut f: Func) -> Value
+where
+	Error: Display,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
+{
+	handle_routed_request(request_body, context, |session_id, ui| to_root_error(f(session_id, ui))).await
 }
 
-pub trait ComponentIndex
+/// Like `handle_request_with_context`, but each handler error is turned into an action by `IntoErrorAction`, so that errors can be
+/// sent to paths other than `root_error`. A fatal error (see `ErrorAction::fatal`) aborts the request, and is the only action in the
+/// response.
+pub async fn handle_routed_request<'a, Func, Output, Error>(request_body: Value, context: RequestContext, f: Func) -> Value
 where
-	Self: Sized,
+	Error: IntoErrorAction,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
 {
-	fn to_value(self) -> Value;
+	let recorder = REQUEST_RECORDER.lock().unwrap().clone();
+
+	if let Some(recorder) = recorder {
+		recorder(&request_body);
+	}
+
+	handle_parsed_request(request_body, context, f).await
 }
 
-impl<Index: ComponentIndex> IntoComponentIndex for Index {
-	type Index = Index;
+type RequestRecorder = Arc<dyn Fn(&Value) + Send + Sync>;
 
-	fn into_index(self) -> Index {
-		self
+static REQUEST_RECORDER: Mutex<Option<RequestRecorder>> = Mutex::new(None);
+
+/// Call `recorder` with the body of every request that is passed to `handle_request`, `handle_request_with_context` or
+/// `handle_routed_request`, before it is handled. The body can be stored, then re-run locally with `replay_request`. Requests passed
+/// to `handle_request_stream` are not recorded, because their bodies are never held in memory at once.
+pub fn set_request_recorder(recorder: impl Fn(&Value) + Send + Sync + 'static) {
+	*REQUEST_RECORDER.lock().unwrap() = Some(Arc::new(recorder));
+}
+
+/// Stop recording requests. See `set_request_recorder`
+pub fn clear_request_recorder() {
+	*REQUEST_RECORDER.lock().unwrap() = None;
+}
+
+/// The seed that action keys are generated from while a request is replayed
+const REPLAY_SEED: u64 = 0;
+
+/// Re-run a request body that was captured with `set_request_recorder`. The action keys that the replay creates are generated from a
+/// generator of its own, seeded with a fixed seed, so replaying the same request against the same handlers always produces the same
+/// response, even while other requests are being handled. The generator set by `seed_action_keys` is neither used nor changed. Keys
+/// are only generated from the replay's generator while its future is being polled, so handlers that pass events to other tasks are
+/// not reproducible. Replayed requests are not recorded.
+pub async fn replay_request<'a, Func, Output, Error>(recorded_body: Value, mut f: Func) -> Value
+where
+	Error: Display,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
+{
+	replay_routed_request(recorded_body, |session_id, ui| to_root_error(f(session_id, ui))).await
+}
+
+/// Like `replay_request`, but for requests that were handled by `handle_routed_request`
+pub async fn replay_routed_request<'a, Func, Output, Error>(recorded_body: Value, f: Func) -> Value
+where
+	Error: IntoErrorAction,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
+{
+	let rng = StdRng::seed_from_u64(REPLAY_SEED);
+	let (response, _) = with_scoped(&SCOPED_ACTION_KEY_RNG, rng, handle_parsed_request(recorded_body, RequestContext::default(), f)).await;
+
+	response
+}
+
+thread_local! {
+	/// The generator of the replay whose future is currently being polled on this thread, if there is one. See `with_scoped`
+	static SCOPED_ACTION_KEY_RNG: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Run `future` with `value` installed in `slot`, then return its output along with `value`, as `future` left it. The value is
+/// installed only while `future` is being polled, so it follows the future across threads, and is never seen by other futures.
+async fn with_scoped<T: 'static, F: Future>(slot: &'static LocalKey<RefCell<Option<T>>>, value: T, future: F) -> (F::Output, T) {
+	let mut value = Some(value);
+	let mut future = pin!(future);
+
+	let output = poll_fn(|cx| {
+		let previous = slot.with(|scoped| scoped.replace(value.take()));
+		let poll = future.as_mut().poll(cx);
+		value = slot.with(|scoped| scoped.replace(previous));
+
+		poll
+	})
+	.await;
+
+	(output, value.expect("the scoped value is put back after every poll"))
+}
+
+async fn handle_parsed_request<'a, Func, Output, Error>(request_body: Value, context: RequestContext, mut f: Func) -> Value
+where
+	Error: IntoErrorAction,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
+{
+	let context = Arc::new(context);
+	let RawRequest {
+		session_id,
+		events,
+		intern_path_prefixes,
+	} = match parse_request(request_body) {
+		Ok(infos) => infos,
+		Err(err) => return err.into_response(),
+	};
+	let session_id = match SessionId::validate(session_id) {
+		Ok(session_id) => session_id,
+		Err(response) => return response,
+	};
+
+	let mut builder = ResponseBuilder::new();
+
+	if intern_path_prefixes {
+		builder.intern_path_prefixes();
+	}
+
+	for (index, event) in events.into_iter().enumerate() {
+		if let Err(response) = handle_event(parse_event(index, event), &session_id, &context, &mut f, &mut builder).await {
+			return response;
+		}
 	}
+
+	builder.finish()
 }
 
-fn borrow_scope<'a>(scope: &'a Vec<EventScope<'a>>) -> Vec<EventScope<'a>> {
-	scope
+/// Like `handle_request_with_context`, but reads the request body from `reader`, handling each event as soon as it has been read.
+/// Only one event is held in memory at a time, so that large batches don't need to be buffered. Handler errors are treated the same as
+/// in `handle_request`, as are malformed events. If the body turns out to be invalid partway through, the response contains only the
+/// error, but the events before the invalid part will have already been handled.
+///
+/// Events can only be handled once the session id is known, so events that come before the `sessionId` field in the body are
+/// buffered until it is read.
+pub async fn handle_request_stream<Reader, Func, Output, Error>(reader: Reader, context: RequestContext, mut f: Func) -> Value
+where
+	Reader: AsyncRead + Unpin,
+	Error: Display,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
+{
+	handle_routed_request_stream(reader, context, |session_id, ui| to_root_error(f(session_id, ui))).await
+}
+
+/// Like `handle_request_stream`, but handler errors are turned into actions as in `handle_routed_request`
+pub async fn handle_routed_request_stream<Reader, Func, Output, Error>(reader: Reader, context: RequestContext, mut f: Func) -> Value
+where
+	Reader: AsyncRead + Unpin,
+	Error: IntoErrorAction,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
+{
+	let context = Arc::new(context);
+	let mut reader = ValueReader::new(reader);
+	let mut session_id = None::<SessionId>;
+	let mut pending_events = Vec::new();
+	let mut event_count = 0;
+	let mut builder = ResponseBuilder::new();
+
+	let result = async {
+		reader.expect(b'{').await?;
+		let mut is_empty = reader.consume_if(b'}').await?;
+
+		while !is_empty {
+			let key = reader.read::<String>().await?;
+			reader.expect(b':').await?;
+
+			match key.as_str() {
+				"sessionId" => {
+					let id = match SessionId::validate(reader.read::<String>().await?) {
+						Ok(id) => id,
+						Err(response) => return Ok(Some(response)),
+					};
+
+					for event in pending_events.drain(..) {
+						if let Err(response) = handle_event(event, &id, &context, &mut f, &mut builder).await {
+							return Ok(Some(response));
+						}
+					}
+
+					session_id = Some(id);
+				}
+				"events" => {
+					reader.expect(b'[').await?;
+					let mut is_done = reader.consume_if(b']').await?;
+
+					while !is_done {
+						let event = parse_event(event_count, reader.read::<Value>().await?);
+						event_count += 1;
+
+						match &session_id {
+							Some(id) => {
+								if let Err(response) = handle_event(event, id, &context, &mut f, &mut builder).await {
+									return Ok(Some(response));
+								}
+							}
+							None => pending_events.push(event),
+						}
+
+						// a comma must be followed by another event, so that a trailing comma is rejected
+						if !reader.consume_if(b',').await? {
+							reader.expect(b']').await?;
+							is_done = true;
+						}
+					}
+				}
+				"internPathPrefixes" => {
+					if reader.read::<bool>().await? {
+						builder.intern_path_prefixes();
+					}
+				}
+				_ => {
+					reader.read::<Value>().await?;
+				}
+			}
+
+			if !reader.consume_if(b',').await? {
+				reader.expect(b'}').await?;
+				is_empty = true;
+			}
+		}
+
+		if session_id.is_none() {
+			return Err(StreamError::MissingField("sessionId"));
+		}
+
+		Ok::<_, StreamError>(None)
+	}
+	.await;
+
+	match result {
+		Ok(Some(response)) => response,
+		Ok(None) => builder.finish(),
+		Err(error) => RequestError {
+			serde_error: error.to_string(),
+		}
+		.into_response(),
+	}
+}
+
+/// Send the error of a handler that only implements `Display` to `root_error`
+async fn to_root_error<Output, Error>(output: Output) -> std::result::Result<UiResponse, ErrorAction>
+where
+	Error: Display,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+{
+	output.await.map_err(|error| ErrorAction::new([ERROR_ACTION], error.to_string()))
+}
+
+/// Handle a single event of a request, adding the actions that it produced to `actions`. If the event is malformed, the action that
+/// reports it is added instead. If the handler failed fatally, the response is returned as the error, and the rest of the request
+/// should be abandoned.
+async fn handle_event<Func, Output, Error>(
+	event: std::result::Result<RawEvent, Value>,
+	session_id: &SessionId,
+	context: &Arc<RequestContext>,
+	f: &mut Func,
+	builder: &mut ResponseBuilder,
+) -> std::result::Result<(), Value>
+where
+	Error: IntoErrorAction,
+	Output: Future<Output = std::result::Result<UiResponse, Error>>,
+	Func: FnMut(SessionId, RootUi) -> Output,
+{
+	let event = match event {
+		Ok(event) => event,
+		Err(action) => {
+			builder.actions.push(action);
+			return Ok(());
+		}
+	};
+
+	// really hate that I have to do this clone here, but it needs to be done until rust has better support for async closures
+	// the concept is to ensure that session_id is borowed
+	let handler = f(session_id.clone(), RootUi::from_event(event, context.clone()));
+	let result = match builder.prefixes.take() {
+		Some(prefixes) => {
+			let (result, prefixes) = with_scoped(&SCOPED_PATH_PREFIXES, prefixes, handler).await;
+			builder.prefixes = Some(prefixes);
+
+			result
+		}
+		None => handler.await,
+	};
+
+	match result {
+		Ok(response) => builder.actions.extend(response.actions),
+		Err(error) => {
+			let error = error.into_error_action();
+
+			if error.is_fatal() {
+				error!("aborting request due to a fatal error: {}", error.data);
+
+				return Err(json!([error.into_value()]));
+			}
+
+			builder.actions.push(error.into_value());
+		}
+	}
+
+	Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventKey<T> {
+	event_path: Vec<String>,
+	debug_symbol: Option<String>,
+	/// The protocol version that the key was created under. See `set_protocol_version`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	version: Option<u32>,
+
+	#[serde(skip)]
+	_marker: PhantomData<T>,
+}
+
+impl<T> EventKey<T> {
+	pub fn get_dynamic_symbols(&self) -> Vec<String> {
+		self.event_path.clone()
+	}
+
+	/// The id of this key's prefix, along with the rest of its path, if the prefixes of the response that it is being serialized into
+	/// are being interned. See `set_path_prefix_interning`
+	fn intern_path_prefix(&self) -> Option<(String, &[String])> {
+		let (prefix, rest) = self.event_path.split_at(self.event_path.len().checked_sub(1)?);
+
+		if prefix.is_empty() {
+			return None;
+		}
+
+		SCOPED_PATH_PREFIXES.with(|scoped| {
+			let mut scoped = scoped.borrow_mut();
+			let prefixes = scoped.as_mut()?;
+			let id = get_path_prefix_id(prefix);
+			prefixes.entry(id.clone()).or_insert_with(|| prefix.to_vec());
+
+			Some((id, rest))
+		})
+	}
+}
+
+impl<T> Serialize for EventKey<T> {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		let mut key = serializer.serialize_struct("EventKey", 4)?;
+
+		match self.intern_path_prefix() {
+			Some((id, rest)) => {
+				key.serialize_field("eventPath", rest)?;
+				key.serialize_field("prefix", &id)?;
+			}
+			None => {
+				key.serialize_field("eventPath", &self.event_path)?;
+				key.skip_field("prefix")?;
+			}
+		}
+
+		key.serialize_field("debugSymbol", &self.debug_symbol)?;
+
+		match &self.version {
+			Some(version) => key.serialize_field("version", version)?,
+			None => key.skip_field("version")?,
+		}
+
+		key.end()
+	}
+}
+
+static PROTOCOL_VERSION: AtomicU32 = AtomicU32::new(0);
+
+/// Stamp every `EventKey` created after this call with `version`. When an event comes back with a key from a different version, such
+/// as from a client that was loaded before a deploy, `EventKey::take_data` fails with `TakeDataError::VersionMismatch` rather than a
+/// confusing `DifferingEventPaths`. Versions start at 1. A version of 0 disables versioning, which is the default.
+pub fn set_protocol_version(version: u32) {
+	PROTOCOL_VERSION.store(version, Ordering::Relaxed);
+}
+
+/// The version set by `set_protocol_version`, if versioning is enabled
+pub fn protocol_version() -> Option<u32> {
+	match PROTOCOL_VERSION.load(Ordering::Relaxed) {
+		0 => None,
+		version => Some(version),
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum TakeDataError {
+	#[error(
+		"the incoming event was sent with a key from protocol version {incoming}, but this is protocol version {expected};
+		the client is probably out of date, and should be reloaded"
+	)]
+	VersionMismatch { expected: u32, incoming: u32 },
+
+	#[error(
+		"this event path is different from the incomming event path;
+		application should always validate the event path before taking the event data;
+		this event path: {existing:?}; incomming event path: {incomming:?}"
+	)]
+	DifferingEventPaths { existing: Vec<String>, incomming: Vec<String> },
+
+	#[error("tried to take event data, but it was already taken; this is probably caused by calling the EventKey::take_data function more than once in a single event loop cycle")]
+	DataAlreadyTaken,
+
+	#[error("failed to deserialize event data according the the pre-specified type; {serde_error}")]
+	FailedToDeserialize { serde_error: String },
+}
+
+impl<T: DeserializeOwned> EventKey<T> {
+	/// Take the data of the event that `client` was created for. Fails with `TakeDataError::DifferingEventPaths` if this key is not
+	/// for that event, even if this key matches another event in the same request. If both this key and the incoming event are
+	/// versioned, fails with `TakeDataError::VersionMismatch` when their versions differ.
+	pub fn take_data(&self, client: &mut Client) -> Result<T, TakeDataError> {
+		if let (Some(expected), Some(incoming)) = (self.version, client.event_version) {
+			if expected != incoming {
+				return Err(TakeDataError::VersionMismatch { expected, incoming });
+			}
+		}
+
+		if self.event_path.len() != client.event_path.len() {
+			return Err(TakeDataError::DifferingEventPaths {
+				existing: self.event_path.clone(),
+				incomming: client.event_path.to_vec(),
+			});
+		}
+
+		for (index, symbol) in self.event_path.iter().enumerate() {
+			let expected_symbol = client
+				.event_path
+				.get(index)
+				.expect("this should never happen because of the length check above");
+
+			if expected_symbol != symbol {
+				return Err(TakeDataError::DifferingEventPaths {
+					existing: self.event_path.clone(),
+					incomming: client.event_path.to_vec(),
+				});
+			}
+		}
+
+		let raw_data = client.take_current_event_data().ok_or(TakeDataError::DataAlreadyTaken)?;
+
+		let data = from_value(raw_data).map_err(|inner| TakeDataError::FailedToDeserialize {
+			serde_error: inner.to_string(),
+		})?;
+
+		Ok(data)
+	}
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ActionKey<T> {
+	action_path: Vec<String>,
+	debug_symbol: Option<String>,
+
+	#[serde(skip)]
+	_marker: PhantomData<T>,
+}
+
+// implemented by hand, because deriving would require `T: Clone`, which generated component indices don't implement
+impl<T> Clone for ActionKey<T> {
+	fn clone(&self) -> Self {
+		ActionKey {
+			action_path: self.action_path.clone(),
+			debug_symbol: self.debug_symbol.clone(),
+			_marker: PhantomData,
+		}
+	}
+}
+
+/// The way in which the random id of an `ActionKey` is written into its action path. Either way, the id is a full random `u64`, so
+/// the chance of two keys colliding is the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ActionKeyEncoding {
+	/// The id in decimal. Up to 20 characters
+	#[default]
+	Decimal,
+	/// The id in base62. Up to 11 characters, which shrinks responses that contain many actions
+	Base62,
+}
+
+const BASE62_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+static ACTION_KEY_ENCODING: AtomicU8 = AtomicU8::new(ActionKeyEncoding::Decimal as u8);
+
+/// Set the encoding used by every `ActionKey` created after this call. See `ActionKeyEncoding`
+pub fn set_action_key_encoding(encoding: ActionKeyEncoding) {
+	ACTION_KEY_ENCODING.store(encoding as u8, Ordering::Relaxed);
+}
+
+static ACTION_KEY_RNG: Mutex<Option<StdRng>> = Mutex::new(None);
+
+/// Generate the ids of every `ActionKey` created after this call from a generator seeded with `seed`, so that responses are
+/// reproducible, such as in snapshot tests. `None` goes back to generating ids randomly, which is the default. The generator is
+/// shared by every thread, so ids are only reproducible if requests are handled one at a time. Replays have generators of their own.
+/// See `replay_request`
+pub fn seed_action_keys(seed: Option<u64>) {
+	*ACTION_KEY_RNG.lock().unwrap() = seed.map(StdRng::seed_from_u64);
+}
+
+fn generate_action_id() -> u64 {
+	if let Some(id) = SCOPED_ACTION_KEY_RNG.with(|scoped| scoped.borrow_mut().as_mut().map(|rng| rng.gen())) {
+		return id;
+	}
+
+	match ACTION_KEY_RNG.lock().unwrap().as_mut() {
+		Some(rng) => rng.gen(),
+		None => random(),
+	}
+}
+
+fn encode_action_id(id: u64) -> String {
+	if ACTION_KEY_ENCODING.load(Ordering::Relaxed) == ActionKeyEncoding::Decimal as u8 {
+		return id.to_string();
+	}
+
+	encode_base62(id)
+}
+
+fn encode_base62(id: u64) -> String {
+	let mut remaining = id;
+	let mut digits = Vec::new();
+
+	loop {
+		digits.push(BASE62_ALPHABET[(remaining % 62) as usize]);
+		remaining /= 62;
+
+		if remaining == 0 {
+			break;
+		}
+	}
+
+	digits.reverse();
+	String::from_utf8(digits).unwrap()
+}
+
+impl<T: Serialize> ActionKey<T> {
+	pub fn create() -> ActionKey<T> {
+		ActionKey {
+			action_path: Vec::from([encode_action_id(generate_action_id())]),
+			debug_symbol: None,
+			_marker: PhantomData,
+		}
+	}
+
+	pub fn with_debug_symbol(mut self, data: impl Into<String>) -> Self {
+		self.debug_symbol = Some(data.into());
+
+		self
+	}
+
+	pub fn emit(&self, data: T, client: &mut Client) {
+		client.push_action(Action {
+			key: self.to_owned(),
+			data,
+			priority: None,
+		});
+	}
+
+	/// Emit an action that will be ordered by `priority` in the response. Lower priorities are sent first. Actions emitted via
+	/// `ActionKey::emit` have a priority of `DEFAULT_ACTION_PRIORITY`
+	pub fn emit_with_priority(&self, data: T, priority: u8, client: &mut Client) {
+		client.push_action(Action {
+			key: self.to_owned(),
+			data,
+			priority: Some(priority),
+		});
+	}
+}
+
+impl<Index: ComponentIndex> ActionKey<Index> {
+	/// Emit a renderable component tree as the action's data. Used for swapping a subtree into a slot on the client
+	pub fn emit_component(&self, ui: impl IntoComponentIndex<Index = Index>, client: &mut Client) {
+		self.push_component(ui, None, client);
+	}
+
+	/// Like `ActionKey::emit_component`, but ordered by `priority` in the response. See `ActionKey::emit_with_priority`
+	pub fn emit_component_with_priority(&self, ui: impl IntoComponentIndex<Index = Index>, priority: u8, client: &mut Client) {
+		self.push_component(ui, Some(priority), client);
+	}
+
+	fn push_component(&self, ui: impl IntoComponentIndex<Index = Index>, priority: Option<u8>, client: &mut Client) {
+		let mut action = json!({ "key": self, "data": ui.into_index().to_value() });
+
+		if let Some(priority) = priority {
+			action["priority"] = json!(priority);
+		}
+
+		client.actions.borrow_mut().push(action);
+	}
+}
+
+/// The priority of actions that were not given an explicit priority
+pub const DEFAULT_ACTION_PRIORITY: u8 = 128;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Event<T> {
+	key: EventKey<T>,
+	data: T,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Action<T> {
+	key: ActionKey<T>,
+	data: T,
+
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	priority: Option<u8>,
+}
+
+impl<T: DeserializeOwned> Action<T> {
+	/// Deserialize an action, as it would be found in a response, back into a typed action. Useful for asserting on emitted actions
+	pub fn from_value(value: Value) -> Result<Action<T>, serde_json::Error> {
+		from_value(value)
+	}
+}
+
+impl<T> Action<T> {
+	pub fn key(&self) -> &ActionKey<T> {
+		&self.key
+	}
+
+	pub fn data(&self) -> &T {
+		&self.data
+	}
+
+	pub fn into_data(self) -> T {
+		self.data
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum FromStringError {
+	#[error("failed to decode hex; {inner_error}; the following text is what we tried to parse: {hex}")]
+	FailedToDecodeHex { hex: String, inner_error: String },
+
+	#[error("failed to decode base64url; {inner_error}; the following text is what we tried to parse: {base64}")]
+	FailedToDecodeBase64 { base64: String, inner_error: String },
+
+	#[error("failed to deserialize from raw bytes; {serde_error}; the following bytes are what we tried to deserialize: {bytes:?}")]
+	FailedToDeserialize { bytes: Vec<u8>, serde_error: String },
+
+	#[error("failed to deserialize from json; {serde_error}; the following text is what we tried to parse: {json}")]
+	FailedToDeserializeJson { json: String, serde_error: String },
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+	#[error("failed to parse the symbol from a string; {0}")]
+	FromStringError(FromStringError),
+
+	#[error(
+		"tried to parse the next symbol, but there are none left; this could be a user error, but it could also be caused by not calling Ui::scope somewhere"
+	)]
+	NoSymbolsLeft,
+}
+
+/// The way in which an `EventSymbol` is encoded into a segment of an event path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventSymbolEncoding {
+	/// Hex-encoded bincode bytes. Compact, but unreadable, and tied to bincode's representation
+	#[default]
+	HexBincode,
+	/// The symbol's json representation. Readable in network logs, and round-trips structs and enums unambiguously
+	Json,
+	/// Unpadded base64url-encoded bincode bytes. A third shorter than `HexBincode`, which matters for large symbols, and safe to
+	/// place in urls
+	Base64UrlBincode,
+}
+
+/// A value that can scope a `Ui`. To use a representation that none of the `EventSymbolEncoding`s provide, override both
+/// `EventSymbol::to_string` and `EventSymbol::from_string`.
+pub trait EventSymbol: Sized + Serialize + for<'de> Deserialize<'de> {
+	/// The encoding used by `EventSymbol::to_string` and `EventSymbol::from_string`. Defaults to `EventSymbolEncoding::HexBincode`
+	fn encoding() -> EventSymbolEncoding {
+		EventSymbolEncoding::HexBincode
+	}
+
+	fn to_string(&self) -> String {
+		match Self::encoding() {
+			EventSymbolEncoding::HexBincode => hex::encode(bincode::serialize(&self).unwrap()),
+			EventSymbolEncoding::Json => serde_json::to_string(&self).unwrap(),
+			EventSymbolEncoding::Base64UrlBincode => URL_SAFE_NO_PAD.encode(bincode::serialize(&self).unwrap()),
+		}
+	}
+
+	fn from_string(string: &str) -> Result<Self, FromStringError> {
+		match Self::encoding() {
+			EventSymbolEncoding::HexBincode => {
+				let bytes = hex::decode(string).map_err(|inner| FromStringError::FailedToDecodeHex {
+					hex: string.to_string(),
+					inner_error: inner.to_string(),
+				})?;
+
+				bincode::deserialize(&bytes).map_err(|inner| FromStringError::FailedToDeserialize {
+					bytes: bytes.to_vec(),
+					serde_error: inner.to_string(),
+				})
+			}
+			EventSymbolEncoding::Base64UrlBincode => {
+				let bytes = URL_SAFE_NO_PAD.decode(string).map_err(|inner| FromStringError::FailedToDecodeBase64 {
+					base64: string.to_string(),
+					inner_error: inner.to_string(),
+				})?;
+
+				bincode::deserialize(&bytes).map_err(|inner| FromStringError::FailedToDeserialize {
+					bytes: bytes.to_vec(),
+					serde_error: inner.to_string(),
+				})
+			}
+			EventSymbolEncoding::Json => from_str(string).map_err(|inner| FromStringError::FailedToDeserializeJson {
+				json: string.to_string(),
+				serde_error: inner.to_string(),
+			}),
+		}
+	}
+}
+
+pub trait IntoComponentIndex
+where
+	Self: Sized,
+{
+	type Index: ComponentIndex;
+
+	fn into_index(self) -> Self::Index;
+}
+
+pub trait ComponentIndex
+where
+	Self: Sized,
+{
+	fn to_value(self) -> Value;
+}
+
+impl<Index: ComponentIndex> IntoComponentIndex for Index {
+	type Index = Index;
+
+	fn into_index(self) -> Index {
+		self
+	}
+}
+
+fn borrow_scope<'a>(scope: &'a Vec<EventScope<'a>>) -> Vec<EventScope<'a>> {
+	scope
 		.iter()
 		.map(|item| match item {
 			EventScope::Owned(string) => EventScope::Borrowed(string.as_str()),
@@ -385,3 +1357,681 @@ fn borrow_scope<'a>(scope: &'a Vec<EventScope<'a>>) -> Vec<EventScope<'a>> {
 		})
 		.collect::<Vec<_>>()
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures_util::task::noop_waker;
+	use std::{
+		collections::HashSet,
+		sync::{MutexGuard, PoisonError},
+		task::{Context, Poll},
+	};
+
+	/// Poll `future` until it is ready. Nothing that the tests await ever waits on io, so this never spins for long
+	fn block_on<F: Future>(future: F) -> F::Output {
+		let waker = noop_waker();
+		let mut cx = Context::from_waker(&waker);
+		let mut future = pin!(future);
+
+		loop {
+			if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+				return output;
+			}
+		}
+	}
+
+	static GLOBAL_CONFIG: Mutex<()> = Mutex::new(());
+
+	/// Held by the tests that change the global configuration, such as `set_session_id_validator`, so that they run one at a time.
+	/// The configuration is reset to its defaults when the guard is dropped, even if the test failed
+	struct GlobalConfigGuard {
+		_lock: MutexGuard<'static, ()>,
+	}
+
+	impl Drop for GlobalConfigGuard {
+		fn drop(&mut self) {
+			clear_session_id_validator();
+			clear_request_recorder();
+			set_path_prefix_interning(false);
+			set_protocol_version(0);
+			set_action_key_encoding(ActionKeyEncoding::default());
+			seed_action_keys(None);
+		}
+	}
+
+	fn lock_global_config() -> GlobalConfigGuard {
+		GlobalConfigGuard {
+			_lock: GLOBAL_CONFIG.lock().unwrap_or_else(PoisonError::into_inner),
+		}
+	}
+
+	// mirrors the bindings generated by the cli, which derive neither `Clone` nor `PartialEq`
+	#[derive(Debug, Serialize, Deserialize)]
+	struct Label {
+		text: String,
+	}
+
+	#[derive(Debug, Serialize, Deserialize)]
+	#[serde(tag = "type", content = "def")]
+	enum Component {
+		Label(Box<Label>),
+	}
+
+	impl ComponentIndex for Component {
+		fn to_value(self) -> Value {
+			to_value(self).unwrap()
+		}
+	}
+
+	impl IntoComponentIndex for Label {
+		type Index = Component;
+
+		fn into_index(self) -> Component {
+			Component::Label(Box::new(self))
+		}
+	}
+
+	fn root_ui(event: Value) -> RootUi {
+		RootUi::from_event(from_value(event).unwrap(), Arc::default())
+	}
+
+	fn label(text: &str) -> Label {
+		Label { text: text.to_string() }
+	}
+
+	fn request(events: &[&str]) -> Value {
+		let events = events
+			.iter()
+			.map(|head| json!({ "key": { "eventPath": [head] }, "data": null }))
+			.collect::<Vec<_>>();
+
+		json!({ "sessionId": "session", "events": events })
+	}
+
+	#[derive(Debug, Error)]
+	enum AppError {
+		#[error("invalid email")]
+		Validation,
+		#[error("not signed in")]
+		Auth,
+	}
+
+	impl IntoErrorAction for AppError {
+		fn into_error_action(self) -> ErrorAction {
+			match self {
+				AppError::Validation => ErrorAction::new(["form_boundary"], self.to_string()),
+				AppError::Auth => ErrorAction::new(["toast"], self.to_string()),
+			}
+		}
+	}
+
+	#[test]
+	fn error_variants_are_routed_to_their_own_paths() {
+		let response = block_on(handle_routed_request(
+			request(&["validate", "login"]),
+			RequestContext::default(),
+			|_, ui| async move {
+				match ui.event_head() {
+					Some("validate") => Err::<UiResponse, _>(AppError::Validation),
+					_ => Err(AppError::Auth),
+				}
+			},
+		));
+
+		assert_eq!(
+			response,
+			json!([
+				{ "key": { "actionPath": ["form_boundary"] }, "data": "invalid email" },
+				{ "key": { "actionPath": ["toast"] }, "data": "not signed in" },
+			])
+		);
+	}
+
+	async fn yield_once() {
+		let mut has_yielded = false;
+
+		poll_fn(|cx| {
+			if has_yielded {
+				return Poll::Ready(());
+			}
+
+			has_yielded = true;
+			cx.waker().wake_by_ref();
+
+			Poll::Pending
+		})
+		.await
+	}
+
+	async fn emit_new_keys(_: SessionId, ui: RootUi) -> Result<UiResponse, String> {
+		ActionKey::<()>::create().emit((), &mut ui.get_client());
+		yield_once().await;
+		ActionKey::<()>::create().emit((), &mut ui.get_client());
+
+		Ok(ui.into_response())
+	}
+
+	#[test]
+	fn replays_are_deterministic_while_other_requests_are_handled() {
+		// the unseeded request must not be generated from a seed that another test set
+		let _config = lock_global_config();
+		let body = request(&["first", "second"]);
+		let waker = noop_waker();
+		let mut cx = Context::from_waker(&waker);
+		let mut first_replay = pin!(replay_request(body.clone(), emit_new_keys));
+
+		// suspend the first replay partway through, then handle other requests before it finishes
+		assert!(first_replay.as_mut().poll(&mut cx).is_pending());
+
+		let unseeded = block_on(handle_request(body.clone(), emit_new_keys));
+		let second = block_on(replay_request(body, emit_new_keys));
+		let first = block_on(first_replay);
+
+		assert_eq!(first, second);
+		assert_ne!(first, unseeded);
+	}
+
+	#[test]
+	fn other_errors_are_routed_to_root_error() {
+		let response = block_on(handle_request(request(&["submit"]), |_, _| async {
+			Err::<UiResponse, _>("something broke".to_string())
+		}));
+
+		assert_eq!(response, json!([{ "key": { "actionPath": ["root_error"] }, "data": "something broke" }]));
+	}
+
+	/// An error type from outside of the crate, which can't implement `IntoErrorAction`
+	struct DisplayError;
+
+	impl Display for DisplayError {
+		fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+			write!(f, "display only")
+		}
+	}
+
+	#[test]
+	fn errors_that_only_implement_display_are_routed_to_root_error() {
+		let body = request(&["submit"]);
+		let buffered = block_on(handle_request(body.clone(), |_, _| async { Err::<UiResponse, _>(DisplayError) }));
+		let streamed = block_on(handle_request_stream(body.to_string().as_bytes(), RequestContext::default(), |_, _| async {
+			Err::<UiResponse, _>(DisplayError)
+		}));
+		let replayed = block_on(replay_request(body, |_, _| async { Err::<UiResponse, _>(DisplayError) }));
+
+		assert_eq!(buffered, json!([{ "key": { "actionPath": ["root_error"] }, "data": "display only" }]));
+		assert_eq!(streamed, buffered);
+		assert_eq!(replayed, buffered);
+
+		// errors that could be routed are still sent to `root_error`, unless they are handled by `handle_routed_request`
+		let response = block_on(handle_request(request(&["validate"]), |_, _| async {
+			Err::<UiResponse, _>(AppError::Validation)
+		}));
+
+		assert_eq!(response, json!([{ "key": { "actionPath": ["root_error"] }, "data": "invalid email" }]));
+	}
+
+	/// Emit the head of each event, except for `fail`, which fails with an error that is fatal if `is_fatal` is set
+	async fn emit_event_head(ui: RootUi, is_fatal: bool) -> Result<UiResponse, ErrorAction> {
+		let head = ui.event_head().unwrap_or_default().to_string();
+
+		if head == "fail" {
+			let error = ErrorAction::new(["toast"], "session is corrupt");
+
+			return Err(if is_fatal { error.fatal() } else { error });
+		}
+
+		ActionKey::<String>::create().emit(head, &mut ui.get_client());
+
+		Ok(ui.into_response())
+	}
+
+	#[test]
+	fn fatal_errors_abort_the_request_while_other_errors_replace_their_event() {
+		let body = request(&["before", "fail", "after"]);
+		let scoped = block_on(handle_routed_request(body.clone(), RequestContext::default(), |_, ui| {
+			emit_event_head(ui, false)
+		}));
+		let fatal = block_on(handle_routed_request(body, RequestContext::default(), |_, ui| emit_event_head(ui, true)));
+		let data = scoped.as_array().unwrap().iter().map(|action| action["data"].clone()).collect::<Vec<_>>();
+
+		assert_eq!(data, [json!("before"), json!("session is corrupt"), json!("after")]);
+		assert_eq!(scoped[1]["key"]["actionPath"], json!(["toast"]));
+		assert_eq!(fatal, json!([{ "key": { "actionPath": ["toast"] }, "data": "session is corrupt" }]));
+	}
+
+	async fn emit_request_context(_: SessionId, ui: RootUi) -> Result<UiResponse, String> {
+		let mut client = ui.get_client();
+		let context = json!({ "user": client.context().header("x-user"), "address": ui.context().remote_addr() });
+
+		ActionKey::<Value>::create().emit(context, &mut client);
+		drop(client);
+
+		Ok(ui.into_response())
+	}
+
+	#[test]
+	fn handlers_can_read_the_context_of_their_request() {
+		let context = RequestContext::new().with_header("X-User", "ada").with_remote_addr("127.0.0.1:8000");
+		let response = block_on(handle_request_with_context(request(&["a"]), context, emit_request_context));
+		let without_context = block_on(handle_request(request(&["a"]), emit_request_context));
+
+		assert_eq!(response[0]["data"], json!({ "user": "ada", "address": "127.0.0.1:8000" }));
+		assert_eq!(without_context[0]["data"], json!({ "user": null, "address": null }));
+	}
+
+	async fn fail_with_event_head(_: SessionId, ui: RootUi) -> Result<UiResponse, String> {
+		Err(ui.event_head().unwrap_or_default().to_string())
+	}
+
+	#[test]
+	fn streamed_requests_are_handled_like_buffered_requests() {
+		// the session id comes last, so that the events before it are buffered, and the second event is malformed
+		let body = r#"{ "events": [{ "key": { "eventPath": ["a"] }, "data": null }, { "data": null }, { "key": { "eventPath": ["b"] }, "data": null }], "sessionId": "session" }"#;
+		let streamed = block_on(handle_request_stream(body.as_bytes(), RequestContext::default(), fail_with_event_head));
+		let buffered = block_on(handle_request(from_str(body).unwrap(), fail_with_event_head));
+
+		assert_eq!(streamed, buffered);
+		assert_eq!(streamed[0]["data"], "a");
+		assert_eq!(streamed[1]["key"]["actionPath"], json!([EVENT_ERROR_ACTION]));
+		assert_eq!(streamed[2]["data"], "b");
+	}
+
+	/// The action path of every action in the response to `body`, once buffered and once streamed
+	fn get_error_paths(body: &str) -> (Vec<Value>, Vec<Value>) {
+		let get_paths = |response: Value| -> Vec<Value> {
+			response
+				.as_array()
+				.unwrap()
+				.iter()
+				.map(|action| action["key"]["actionPath"][0].clone())
+				.collect()
+		};
+		let buffered = block_on(handle_request(from_str(body).unwrap(), fail_with_event_head));
+		let streamed = block_on(handle_request_stream(body.as_bytes(), RequestContext::default(), fail_with_event_head));
+
+		(get_paths(buffered), get_paths(streamed))
+	}
+
+	#[test]
+	fn malformed_requests_and_malformed_events_have_their_own_error_actions() {
+		let request_error = json!(REQUEST_ERROR_ACTION);
+		let event_error = json!(EVENT_ERROR_ACTION);
+
+		for body in [r#"{ "events": [] }"#, r#"{ "sessionId": 5, "events": [] }"#, r#"[]"#] {
+			let (buffered, streamed) = get_error_paths(body);
+
+			assert_eq!(buffered, [request_error.clone()], "{body}");
+			assert_eq!(streamed, buffered, "{body}");
+		}
+
+		// events are only reported as malformed once the request is known to be valid
+		let (buffered, streamed) = get_error_paths(r#"{ "events": [{ "data": null }] }"#);
+
+		assert_eq!(buffered, [request_error]);
+		assert_eq!(streamed, buffered);
+
+		let (buffered, streamed) =
+			get_error_paths(r#"{ "sessionId": "session", "events": [{ "data": null }, { "key": { "eventPath": ["a"] }, "data": null }] }"#);
+
+		assert_eq!(buffered, [event_error, json!("root_error")]);
+		assert_eq!(streamed, buffered);
+	}
+
+	#[test]
+	fn streamed_requests_reject_a_trailing_comma_in_the_events() {
+		let body = r#"{ "sessionId": "session", "events": [{ "key": { "eventPath": ["a"] }, "data": null },] }"#;
+		let response = block_on(handle_request_stream(body.as_bytes(), RequestContext::default(), fail_with_event_head));
+
+		assert_eq!(response.as_array().unwrap().len(), 1);
+		assert_eq!(response[0]["key"]["actionPath"], json!([REQUEST_ERROR_ACTION]));
+	}
+
+	#[derive(Serialize, Deserialize)]
+	struct Row(u32);
+
+	impl EventSymbol for Row {}
+
+	async fn emit_row_keys(_: SessionId, ui: RootUi) -> Result<UiResponse, String> {
+		let mut client = ui.get_client();
+		let rows = (0..3).map(|index| client.ui().scope(Row(index)).event_key::<()>()).collect::<Vec<_>>();
+		let data = json!({ "rows": rows, "lookalike": { "eventPath": ["not", "a", "key"] } });
+
+		ActionKey::<Value>::create().emit(data, &mut client);
+		drop(client);
+
+		Ok(ui.into_response())
+	}
+
+	/// Try to take the data of every row's event, as if the handler had mixed up which event it was handling
+	async fn take_every_row(_: SessionId, ui: RootUi) -> Result<UiResponse, String> {
+		let mut client = ui.get_client();
+		let mut results = Vec::new();
+
+		for index in 0..2 {
+			let key = client.ui().scope(Row(index)).event_key::<String>();
+
+			results.push(match key.take_data(&mut client) {
+				Ok(data) => data,
+				Err(TakeDataError::DifferingEventPaths { .. }) => "differing".to_string(),
+				Err(error) => return Err(error.to_string()),
+			});
+		}
+
+		ActionKey::<Vec<String>>::create().emit(results, &mut client);
+		drop(client);
+
+		Ok(ui.into_response())
+	}
+
+	#[test]
+	fn the_current_path_is_the_path_of_the_event_key() {
+		let ui = root_ui(json!({ "key": { "eventPath": ["main"] }, "data": null }));
+		let client = ui.get_client();
+		let root = client.ui();
+		let list = root.scope(Row(4));
+		let item = list.scope(HexFilter(tagged()));
+
+		assert_eq!(root.current_path(), ["main"]);
+		assert_eq!(item.current_path(), ["main".to_string(), Row(4).to_string(), HexFilter(tagged()).to_string()]);
+		assert_eq!(item.current_path(), item.event_key::<()>().event_path);
+		assert_eq!(list.current_path(), list.event_key::<()>().event_path);
+	}
+
+	#[test]
+	fn handlers_can_only_take_the_data_of_their_own_event() {
+		let body = json!({
+			"sessionId": "session",
+			"events": [
+				{ "key": { "eventPath": ["main", Row(0).to_string()] }, "data": "first" },
+				{ "key": { "eventPath": ["main", Row(1).to_string()] }, "data": "second" },
+			],
+		});
+		let response = block_on(handle_request(body, take_every_row));
+
+		assert_eq!(response[0]["data"], json!(["first", "differing"]));
+		assert_eq!(response[1]["data"], json!(["differing", "second"]));
+	}
+
+	fn row_request(is_interning: bool) -> Value {
+		json!({ "sessionId": "session", "internPathPrefixes": is_interning, "events": [{ "key": { "eventPath": ["rows"] }, "data": null }] })
+	}
+
+	/// Expand the prefixes of `key` as the client does, using the prefixes that were sent in `response`
+	fn expand_event_key(response: &Value, key: &Value) -> Value {
+		let prefix = match key.get("prefix") {
+			Some(id) => response[0]["data"][id.as_str().unwrap()].as_array().unwrap().clone(),
+			None => Vec::new(),
+		};
+
+		json!(prefix.iter().chain(key["eventPath"].as_array().unwrap()).collect::<Vec<_>>())
+	}
+
+	#[test]
+	fn event_key_prefixes_round_trip_with_and_without_interning() {
+		let _config = lock_global_config();
+		set_path_prefix_interning(true);
+
+		let interned = block_on(handle_request(row_request(true), emit_row_keys));
+		let full = block_on(handle_request(row_request(false), emit_row_keys));
+
+		assert_eq!(interned[0]["key"]["actionPath"], json!([PATH_PREFIXES_ACTION]));
+		assert_eq!(interned[0]["data"].as_object().unwrap().len(), 1);
+		assert_eq!(full.as_array().unwrap().len(), 1);
+
+		let interned_data = &interned[1]["data"];
+		let full_data = &full[0]["data"];
+
+		for (interned_key, full_key) in interned_data["rows"].as_array().unwrap().iter().zip(full_data["rows"].as_array().unwrap()) {
+			assert_eq!(interned_key["eventPath"].as_array().unwrap().len(), 1);
+			assert!(full_key.get("prefix").is_none());
+			assert_eq!(expand_event_key(&interned, interned_key), full_key["eventPath"]);
+		}
+
+		// objects that merely look like keys are left alone
+		assert_eq!(interned_data["lookalike"], full_data["lookalike"]);
+	}
+
+	#[test]
+	fn prefix_ids_are_derived_from_the_prefix() {
+		let prefix = ["main".to_string(), "list".to_string()];
+
+		assert_eq!(get_path_prefix_id(&prefix), get_path_prefix_id(&prefix.clone()));
+		assert_ne!(get_path_prefix_id(&prefix), get_path_prefix_id(&["mainlist".to_string()]));
+	}
+
+	#[test]
+	fn unexpanded_event_keys_are_rejected() {
+		let body = json!({ "sessionId": "session", "events": [{ "key": { "eventPath": ["a"], "prefix": "abc" }, "data": null }] });
+		let response = block_on(handle_request(body, fail_with_event_head));
+
+		assert_eq!(response[0]["key"]["actionPath"], json!([EVENT_ERROR_ACTION]));
+		assert_eq!(response[0]["data"]["index"], 0);
+	}
+
+	#[test]
+	fn refused_session_ids_reject_the_whole_request() {
+		let _config = lock_global_config();
+		set_session_id_validator(|id| !id.starts_with("refused"));
+
+		let body = r#"{ "sessionId": "refused-session", "events": [{ "key": { "eventPath": ["a"] }, "data": null }] }"#;
+		let buffered = block_on(handle_request(from_str(body).unwrap(), fail_with_event_head));
+		let streamed = block_on(handle_request_stream(body.as_bytes(), RequestContext::default(), fail_with_event_head));
+
+		assert_eq!(
+			buffered,
+			json!([{ "key": { "actionPath": [SESSION_ERROR_ACTION] }, "data": "Invalid session id" }])
+		);
+		assert_eq!(streamed, buffered);
+	}
+
+	fn take_versioned_data(version: u32) -> Result<String, TakeDataError> {
+		let ui = root_ui(json!({ "key": { "eventPath": ["main"], "version": version }, "data": "clicked" }));
+		let mut client = ui.get_client();
+		let key = client.ui().event_key::<String>();
+
+		key.take_data(&mut client)
+	}
+
+	#[test]
+	fn keys_from_another_protocol_version_are_refused() {
+		let _config = lock_global_config();
+		set_protocol_version(2);
+
+		assert_eq!(take_versioned_data(2).unwrap(), "clicked");
+
+		let error = take_versioned_data(1).unwrap_err();
+
+		assert!(matches!(error, TakeDataError::VersionMismatch { expected: 2, incoming: 1 }));
+		assert!(error
+			.to_string()
+			.starts_with("the incoming event was sent with a key from protocol version 1, but this is protocol version 2"));
+	}
+
+	#[test]
+	fn generated_components_can_be_emitted_with_priorities() {
+		let key = ActionKey::<Component>::create();
+		let response = render_once(
+			|_: (), client: &mut Client| {
+				key.emit_component(label("later"), client);
+				key.emit_component_with_priority(label("sooner"), 0, client);
+
+				label("root")
+			},
+			(),
+		);
+
+		assert_eq!(
+			response.actions(),
+			[
+				json!({ "key": key, "data": { "type": "Label", "def": { "text": "sooner" } }, "priority": 0 }),
+				json!({ "key": key, "data": { "type": "Label", "def": { "text": "later" } } }),
+				json!({ "key": { "actionPath": ["root_mount"] }, "data": { "type": "Label", "def": { "text": "root" } } }),
+			]
+		);
+	}
+
+	fn create_action_paths(encoding: ActionKeyEncoding) -> Vec<String> {
+		set_action_key_encoding(encoding);
+		seed_action_keys(Some(7));
+
+		(0..1000).map(|_| ActionKey::<()>::create().action_path[0].clone()).collect()
+	}
+
+	#[test]
+	fn base62_action_keys_are_shorter_and_still_unique() {
+		let _config = lock_global_config();
+		let decimal = create_action_paths(ActionKeyEncoding::Decimal);
+		let base62 = create_action_paths(ActionKeyEncoding::Base62);
+		let get_total_length = |paths: &[String]| paths.iter().map(String::len).sum::<usize>();
+
+		assert!(get_total_length(&base62) < get_total_length(&decimal));
+		assert!(base62.iter().all(|path| path.len() <= 11));
+		assert_eq!(base62.iter().collect::<HashSet<_>>().len(), base62.len());
+
+		let key = ActionKey::<()>::create();
+		let round_tripped = from_value::<ActionKey<()>>(to_value(&key).unwrap()).unwrap();
+
+		assert_eq!(round_tripped.action_path, key.action_path);
+	}
+
+	#[test]
+	fn actions_of_every_client_of_a_root_ui_reach_the_response() {
+		let ui = root_ui(json!({ "key": { "eventPath": ["main"] }, "data": "clicked" }));
+		let key = ActionKey::<String>::create();
+		let mut header = ui.get_client();
+		let mut body = ui.get_client();
+		let event_key = body.ui().event_key::<String>();
+
+		key.emit("header".to_string(), &mut header);
+		key.emit("body".to_string(), &mut body);
+
+		// the event data is shared by both clients, so it can still only be taken once
+		assert_eq!(event_key.take_data(&mut body).unwrap(), "clicked");
+		assert!(matches!(event_key.take_data(&mut header), Err(TakeDataError::DataAlreadyTaken)));
+		drop((header, body));
+
+		let response = ui.into_response();
+		let data = response.actions().iter().map(|action| action["data"].clone()).collect::<Vec<_>>();
+
+		assert_eq!(data, [json!("header"), json!("body")]);
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Toast {
+		message: String,
+		timeout: u32,
+	}
+
+	#[test]
+	fn emitted_actions_deserialize_back_into_typed_actions() {
+		let key = ActionKey::<Toast>::create().with_debug_symbol("toast");
+		let saved = || Toast {
+			message: "saved".to_string(),
+			timeout: 3,
+		};
+		let response = render_once(
+			|_: (), client: &mut Client| {
+				key.emit_with_priority(saved(), 5, client);
+
+				label("root")
+			},
+			(),
+		);
+		let value = response.actions()[0].clone();
+		let action = Action::<Toast>::from_value(value.clone()).unwrap();
+
+		assert_eq!(action.data(), &saved());
+		assert_eq!(to_value(action.key()).unwrap(), to_value(&key).unwrap());
+		assert_eq!(to_value(&action).unwrap(), value);
+		assert!(Action::<Toast>::from_value(json!({ "key": key, "data": "saved" })).is_err());
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	enum Filter {
+		All,
+		Tagged { tag: String, page: u32 },
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct JsonFilter(Filter);
+
+	impl EventSymbol for JsonFilter {
+		fn encoding() -> EventSymbolEncoding {
+			EventSymbolEncoding::Json
+		}
+	}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct HexFilter(Filter);
+
+	impl EventSymbol for HexFilter {}
+
+	#[derive(Debug, PartialEq, Serialize, Deserialize)]
+	struct Base64Filter(Filter);
+
+	impl EventSymbol for Base64Filter {
+		fn encoding() -> EventSymbolEncoding {
+			EventSymbolEncoding::Base64UrlBincode
+		}
+	}
+
+	fn tagged() -> Filter {
+		Filter::Tagged {
+			tag: "urgent".to_string(),
+			page: 2,
+		}
+	}
+
+	#[test]
+	fn enum_symbols_round_trip_through_every_encoding() {
+		let json = JsonFilter(tagged()).to_string();
+		let hex = HexFilter(tagged()).to_string();
+		let base64 = Base64Filter(tagged()).to_string();
+
+		assert_eq!(json, r#"{"Tagged":{"tag":"urgent","page":2}}"#);
+		assert_eq!(JsonFilter::from_string(&json).unwrap(), JsonFilter(tagged()));
+		assert_eq!(JsonFilter::from_string(&JsonFilter(Filter::All).to_string()).unwrap(), JsonFilter(Filter::All));
+		assert_eq!(HexFilter::from_string(&hex).unwrap(), HexFilter(tagged()));
+		assert_eq!(Base64Filter::from_string(&base64).unwrap(), Base64Filter(tagged()));
+		assert!(base64.len() < hex.len());
+	}
+
+	#[test]
+	fn symbols_in_another_encoding_are_rejected() {
+		let hex = HexFilter(tagged()).to_string();
+
+		assert!(matches!(JsonFilter::from_string(&hex), Err(FromStringError::FailedToDeserializeJson { .. })));
+		assert!(matches!(HexFilter::from_string(r#""All""#), Err(FromStringError::FailedToDecodeHex { .. })));
+	}
+
+	#[test]
+	fn actions_are_ordered_by_priority_and_then_by_emission() {
+		let toast = ActionKey::<String>::create();
+		let navigate = ActionKey::<String>::create();
+		let track = ActionKey::<String>::create();
+		let response = render_once(
+			|_: (), client: &mut Client| {
+				track.emit_with_priority("opened".to_string(), 200, client);
+				toast.emit("saved".to_string(), client);
+				navigate.emit_with_priority("/home".to_string(), 10, client);
+				toast.emit("synced".to_string(), client);
+
+				label("root")
+			},
+			(),
+		);
+		let data = response.actions().iter().map(|action| action["data"].clone()).collect::<Vec<_>>();
+
+		assert_eq!(
+			data,
+			[
+				json!("/home"),
+				json!("saved"),
+				json!("synced"),
+				json!({ "type": "Label", "def": { "text": "root" } }),
+				json!("opened"),
+			]
+		);
+	}
+}
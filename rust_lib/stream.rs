@@ -0,0 +1,194 @@
+use futures_util::io::{AsyncRead, AsyncReadExt};
+use serde::de::DeserializeOwned;
+use serde_json::from_slice;
+use std::io;
+use thiserror::Error;
+
+/// The number of bytes that are read from the request body at a time
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Error)]
+pub(crate) enum StreamError {
+	#[error("failed to read the request body; {0}")]
+	Io(#[from] io::Error),
+
+	#[error("the request body ended unexpectedly")]
+	UnexpectedEnd,
+
+	#[error("the request body is missing the `{0}` field")]
+	MissingField(&'static str),
+
+	#[error("expected `{expected}` at byte {position}, but found `{found}`")]
+	UnexpectedByte { expected: char, found: char, position: usize },
+
+	#[error("{0}")]
+	Json(#[from] serde_json::Error),
+}
+
+/// Reads JSON values out of a request body one at a time, so that only the value that is currently being read is held in memory.
+/// This is not a full JSON parser: values are only scanned for their boundaries, and are then deserialized with serde_json.
+pub(crate) struct ValueReader<R> {
+	reader: R,
+	buffer: Vec<u8>,
+	/// The index in `buffer` of the next unread byte
+	cursor: usize,
+	/// The number of bytes that were dropped from the front of `buffer`, for reporting positions
+	dropped: usize,
+	is_done: bool,
+}
+
+impl<R: AsyncRead + Unpin> ValueReader<R> {
+	pub(crate) fn new(reader: R) -> ValueReader<R> {
+		ValueReader {
+			reader,
+			buffer: Vec::new(),
+			cursor: 0,
+			dropped: 0,
+			is_done: false,
+		}
+	}
+
+	/// Skip whitespace, then return the next byte without consuming it. `None` if the body has ended.
+	pub(crate) async fn peek(&mut self) -> Result<Option<u8>, StreamError> {
+		loop {
+			while let Some(byte) = self.buffer.get(self.cursor) {
+				if !byte.is_ascii_whitespace() {
+					return Ok(Some(*byte));
+				}
+
+				self.cursor += 1;
+			}
+
+			if !self.fill().await? {
+				return Ok(None);
+			}
+		}
+	}
+
+	/// Skip whitespace, then consume `expected`
+	pub(crate) async fn expect(&mut self, expected: u8) -> Result<(), StreamError> {
+		match self.peek().await? {
+			Some(byte) if byte == expected => {
+				self.cursor += 1;
+
+				Ok(())
+			}
+			Some(byte) => Err(StreamError::UnexpectedByte {
+				expected: expected as char,
+				found: byte as char,
+				position: self.dropped + self.cursor,
+			}),
+			None => Err(StreamError::UnexpectedEnd),
+		}
+	}
+
+	/// Skip whitespace, then consume `expected` if it is next. Returns whether it was consumed.
+	pub(crate) async fn consume_if(&mut self, expected: u8) -> Result<bool, StreamError> {
+		if self.peek().await? == Some(expected) {
+			self.cursor += 1;
+
+			return Ok(true);
+		}
+
+		Ok(false)
+	}
+
+	/// Read the next complete value and deserialize it into `T`
+	pub(crate) async fn read<T: DeserializeOwned>(&mut self) -> Result<T, StreamError> {
+		if self.peek().await?.is_none() {
+			return Err(StreamError::UnexpectedEnd);
+		}
+
+		let start = self.cursor;
+		let mut end = start;
+		let mut depth = 0_usize;
+		let mut is_in_string = false;
+		let mut is_escaped = false;
+
+		loop {
+			let byte = match self.buffer.get(end) {
+				Some(byte) => *byte,
+				None => {
+					if self.fill().await? {
+						continue;
+					}
+
+					// a scalar at the very end of the body is terminated by the end of the body
+					if depth == 0 && !is_in_string && end > start {
+						break;
+					}
+
+					return Err(StreamError::UnexpectedEnd);
+				}
+			};
+
+			if is_in_string {
+				match byte {
+					_ if is_escaped => is_escaped = false,
+					b'\\' => is_escaped = true,
+					b'"' => is_in_string = false,
+					_ => (),
+				}
+
+				end += 1;
+
+				if !is_in_string && depth == 0 {
+					break;
+				}
+
+				continue;
+			}
+
+			match byte {
+				b'"' => is_in_string = true,
+				b'{' | b'[' => depth += 1,
+				b'}' | b']' if depth == 0 => break,
+				b'}' | b']' => {
+					depth -= 1;
+
+					if depth == 0 {
+						end += 1;
+						break;
+					}
+				}
+				b',' if depth == 0 => break,
+				_ if byte.is_ascii_whitespace() && depth == 0 => break,
+				_ => (),
+			}
+
+			end += 1;
+		}
+
+		let value = from_slice(&self.buffer[start..end])?;
+		self.cursor = end;
+		self.compact();
+
+		Ok(value)
+	}
+
+	/// Read more of the body into the buffer. Returns false if the body has ended.
+	async fn fill(&mut self) -> Result<bool, StreamError> {
+		if self.is_done {
+			return Ok(false);
+		}
+
+		let length = self.buffer.len();
+		self.buffer.resize(length + READ_CHUNK_SIZE, 0);
+
+		let read = self.reader.read(&mut self.buffer[length..]).await?;
+		self.buffer.truncate(length + read);
+
+		if read == 0 {
+			self.is_done = true;
+		}
+
+		Ok(read > 0)
+	}
+
+	/// Drop the bytes that have already been read
+	fn compact(&mut self) {
+		self.buffer.drain(..self.cursor);
+		self.dropped += self.cursor;
+		self.cursor = 0;
+	}
+}